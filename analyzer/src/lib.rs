@@ -228,7 +228,7 @@ pub fn infer_expression_type<'a>(
         Expression::Keyword(_) => ResolvedType::Keyword,
         Expression::SymbolRef(sym) => context
             .borrow()
-            .find_variable_type(sym.name)
+            .find_variable_type(&sym.name)
             .unwrap_or(&ResolvedType::Unknown)
             .clone(),
         Expression::SetLiteral(_) => todo!(),
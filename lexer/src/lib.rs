@@ -3,13 +3,14 @@ pub use token::Token;
 
 use location::{Located, Location, Span};
 use nom::{
-    branch::{alt, permutation},
-    bytes::complete::tag,
-    bytes::complete::{take, take_till, take_till1},
+    branch::alt,
+    bytes::complete::{tag, tag_no_case},
+    bytes::complete::{is_not, take, take_till, take_till1},
     character::complete::{
-        char, digit0, digit1, hex_digit1, line_ending, multispace1, oct_digit1, one_of, space1,
+        alphanumeric1, anychar, char, digit0, digit1, hex_digit1, line_ending, multispace1,
+        oct_digit1, one_of, space1,
     },
-    combinator::{eof, map, map_res, not, opt, recognize},
+    combinator::{consumed, cut, eof, map, map_opt, map_res, not, opt, recognize, verify},
     multi::{many0, many1},
     sequence::{preceded, terminated, tuple, delimited},
     IResult, Parser,
@@ -19,19 +20,40 @@ use nom_locate::position;
 
 type TokenizeResult<'a> = IResult<Span<'a>, Located<Token<'a>>>;
 
-fn comment(input: Span) -> IResult<Span, ()> {
-    map(
-        permutation((
-            char(';'),
-            take_till(|c: char| c == '\r' || c == '\n'),
-            alt((line_ending, eof)),
-        )),
-        |(_, _, _)| (),
-    )(input)
+// the comment text runs from `;` up to, not including, the line ending
+fn comment_text(input: Span) -> IResult<Span, Located<Span>> {
+    located(recognize(preceded(
+        char(';'),
+        take_till(|c: char| c == '\r' || c == '\n'),
+    )))(input)
+}
+
+fn comment(input: Span) -> IResult<Span, Located<Span>> {
+    terminated(comment_text, alt((line_ending, eof)))(input)
+}
+
+// only recognized as the very first line of a file, e.g. `#!/usr/bin/env bb`
+fn shebang_text(input: Span) -> IResult<Span, Located<Span>> {
+    located(recognize(preceded(
+        tag("#!"),
+        take_till(|c: char| c == '\r' || c == '\n'),
+    )))(input)
+}
+
+fn shebang(input: Span) -> IResult<Span, Located<Span>> {
+    terminated(shebang_text, alt((line_ending, eof)))(input)
 }
 
-fn skip0(input: Span) -> IResult<Span, ()> {
-    map(many0(alt((comment, map(multispace1, |_| ())))), |_| ())(input)
+// commas are whitespace in Clojure; the comments skipped over are returned
+fn skip0(input: Span) -> IResult<Span, Vec<Located<Span>>> {
+    map(
+        many0(alt((
+            map(comment, Some),
+            map(multispace1, |_| None),
+            map(char(','), |_| None),
+        ))),
+        |comments| comments.into_iter().flatten().collect(),
+    )(input)
 }
 
 fn located<'a, O>(
@@ -51,7 +73,7 @@ fn located<'a, O>(
                         offset: from.location_offset() as u32,
                     },
                     Location {
-                        col: (to.get_column() + to.len()) as u32,
+                        col: to.get_column() as u32,
                         line: to.location_line() as u32,
                         offset: to.location_offset() as u32,
                     },
@@ -81,69 +103,212 @@ fn rbrace(input: Span) -> TokenizeResult {
     located(map(char('}'), |_| Token::RBrace))(input)
 }
 
-fn char_literal(input: Span) -> TokenizeResult {
-    fn anychar(s: Span) -> IResult<Span, char> {
-        map(take(1usize), |s: Span| s.chars().next().unwrap())(s)
+fn char_value(literal: &str) -> Option<char> {
+    let mut chars = literal.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(c);
+    }
+    match literal {
+        "newline" => Some('\n'),
+        "space" => Some(' '),
+        "tab" => Some('\t'),
+        "backspace" => Some('\u{8}'),
+        "formfeed" => Some('\u{c}'),
+        "return" => Some('\r'),
+        _ => {
+            let (digits, radix, lengths) = if let Some(digits) = literal.strip_prefix('u') {
+                (digits, 16, 4..=4)
+            } else if let Some(digits) = literal.strip_prefix('o') {
+                (digits, 8, 1..=3)
+            } else {
+                return None;
+            };
+            if !lengths.contains(&digits.len()) || !digits.chars().all(|d| d.is_digit(radix)) {
+                return None;
+            }
+            let code = u32::from_str_radix(digits, radix).ok()?;
+            match radix {
+                8 if code > 0o377 => None,
+                _ => char::from_u32(code),
+            }
+        }
     }
-    located(map(preceded(char('\\'), anychar), |c| {
-        Token::CharLiteral(c)
-    }))(input)
 }
 
+// `\a`, `\(`, `\newline`, `\u0041`, `\o101`; as in Clojure the literal runs up to whitespace or a
+// delimiter, so `\ab` is an error rather than `\a` followed by `b`
+fn char_literal(input: Span) -> TokenizeResult {
+    located(map_opt(
+        preceded(
+            char('\\'),
+            recognize(preceded(
+                verify(anychar, |c| !c.is_whitespace()),
+                take_till(|c: char| c.is_whitespace() || "\";@^`~()[]{}\\,".contains(c)),
+            )),
+        ),
+        |literal: Span| char_value(literal.fragment()).map(Token::CharLiteral),
+    ))(input)
+}
+
+// escapes are only skipped over here, so `\"` does not end the literal
+fn string_body(input: Span) -> IResult<Span, Span> {
+    recognize(many0(alt((
+        is_not("\\\""),
+        recognize(preceded(char('\\'), take(1usize))),
+    ))))(input)
+}
+
+// the parser decodes the escapes
 fn string_literal(input: Span) -> TokenizeResult {
     located(map(
-        delimited(char('"'), take_till(|c| c == '"'), char('"')),
+        delimited(char('"'), string_body, char('"')),
         |span| Token::StringLiteral(span),
     ))(input)
 }
 
-fn integer(i: Span) -> TokenizeResult {
-    fn decimal_integer(input: Span) -> TokenizeResult {
-        located(map(
-            map_res(
-                recognize(tuple((one_of("0123456789"), digit0))),
-                |n: Span| i64::from_str_radix(n.fragment(), 10),
-            ),
-            |i| Token::IntegerLiteral(i),
-        ))(input)
-    }
+// the pattern is kept verbatim, `#"\d"` is the two characters `\d`; once `#"` is seen it must
+// be a regex, an unterminated one is not retried as `#` followed by a string
+fn regex_literal(input: Span) -> TokenizeResult {
+    located(map(
+        preceded(tag("#\""), cut(terminated(string_body, char('"')))),
+        |span| Token::RegexLiteral(span),
+    ))(input)
+}
 
-    fn hex_integer(input: Span) -> TokenizeResult {
-        located(map(
-            map_res(preceded(tag("0x"), hex_digit1), |n: Span| {
-                i64::from_str_radix(n.fragment(), 16)
-            }),
-            |i| Token::IntegerLiteral(i),
-        ))(input)
+// numbers end where a symbol could not go on, so `1abc` and `1/` are neither numbers nor symbols
+fn number_end(input: Span) -> IResult<Span, ()> {
+    not(alt((name_chars, tag("/"))))(input)
+}
+
+fn sign(input: Span) -> IResult<Span, Span> {
+    recognize(opt(one_of("+-")))(input)
+}
+
+fn integer(input: Span) -> TokenizeResult {
+    // each yields the literal without its `N` suffix, its signed digits and their radix
+    type Parsed<'a> = (Span<'a>, String, u32);
+
+    fn decimal_integer(input: Span) -> IResult<Span, Parsed> {
+        map(
+            recognize(tuple((
+                sign,
+                alt((recognize(tuple((one_of("123456789"), digit0))), tag("0"))),
+            ))),
+            |n: Span| (n, n.fragment().to_string(), 10),
+        )(input)
     }
 
-    fn oct_integer(input: Span) -> TokenizeResult {
-        located(map(
-            map_res(preceded(tag("0x"), oct_digit1), |n: Span| {
-                i64::from_str_radix(n.fragment(), 8)
-            }),
-            |i| Token::IntegerLiteral(i),
-        ))(input)
+    fn prefixed_integer<'a>(
+        prefix: &'static str,
+        digits: impl Parser<Span<'a>, Span<'a>, nom::error::Error<Span<'a>>>,
+        radix: u32,
+    ) -> impl FnMut(Span<'a>) -> IResult<Span<'a>, Parsed<'a>> {
+        map(
+            consumed(tuple((sign, tag_no_case(prefix), digits))),
+            move |(n, (sign, _, digits))| (n, format!("{}{}", sign, digits), radix),
+        )
     }
 
-    fn bin_integer(input: Span) -> TokenizeResult {
-        located(map(
-            map_res(
-                preceded(tag("0b"), recognize(many1(one_of("01")))),
-                |n: Span| i64::from_str_radix(n.fragment(), 2),
+    // `2r1010`, `36rZZ`
+    fn radix_integer(input: Span) -> IResult<Span, Parsed> {
+        map(
+            verify(
+                consumed(tuple((
+                    sign,
+                    map_res(digit1, |r: Span| r.fragment().parse::<u32>()),
+                    one_of("rR"),
+                    alphanumeric1,
+                ))),
+                |(_, (_, radix, _, digits))| {
+                    (2..=36).contains(radix) && digits.chars().all(|c| c.is_digit(*radix))
+                },
             ),
-            |i| Token::IntegerLiteral(i),
-        ))(input)
+            |(n, (sign, radix, _, digits))| (n, format!("{}{}", sign, digits), radix),
+        )(input)
     }
-    alt((decimal_integer, hex_integer, bin_integer, oct_integer))(i)
+
+    located(map(
+        terminated(
+            tuple((
+                alt((
+                    radix_integer,
+                    prefixed_integer("0x", hex_digit1, 16),
+                    prefixed_integer("0b", recognize(many1(one_of("01"))), 2),
+                    prefixed_integer("0o", oct_digit1, 8),
+                    prefixed_integer("0", oct_digit1, 8),
+                    decimal_integer,
+                )),
+                opt(char('N')),
+            )),
+            number_end,
+        ),
+        // integers that do not fit in an i64 are promoted, as Clojure does
+        |((literal, digits, radix), big)| match (big, i64::from_str_radix(&digits, radix)) {
+            (None, Ok(i)) => Token::IntegerLiteral(i),
+            _ => Token::BigIntLiteral(literal),
+        },
+    ))(input)
 }
 
+// `1.5`, `1e10`, `1.`, and with an `M` suffix (`1.5M`, `1M`) BigDecimals
 fn float(input: Span) -> TokenizeResult {
+    located(map_res(
+        terminated(
+            tuple((
+                recognize(tuple((
+                    sign,
+                    digit1,
+                    opt(tuple((char('.'), digit0))),
+                    opt(tuple((one_of("eE"), sign, digit1))),
+                ))),
+                opt(char('M')),
+            )),
+            number_end,
+        ),
+        |(n, big): (Span, _)| match big {
+            Some(_) => Ok(Token::BigDecimalLiteral(n)),
+            // plain integers are left to `integer`
+            None if !n.fragment().contains(['.', 'e', 'E']) => Err(()),
+            None => n.fragment().parse::<f64>().map(Token::FloatLiteral).map_err(|_| ()),
+        },
+    ))(input)
+}
+
+// `##Inf`, `##-Inf` and `##NaN`, the floats that have no digits to write
+fn symbolic_float(input: Span) -> TokenizeResult {
     located(map(
-        map_res(recognize(tuple((digit1, char('.'), digit0))), |n: Span| {
-            n.fragment().parse::<f64>()
-        }),
-        |v| Token::FloatLiteral(v),
+        terminated(
+            preceded(tag("##"), alt((tag("Inf"), tag("-Inf"), tag("NaN")))),
+            number_end,
+        ),
+        |s: Span| {
+            Token::FloatLiteral(match *s.fragment() {
+                "Inf" => f64::INFINITY,
+                "-Inf" => f64::NEG_INFINITY,
+                _ => f64::NAN,
+            })
+        },
+    ))(input)
+}
+
+// `22/7`; the bare `/` and `ns//` stay symbols since a ratio needs digits on both sides
+fn ratio(input: Span) -> TokenizeResult {
+    located(map_res(
+        terminated(
+            tuple((recognize(tuple((sign, digit1))), char('/'), digit1)),
+            number_end,
+        ),
+        |(numerator, _, denominator): (Span, _, Span)| {
+            match (
+                numerator.fragment().parse::<i64>(),
+                denominator.fragment().parse::<i64>(),
+            ) {
+                (Ok(numerator), Ok(denominator)) if denominator != 0 => {
+                    Ok(Token::RatioLiteral(numerator, denominator))
+                }
+                _ => Err(()),
+            }
+        },
     ))(input)
 }
 
@@ -155,6 +320,26 @@ fn sharp_underscore(input: Span) -> TokenizeResult {
     located(map(tag("#_"), |_| Token::SharpUnderescore))(input)
 }
 
+// the `#:ns`, `#::` or `#::alias` prefix of a namespaced map
+fn namespaced_map(input: Span) -> TokenizeResult {
+    located(map(
+        recognize(tuple((tag("#:"), opt(char(':')), opt(name_chars)))),
+        |s| Token::NamespacedMap(s),
+    ))(input)
+}
+
+fn reader_conditional_splicing(input: Span) -> TokenizeResult {
+    located(map(tag("#?@"), |_| Token::ReaderConditionalSplicing))(input)
+}
+
+fn reader_conditional(input: Span) -> TokenizeResult {
+    located(map(tag("#?"), |_| Token::ReaderConditional))(input)
+}
+
+fn var_quote(input: Span) -> TokenizeResult {
+    located(map(tag("#'"), |_| Token::VarQuote))(input)
+}
+
 fn sharp(input: Span) -> TokenizeResult {
     located(map(char('#'), |_| Token::Sharp))(input)
 }
@@ -172,7 +357,7 @@ fn syntax_quote(input: Span) -> TokenizeResult {
 }
 
 fn at(input: Span) -> TokenizeResult {
-    located(map(terminated(char('@'), space1), |_| Token::And))(input)
+    located(map(char('@'), |_| Token::At))(input)
 }
 
 fn tilde_at(input: Span) -> TokenizeResult {
@@ -183,64 +368,175 @@ fn tilde(input: Span) -> TokenizeResult {
     located(map(char('~'), |_| Token::Tilde))(input)
 }
 
+fn is_name_char(x: char) -> bool {
+    x.is_alphanumeric() || "*+!-_?.<>%=$'&@".contains(x)
+}
+
+// `#` may follow the first char, as in the auto-gensym `x#` of a syntax quote
+fn name_chars(input: Span) -> IResult<Span, Span> {
+    recognize(tuple((
+        take_till1(|x: char| !is_name_char(x)),
+        take_till(|x: char| x != '#' && !is_name_char(x)),
+    )))(input)
+}
+
 fn name(input: Span) -> IResult<Span, Span> {
-    recognize(preceded(
-        not(digit1),
-        take_till1(|x: char| !x.is_alphanumeric() && !"*+!-_?.<>%=$'&@".contains(x)),
-    ))(input)
+    recognize(preceded(not(digit1), name_chars))(input)
 }
 
+// unlike symbols, keyword names may start with a digit (`:1`); as with symbols, a second `/`
+// (`:a/b/c`) is rejected
 fn keyword(input: Span) -> TokenizeResult {
     located(map(
-        recognize(permutation((
-            char(':'),
-            opt(char(':')),
-            opt(terminated(name, char('/'))),
-            name,
-        ))),
+        terminated(
+            recognize(tuple((
+                char(':'),
+                opt(char(':')),
+                opt(terminated(name_chars, char('/'))),
+                name_chars,
+            ))),
+            not(char('/')),
+        ),
         |s| Token::Keyword(s),
     ))(input)
 }
 
+// `name`, `ns/name`, the bare `/` and `ns//`; a second `/` (`a/b/c`) is rejected
 fn symbol(input: Span) -> TokenizeResult {
     located(map(
-        recognize(permutation((opt(terminated(name, char('/'))), name))),
+        terminated(
+            alt((
+                recognize(tuple((name, char('/'), alt((name, tag("/")))))),
+                name,
+                tag("/"),
+            )),
+            not(char('/')),
+        ),
         |s| Token::Symbol(s),
     ))(input)
 }
 
-pub fn tokenize<'a>(input: Span<'a>) -> IResult<Span, Vec<Located<Token<'a>>>> {
-    let mut tokens = Vec::new();
-    let mut rest = input;
-    (rest, _) = skip0(rest)?;
-    while rest.len() > 0 {
-        let token: Located<Token>;
-        (rest, token) = alt((
-            lparen,
-            rparen,
-            lbracket,
-            rbracket,
-            lbrace,
-            rbrace,
+fn next_token(input: Span) -> TokenizeResult {
+    alt((
+        lparen,
+        rparen,
+        lbracket,
+        rbracket,
+        lbrace,
+        rbrace,
+        // reader macro prefixes, nested to stay within nom's alt arity
+        alt((
             quote,
             syntax_quote,
             hat,
             sharp_underscore,
+            symbolic_float,
+            var_quote,
+            namespaced_map,
+            reader_conditional_splicing,
+            reader_conditional,
+            regex_literal,
             sharp,
             at,
             tilde_at,
             tilde,
             and,
-            symbol,
-            keyword,
-            char_literal,
-            string_literal,
-            integer,
-            float,
-        ))(rest)?;
+        )),
+        ratio,
+        float,
+        integer,
+        symbol,
+        keyword,
+        char_literal,
+        string_literal,
+    ))(input)
+    // report where the token that failed to lex starts, not where its last alternative gave up
+    .map_err(|err| err.map_input(|_| input))
+}
+
+pub fn tokenize<'a>(input: Span<'a>) -> IResult<Span, Vec<Located<Token<'a>>>> {
+    let (rest, (tokens, _)) = tokenize_with_comments(input)?;
+    Ok((rest, tokens))
+}
+
+// the tokens, then the comments between them
+type TokensWithComments<'a> = (Vec<Located<Token<'a>>>, Vec<Located<Span<'a>>>);
+
+/// Like [`tokenize`], but also returns the comments it skipped, a leading `#!` line included,
+/// in source order.
+pub fn tokenize_with_comments<'a>(input: Span<'a>) -> IResult<Span<'a>, TokensWithComments<'a>> {
+    let mut tokens = Vec::new();
+    let mut comments = Vec::new();
+    let mut rest = input;
+    if rest.location_offset() == 0 {
+        let shebang_line;
+        (rest, shebang_line) = opt(shebang)(rest)?;
+        comments.extend(shebang_line);
+    }
+    let mut skipped;
+    (rest, skipped) = skip0(rest)?;
+    comments.append(&mut skipped);
+    while rest.len() > 0 {
+        let token;
+        (rest, token) = next_token(rest)?;
         tokens.push(token);
-        (rest, _) = skip0(rest)?;
+        (rest, skipped) = skip0(rest)?;
+        comments.append(&mut skipped);
     }
 
-    Ok((rest, tokens))
+    Ok((rest, (tokens, comments)))
+}
+
+/// A piece of the source as split by [`tokenize_lossless`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Lexeme<'a> {
+    Token(Token<'a>),
+    /// Spaces, line endings and commas.
+    Whitespace(Span<'a>),
+    /// A `;` comment or the `#!` line, without its line ending.
+    Comment(Span<'a>),
+}
+
+/// Splits the whole source into tokens, whitespace and comments, in order. Nothing is skipped, so
+/// the ranges of the lexemes cover the source byte for byte.
+pub fn tokenize_lossless<'a>(input: Span<'a>) -> IResult<Span<'a>, Vec<Located<Lexeme<'a>>>> {
+    fn whitespace(input: Span) -> IResult<Span, Located<Span>> {
+        located(recognize(many1(alt((multispace1, tag(","))))))(input)
+    }
+    fn lexeme<'a>(
+        mut parser: impl Parser<Span<'a>, Located<Span<'a>>, nom::error::Error<Span<'a>>>,
+        kind: fn(Span<'a>) -> Lexeme<'a>,
+    ) -> impl FnMut(Span<'a>) -> IResult<Span<'a>, Located<Lexeme<'a>>> {
+        move |input| {
+            let (rest, text) = parser.parse(input)?;
+            Ok((
+                rest,
+                Located {
+                    range: text.range,
+                    value: kind(text.value),
+                },
+            ))
+        }
+    }
+
+    let mut lexemes = Vec::new();
+    let mut rest = input;
+    if rest.location_offset() == 0 {
+        let shebang_line;
+        (rest, shebang_line) = opt(lexeme(shebang_text, Lexeme::Comment))(rest)?;
+        lexemes.extend(shebang_line);
+    }
+    while rest.len() > 0 {
+        let next;
+        (rest, next) = alt((
+            lexeme(whitespace, Lexeme::Whitespace),
+            lexeme(comment_text, Lexeme::Comment),
+            map(next_token, |token| Located {
+                range: token.range,
+                value: Lexeme::Token(token.value),
+            }),
+        ))(rest)?;
+        lexemes.push(next);
+    }
+    Ok((rest, lexemes))
 }
@@ -1,29 +1,100 @@
-use std::vec;
+use std::fmt::Display;
 
 use location::{Span};
 use token_combinator::TokenParser;
 
 #[derive(Debug, PartialEq, Clone, Copy, TokenParser)]
 pub enum Token<'a> {
+    #[token(expects = "(")]
     LParen,           // (
+    #[token(expects = ")")]
     RParen,           // )
+    #[token(expects = "[")]
     LBracket,         // [
+    #[token(expects = "]")]
     RBracket,         // ]
+    #[token(expects = "{")]
     LBrace,           // {
+    #[token(expects = "}")]
     RBrace,           // }
+    #[token(expects = "'")]
     Quote,            // '
+    #[token(expects = "`")]
     SyntaxQuote,      // `
+    #[token(expects = "^")]
     Hat,              // ^
+    #[token(expects = "#")]
     Sharp,            // #
+    #[token(expects = "&")]
     And,              // &
+    #[token(expects = "@")]
     At,               // @
+    #[token(expects = "~")]
     Tilde,            // ~
+    #[token(expects = "~@")]
     TildeAt,          // ~@
+    #[token(expects = "#_")]
     SharpUnderescore, // #_
+    #[token(expects = "#'")]
+    VarQuote,         // #'
+    #[token(expects = "#?")]
+    ReaderConditional, // #?
+    #[token(expects = "#?@")]
+    ReaderConditionalSplicing, // #?@
     CharLiteral(char),
     StringLiteral(Span<'a>),
-    IntegerLiteral(i64), // 10, 0xFF, 0b01, 0o70...
-    FloatLiteral(f64), // 3.14...
+    RegexLiteral(Span<'a>), // #"pattern"
+    IntegerLiteral(i64), // 10, -1, 0xFF, 017, 2r1010, 0b01, 0o70...
+    FloatLiteral(f64), // 3.14, 1e10...
+    RatioLiteral(i64, i64), // 22/7
+    BigIntLiteral(Span<'a>), // 10N, or an integer too large for i64; the literal without its N
+    BigDecimalLiteral(Span<'a>), // 1.5M; the literal without its M
     Keyword(Span<'a>), // :keyword, ::keyword, ::ns/keyword :key.word ...
     Symbol(Span<'a>),  // symbol, ns/symbol ...
+    NamespacedMap(Span<'a>), // #:ns, #::, #::alias
+}
+
+// renders the token the way it appears in source, used in parse error messages
+impl Display for Token<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::LParen => f.write_str("("),
+            Token::RParen => f.write_str(")"),
+            Token::LBracket => f.write_str("["),
+            Token::RBracket => f.write_str("]"),
+            Token::LBrace => f.write_str("{"),
+            Token::RBrace => f.write_str("}"),
+            Token::Quote => f.write_str("'"),
+            Token::SyntaxQuote => f.write_str("`"),
+            Token::Hat => f.write_str("^"),
+            Token::Sharp => f.write_str("#"),
+            Token::And => f.write_str("&"),
+            Token::At => f.write_str("@"),
+            Token::Tilde => f.write_str("~"),
+            Token::TildeAt => f.write_str("~@"),
+            Token::SharpUnderescore => f.write_str("#_"),
+            Token::VarQuote => f.write_str("#'"),
+            Token::ReaderConditional => f.write_str("#?"),
+            Token::ReaderConditionalSplicing => f.write_str("#?@"),
+            Token::CharLiteral(c) => match c {
+                '\n' => f.write_str("\\newline"),
+                ' ' => f.write_str("\\space"),
+                '\t' => f.write_str("\\tab"),
+                '\u{8}' => f.write_str("\\backspace"),
+                '\u{c}' => f.write_str("\\formfeed"),
+                '\r' => f.write_str("\\return"),
+                c => write!(f, "\\{}", c),
+            },
+            Token::StringLiteral(s) => write!(f, "\"{}\"", s.fragment()),
+            Token::RegexLiteral(s) => write!(f, "#\"{}\"", s.fragment()),
+            Token::IntegerLiteral(i) => write!(f, "{}", i),
+            Token::FloatLiteral(v) => write!(f, "{}", v),
+            Token::RatioLiteral(n, d) => write!(f, "{}/{}", n, d),
+            Token::BigIntLiteral(s) => write!(f, "{}N", s.fragment()),
+            Token::BigDecimalLiteral(s) => write!(f, "{}M", s.fragment()),
+            Token::Keyword(s) | Token::Symbol(s) | Token::NamespacedMap(s) => {
+                f.write_str(s.fragment())
+            }
+        }
+    }
 }
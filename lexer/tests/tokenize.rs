@@ -1,9 +1,83 @@
 use std::fs::read_to_string;
 
-use lexer::tokenize;
+use lexer::{tokenize, tokenize_lossless, Lexeme};
 
 #[test]
 fn tokenize_succeeds_on_various_sources() {
     let source = read_to_string("../examples/handler.clj").unwrap();
     tokenize(source.as_str().into()).unwrap();
 }
+
+#[test]
+fn token_ranges_end_right_after_the_token() {
+    let (_, tokens) = tokenize("(a\n  :bc)".into()).unwrap();
+    let ranges: Vec<_> = tokens
+        .iter()
+        .map(|token| {
+            let (from, to) = token.range;
+            (
+                (from.line, from.col, from.offset),
+                (to.line, to.col, to.offset),
+            )
+        })
+        .collect();
+    assert_eq!(
+        ranges,
+        vec![
+            ((1, 1, 0), (1, 2, 1)),
+            ((1, 2, 1), (1, 3, 2)),
+            ((2, 3, 5), (2, 6, 8)),
+            ((2, 6, 8), (2, 7, 9)),
+        ]
+    );
+}
+
+#[test]
+fn tokenize_lossless_covers_the_source() {
+    let source = read_to_string("../examples/handler.clj").unwrap();
+    let (_, lexemes) = tokenize_lossless(source.as_str().into()).unwrap();
+    let mut offset = 0;
+    for lexeme in &lexemes {
+        assert_eq!(lexeme.range.0.offset, offset);
+        offset = lexeme.range.1.offset;
+    }
+    assert_eq!(offset as usize, source.len());
+}
+
+#[test]
+fn tokenize_lossless_keeps_whitespace_and_comments() {
+    let (_, lexemes) = tokenize_lossless("#!bb\n(a, ;c\n \"; s\")".into()).unwrap();
+    let kinds: Vec<String> = lexemes
+        .iter()
+        .map(|lexeme| match lexeme.value {
+            Lexeme::Token(token) => format!("token {}", token),
+            Lexeme::Whitespace(s) => format!("whitespace {:?}", s.fragment()),
+            Lexeme::Comment(s) => format!("comment {}", s.fragment()),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            "comment #!bb",
+            "whitespace \"\\n\"",
+            "token (",
+            "token a",
+            "whitespace \", \"",
+            "comment ;c",
+            "whitespace \"\\n \"",
+            "token \"; s\"",
+            "token )",
+        ]
+    );
+    assert_eq!(lexemes[7].range.0.offset, 13);
+    assert_eq!(lexemes[7].range.1.offset, 18);
+}
+
+#[test]
+fn tokenize_lossless_reports_where_lexing_failed() {
+    let err = tokenize_lossless("(a \"b".into()).unwrap_err();
+    let nom::Err::Error(err) = err else {
+        panic!("expected a recoverable error");
+    };
+    assert_eq!(err.input.location_offset(), 3);
+}
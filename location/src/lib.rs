@@ -4,6 +4,7 @@ use token_combinator::UnwrapToken;
 pub type Span<'a> = LocatedSpan<&'a str>;
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Location {
     pub line: u32,
     pub col: u32,
@@ -11,6 +12,7 @@ pub struct Location {
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Located<T> {
     pub range: (Location, Location),
     pub value: T,
@@ -24,6 +26,12 @@ impl<'a, T> std::ops::Deref for Located<T> {
     }
 }
 
+impl<T: std::fmt::Display> std::fmt::Display for Located<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
 impl<T> UnwrapToken<T> for Located<T> {
     fn unwrap_token(&self) -> &T {
         &self.value
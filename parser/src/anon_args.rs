@@ -0,0 +1,61 @@
+use crate::ast::{AnonArg, Symbol};
+use crate::reader::{Form, ReadError};
+use crate::AST;
+
+/// Replaces the `%`, `%N` and `%&` symbols in the bodies of `#()` forms with [`AST::AnonArg`]s,
+/// so the arguments a function literal takes can be told from its other symbols. Outside of a
+/// `#()`, `%` is just a symbol and is left alone.
+///
+/// ```
+/// use parser::{ast::AnonArg, read_anon_args, read_str, AST};
+///
+/// let forms = read_anon_args(read_str("(def % #(+ % %3 %&))").unwrap()).unwrap();
+/// let AST::List(items) = &forms[0].value else { unreachable!() };
+/// assert!(matches!(items[1].value, AST::Symbol(_)));
+/// let AST::AnonymousFn(body) = &items[2].value else { unreachable!() };
+/// assert_eq!(body[2].value, AST::AnonArg(AnonArg { index: Some(3), rest: false }));
+/// ```
+pub fn read_anon_args(mut forms: Vec<Form>) -> Result<Vec<Form>, ReadError> {
+    for form in &mut forms {
+        read(form, false)?;
+    }
+    Ok(forms)
+}
+
+pub(crate) fn anon_arg(name: &str) -> Option<AnonArg> {
+    match name.strip_prefix('%')? {
+        "" => Some(AnonArg {
+            index: None,
+            rest: false,
+        }),
+        "&" => Some(AnonArg {
+            index: None,
+            rest: true,
+        }),
+        digits if digits.bytes().all(|b| b.is_ascii_digit()) => Some(AnonArg {
+            index: Some(digits.parse().ok().filter(|&index| index > 0)?),
+            rest: false,
+        }),
+        _ => None,
+    }
+}
+
+fn read(form: &mut Form, in_fn: bool) -> Result<(), ReadError> {
+    let in_fn = match &form.value {
+        AST::AnonymousFn(_) => true,
+        AST::Symbol(Symbol { ns: None, name }) if in_fn && name.starts_with('%') => {
+            form.value = AST::AnonArg(anon_arg(name).ok_or_else(|| ReadError {
+                location: form.range.0,
+                message: "arg literal must be %, %& or %integer".to_owned(),
+                related: None,
+                end: Some(form.range.1),
+            })?);
+            return Ok(());
+        }
+        _ => in_fn,
+    };
+    for child in form.value.children_mut() {
+        read(child, in_fn)?;
+    }
+    Ok(())
+}
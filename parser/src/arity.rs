@@ -0,0 +1,131 @@
+use crate::anon_args::anon_arg;
+use crate::ast::{AnonArg, Symbol};
+use crate::reader::Form;
+use crate::AST;
+
+/// The parameters one arity of a function takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Arity {
+    /// Parameters before the `&`, or all of them.
+    pub fixed: usize,
+    /// Takes any number of arguments after the fixed ones, with `[a & more]`.
+    pub variadic: bool,
+}
+
+/// The arities of a `fn`, `defn`, `defn-` or `defmacro` form in the order they are written, either
+/// the single `[params] body` or each `([params] body)`. The name, docstring and attribute maps
+/// before them are skipped, and metadata on any of them is ignored.
+///
+/// A `#()` has the one arity its highest `%N` implies, whether or not it was read with
+/// [`crate::ParseOptions::anon_args`]. Other forms have no arities.
+///
+/// ```
+/// use parser::{fn_arities, read_str, Arity};
+///
+/// let form = read_str("(defn f \"doc\" {:added 1} ([x] x) ([x & xs] xs))").unwrap().remove(0);
+/// assert_eq!(
+///     fn_arities(&form),
+///     [
+///         Arity { fixed: 1, variadic: false },
+///         Arity { fixed: 1, variadic: true }
+///     ]
+/// );
+/// ```
+pub fn fn_arities(form: &Form) -> Vec<Arity> {
+    let items = match &without_metadata(form).value {
+        AST::List(items) => items,
+        AST::AnonymousFn(body) => return vec![anon_fn_arity(body)],
+        _ => return Vec::new(),
+    };
+    let Some((head, mut rest)) = items.split_first() else {
+        return Vec::new();
+    };
+    let AST::Symbol(Symbol { ns, name }) = &head.value else {
+        return Vec::new();
+    };
+    let is_defn = match (ns.as_deref(), name.as_ref()) {
+        (None | Some("clojure.core"), "fn" | "fn*") => false,
+        (None | Some("clojure.core"), "defn" | "defn-" | "defmacro") => true,
+        _ => return Vec::new(),
+    };
+    let mut skip = |is_skipped: fn(&AST) -> bool| {
+        if let Some((first, after)) = rest.split_first() {
+            if is_skipped(&without_metadata(first).value) {
+                rest = after;
+            }
+        }
+    };
+    skip(|form| matches!(form, AST::Symbol(_)));
+    if is_defn {
+        skip(|form| matches!(form, AST::StringLiteral(_)));
+        skip(|form| matches!(form, AST::Map(_)));
+    }
+    if let Some(AST::Vector(params)) = rest.first().map(|form| &without_metadata(form).value) {
+        return vec![params_arity(params)];
+    }
+    rest.iter()
+        .filter_map(|form| match &without_metadata(form).value {
+            AST::List(arity) => match &without_metadata(arity.first()?).value {
+                AST::Vector(params) => Some(params_arity(params)),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+fn without_metadata<'b, 'a>(form: &'b Form<'a>) -> &'b Form<'a> {
+    match &form.value {
+        AST::Metadata(_, form) => without_metadata(form),
+        _ => form,
+    }
+}
+
+// the lexer only makes an `&` followed by a space or tab into `AST::And`, so `[a &\n b]` has the
+// symbol `&` instead
+fn params_arity(params: &[Form]) -> Arity {
+    let is_and = |param: &Form| match &param.value {
+        AST::And => true,
+        AST::Symbol(Symbol { ns: None, name }) => *name == "&",
+        _ => false,
+    };
+    match params.iter().position(is_and) {
+        Some(fixed) => Arity {
+            fixed,
+            variadic: true,
+        },
+        None => Arity {
+            fixed: params.len(),
+            variadic: false,
+        },
+    }
+}
+
+fn anon_fn_arity(body: &[Form]) -> Arity {
+    fn visit(form: &Form, arity: &mut Arity) {
+        let arg = match &form.value {
+            AST::AnonArg(arg) => Some(*arg),
+            AST::Symbol(Symbol { ns: None, name }) => anon_arg(name),
+            _ => None,
+        };
+        match (arg, &form.value) {
+            (Some(AnonArg { rest: true, .. }), _) => arity.variadic = true,
+            (Some(AnonArg { index, .. }), _) => {
+                arity.fixed = arity.fixed.max(index.unwrap_or(1) as usize)
+            }
+            (None, value) => {
+                for child in value.children() {
+                    visit(child, arity);
+                }
+            }
+        }
+    }
+    let mut arity = Arity {
+        fixed: 0,
+        variadic: false,
+    };
+    for form in body {
+        visit(form, &mut arity);
+    }
+    arity
+}
@@ -1,10 +1,16 @@
+use std::borrow::Cow;
+use std::convert::Infallible;
+
 use location::Located;
 use token_combinator::TokenParser;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Symbol<'a> {
-    pub ns: Option<&'a str>,
-    pub name: &'a str,
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub ns: Option<Cow<'a, str>>,
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub name: Cow<'a, str>,
 }
 
 pub struct Metadata<'a> {
@@ -13,55 +19,190 @@ pub struct Metadata<'a> {
 
 impl Symbol<'_> {
     pub fn fullname(&self) -> String {
-        if let Some(ns) = self.ns {
+        if let Some(ns) = &self.ns {
             format!("{}/{}", ns, self.name)
         } else {
-            self.name.to_owned()
+            self.name.to_string()
         }
     }
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Keyword<'a> {
-    pub ns: Option<&'a str>,
-    pub name: &'a str,
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub ns: Option<Cow<'a, str>>,
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub name: Cow<'a, str>,
+    // `::foo` resolves against the current namespace, `::alias/foo` against an alias
+    pub auto_resolved: bool,
+}
+
+/// `%`, `%2` or `%&` in the body of a `#()`, see [`crate::read_anon_args`].
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnonArg {
+    /// `Some(2)` for `%2`, `None` for `%` and `%&`. `%` is the same argument as `%1`.
+    pub index: Option<u32>,
+    /// `%&`, the rest of the arguments.
+    pub rest: bool,
 }
 
 impl Keyword<'_> {
     pub fn fullname(&self) -> String {
-        if let Some(ns) = self.ns {
-            format!("::{}/{}", ns, self.name)
-        } else {
-            self.name.to_owned()
+        match (self.auto_resolved, &self.ns) {
+            (true, Some(ns)) => format!("::{}/{}", ns, self.name),
+            (true, None) => format!("::{}", self.name),
+            (false, Some(ns)) => format!("{}/{}", ns, self.name),
+            (false, None) => self.name.to_string(),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, TokenParser)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "'de: 'a")))]
 pub enum AST<'a> {
     IntegerLiteral(i64),
     FloatLiteral(f64),
+    RatioLiteral(i64, i64),
+    // arbitrary precision numbers are kept as written, without their `N`/`M` suffix
+    BigIntLiteral(#[cfg_attr(feature = "serde", serde(borrow))] Cow<'a, str>),
+    BigDecimalLiteral(#[cfg_attr(feature = "serde", serde(borrow))] Cow<'a, str>),
     CharLiteral(char),
-    StringLiteral(&'a str),
-    RegexLiteral(&'a str),
+    StringLiteral(String), // with its escapes decoded
+    RegexLiteral(#[cfg_attr(feature = "serde", serde(borrow))] Cow<'a, str>),
     AnonymousFn(Vec<Located<AST<'a>>>),
+    AnonArg(AnonArg),
     List(Vec<Located<AST<'a>>>),
     Vector(Vec<Located<AST<'a>>>),
     Set(Vec<Located<AST<'a>>>),
     Map(Vec<Located<AST<'a>>>),
-    AtomDeref(Symbol<'a>),
+    Deref(Box<Located<AST<'a>>>),
+    VarQuote(Symbol<'a>),
     Symbol(Symbol<'a>),
     And,
-    Unquoted(Symbol<'a>),
-    UnquotedSplicing(Symbol<'a>),
+    Unquoted(Box<Located<AST<'a>>>),
+    UnquotedSplicing(Box<Located<AST<'a>>>),
     Keyword(Keyword<'a>),
-    Metadata(Box<Located<AST<'a>>>),
+    // `^:a ^{:b 1} form`: the stacked metas in source order, and the form they are attached to
+    Metadata(Vec<Located<AST<'a>>>, Box<Located<AST<'a>>>),
     Quoted(Box<Located<AST<'a>>>),
     SyntaxQuoted(Box<Located<AST<'a>>>),
+    // `#inst "..."`, `#my/tag form`
+    TaggedLiteral(Symbol<'a>, Box<Located<AST<'a>>>),
+    // `#?(:clj x :cljs y)` or, when splicing, `#?@(...)`, with every clause kept in source order
+    ReaderConditional(bool, Vec<(Keyword<'a>, Located<AST<'a>>)>),
     Root(Vec<Located<AST<'a>>>),
 }
 
-impl AST<'_> {
+impl<'a> AST<'a> {
+    /// Structural equality that ignores where nested forms were read from, e.g. the two
+    /// `[1]` in `#{[1] [1]}` are the same value. Sets and maps compare regardless of order.
+    pub fn same_value(&self, other: &AST) -> bool {
+        fn same_forms(a: &[Located<AST>], b: &[Located<AST>]) -> bool {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.same_value(b))
+        }
+        fn same_unordered<T>(a: &[T], b: &[T], same: impl Fn(&T, &T) -> bool) -> bool {
+            a.len() == b.len() && a.iter().all(|a| b.iter().any(|b| same(a, b)))
+        }
+        match (self, other) {
+            (AST::AnonymousFn(a), AST::AnonymousFn(b))
+            | (AST::List(a), AST::List(b))
+            | (AST::Vector(a), AST::Vector(b))
+            | (AST::Root(a), AST::Root(b)) => same_forms(a, b),
+            (AST::Set(a), AST::Set(b)) => same_unordered(a, b, |a, b| a.same_value(b)),
+            (AST::Map(a), AST::Map(b)) => {
+                let a = a.chunks(2).collect::<Vec<_>>();
+                let b = b.chunks(2).collect::<Vec<_>>();
+                same_unordered(&a, &b, |a, b| same_forms(a, b))
+            }
+            (AST::Metadata(a_metas, a), AST::Metadata(b_metas, b)) => {
+                same_forms(a_metas, b_metas) && a.same_value(b)
+            }
+            (AST::TaggedLiteral(a_tag, a), AST::TaggedLiteral(b_tag, b)) => {
+                a_tag == b_tag && a.same_value(b)
+            }
+            (AST::ReaderConditional(a_splicing, a), AST::ReaderConditional(b_splicing, b)) => {
+                a_splicing == b_splicing
+                    && a.len() == b.len()
+                    && a.iter()
+                        .zip(b)
+                        .all(|((a_key, a), (b_key, b))| a_key == b_key && a.same_value(b))
+            }
+            (AST::Quoted(a), AST::Quoted(b))
+            | (AST::SyntaxQuoted(a), AST::SyntaxQuoted(b))
+            | (AST::Unquoted(a), AST::Unquoted(b))
+            | (AST::UnquotedSplicing(a), AST::UnquotedSplicing(b))
+            | (AST::Deref(a), AST::Deref(b)) => a.same_value(b),
+            _ => self == other,
+        }
+    }
+
+    /// The forms directly nested in this one, in source order.
+    pub fn children(&self) -> Vec<&Located<AST<'a>>> {
+        match self {
+            AST::AnonymousFn(forms)
+            | AST::List(forms)
+            | AST::Vector(forms)
+            | AST::Set(forms)
+            | AST::Map(forms)
+            | AST::Root(forms) => forms.iter().collect(),
+            AST::ReaderConditional(_, clauses) => clauses.iter().map(|(_, form)| form).collect(),
+            AST::Metadata(metas, form) => metas.iter().chain([form.as_ref()]).collect(),
+            AST::Deref(form)
+            | AST::Unquoted(form)
+            | AST::UnquotedSplicing(form)
+            | AST::Quoted(form)
+            | AST::SyntaxQuoted(form)
+            | AST::TaggedLiteral(_, form) => vec![form],
+            _ => vec![],
+        }
+    }
+
+    pub(crate) fn children_mut(&mut self) -> Vec<&mut Located<AST<'a>>> {
+        match self {
+            AST::AnonymousFn(forms)
+            | AST::List(forms)
+            | AST::Vector(forms)
+            | AST::Set(forms)
+            | AST::Map(forms)
+            | AST::Root(forms) => forms.iter_mut().collect(),
+            AST::ReaderConditional(_, clauses) => {
+                clauses.iter_mut().map(|(_, form)| form).collect()
+            }
+            AST::Metadata(metas, form) => metas.iter_mut().chain([form.as_mut()]).collect(),
+            AST::Deref(form)
+            | AST::Unquoted(form)
+            | AST::UnquotedSplicing(form)
+            | AST::Quoted(form)
+            | AST::SyntaxQuoted(form)
+            | AST::TaggedLiteral(_, form) => vec![form],
+            _ => vec![],
+        }
+    }
+
+    // replaces each of the children with what `f` makes of it, for passes that rebuild forms
+    pub(crate) fn try_map_children<E>(
+        &mut self,
+        mut f: impl FnMut(Located<AST<'a>>) -> Result<Located<AST<'a>>, E>,
+    ) -> Result<(), E> {
+        for child in self.children_mut() {
+            // `And` holds nothing, so it stands in for the child while `f` has it
+            let placeholder = Located {
+                range: child.range,
+                value: AST::And,
+            };
+            *child = f(std::mem::replace(child, placeholder))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn map_children(&mut self, mut f: impl FnMut(Located<AST<'a>>) -> Located<AST<'a>>) {
+        self.try_map_children(|child| Ok::<_, Infallible>(f(child)))
+            .unwrap_or_else(|never| match never {})
+    }
+
     // TODO: define in proc macro later
     pub fn integer_or_none(&self) -> Option<&i64> {
         if let AST::IntegerLiteral(v) = self {
@@ -95,6 +236,15 @@ impl AST<'_> {
         }
     }
 
+    // map literals keep their forms flat; this pairs them up as key/value entries
+    pub fn map_entries_or_none(&self) -> Option<Vec<(&Located<AST<'_>>, &Located<AST<'_>>)>> {
+        if let AST::Map(v) = self {
+            Some(v.chunks_exact(2).map(|kv| (&kv[0], &kv[1])).collect())
+        } else {
+            None
+        }
+    }
+
     pub fn list_or_none(&self) -> Option<&Vec<Located<AST<'_>>>> {
         if let AST::List(v) = self {
             Some(v)
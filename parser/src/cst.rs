@@ -0,0 +1,377 @@
+use std::fmt::Display;
+use std::ops::Range;
+
+use lexer::{tokenize_with_comments, Token};
+use location::{Located, Location, Span};
+
+use crate::reader::{lex_error, read_all, Form, ReadError};
+use crate::{discard, AST};
+
+/// What a [`Node`] was read as. Forms without nested forms, symbols and strings alike, are atoms.
+/// A list is a [`NodeKind::Comment`] when it is a `(comment ...)` form, whose body Clojure reads
+/// but never evaluates, so a formatter can tell it from code like any other comment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum NodeKind {
+    Root,
+    List,
+    Comment,
+    Vector,
+    Map,
+    Set,
+    AnonymousFn,
+    Quoted,
+    SyntaxQuoted,
+    Unquoted,
+    UnquotedSplicing,
+    Deref,
+    Metadata,
+    TaggedLiteral,
+    ReaderConditional,
+    Atom,
+}
+
+impl From<&AST<'_>> for NodeKind {
+    fn from(ast: &AST) -> Self {
+        match ast {
+            AST::Root(_) => NodeKind::Root,
+            AST::List(forms) if is_comment_form(forms) => NodeKind::Comment,
+            AST::List(_) => NodeKind::List,
+            AST::Vector(_) => NodeKind::Vector,
+            AST::Map(_) => NodeKind::Map,
+            AST::Set(_) => NodeKind::Set,
+            AST::AnonymousFn(_) => NodeKind::AnonymousFn,
+            AST::Quoted(_) => NodeKind::Quoted,
+            AST::SyntaxQuoted(_) => NodeKind::SyntaxQuoted,
+            AST::Unquoted(_) => NodeKind::Unquoted,
+            AST::UnquotedSplicing(_) => NodeKind::UnquotedSplicing,
+            AST::Deref(_) => NodeKind::Deref,
+            AST::Metadata(..) => NodeKind::Metadata,
+            AST::TaggedLiteral(..) => NodeKind::TaggedLiteral,
+            AST::ReaderConditional(..) => NodeKind::ReaderConditional,
+            _ => NodeKind::Atom,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum TriviaKind {
+    /// Spaces, line endings and commas.
+    Whitespace,
+    /// A `;` comment or the `#!` line.
+    Comment,
+    /// `#_` and the form it discards, comments in between included.
+    Discard,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Element<'a> {
+    Node(Node<'a>),
+    /// A token that is not a form of its own: a delimiter, a reader macro prefix, or an atom.
+    Token(Located<&'a str>),
+    Trivia(TriviaKind, Located<&'a str>),
+}
+
+/// A form of the concrete syntax tree. Its children cover its range byte for byte, so writing
+/// a node out gives back exactly the source it was read from.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Node<'a> {
+    pub kind: NodeKind,
+    pub range: (Location, Location),
+    pub children: Vec<Element<'a>>,
+}
+
+impl Display for Element<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Element::Node(node) => node.fmt(f),
+            Element::Token(text) | Element::Trivia(_, text) => f.write_str(text.value),
+        }
+    }
+}
+
+impl Display for Node<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.children.iter().try_for_each(|child| child.fmt(f))
+    }
+}
+
+// the location right after `text`, when it starts at `from`; columns count bytes like the lexer's
+fn advance(from: Location, text: &str) -> Location {
+    let col = match text.rsplit_once('\n') {
+        Some((_, last_line)) => last_line.len() as u32 + 1,
+        None => from.col + text.len() as u32,
+    };
+    Location {
+        line: from.line + text.matches('\n').count() as u32,
+        col,
+        offset: from.offset + text.len() as u32,
+    }
+}
+
+fn is_comment_form(forms: &[Form]) -> bool {
+    let Some(AST::Symbol(head)) = forms.first().map(|form| &form.value) else {
+        return false;
+    };
+    matches!(head.ns.as_deref(), None | Some("clojure.core")) && head.name == "comment"
+}
+
+struct Builder<'s, 'a> {
+    src: &'a str,
+    tokens: &'s [Located<Token<'a>>],
+    comments: &'s [Located<Span<'a>>],
+    location: Location,
+}
+
+impl<'s, 'a> Builder<'s, 'a> {
+    fn offset(&self) -> usize {
+        self.location.offset as usize
+    }
+
+    fn leaf(&mut self, end: usize) -> Located<&'a str> {
+        let text = &self.src[self.offset()..end];
+        let from = self.location;
+        self.location = advance(from, text);
+        // tokens and comments before `end` are now covered by this leaf
+        let end = end as u32;
+        while matches!(self.tokens.first(), Some(t) if t.range.0.offset < end) {
+            self.tokens = &self.tokens[1..];
+        }
+        while matches!(self.comments.first(), Some(c) if c.range.0.offset < end) {
+            self.comments = &self.comments[1..];
+        }
+        Located {
+            range: (from, self.location),
+            value: text,
+        }
+    }
+
+    // turns everything up to `end` that is not part of a nested form into leaves
+    fn fill(&mut self, end: usize, children: &mut Vec<Element<'a>>) {
+        while self.offset() < end {
+            let next_token = self.tokens.first().map(|t| t.range.0.offset as usize);
+            let next_comment = self.comments.first().map(|c| c.range.0.offset as usize);
+            let element = if next_comment == Some(self.offset()) {
+                let end = self.comments[0].range.1.offset as usize;
+                Element::Trivia(TriviaKind::Comment, self.leaf(end))
+            } else if next_token == Some(self.offset()) {
+                match self.tokens[0].value {
+                    Token::SharpUnderescore => {
+                        // the source has already been read, so the discarded form is well formed
+                        let (rest, _) = discard(self.tokens).unwrap();
+                        let consumed = self.tokens.len() - rest.len();
+                        let end = self.tokens[consumed - 1].range.1.offset as usize;
+                        Element::Trivia(TriviaKind::Discard, self.leaf(end))
+                    }
+                    _ => {
+                        let end = self.tokens[0].range.1.offset as usize;
+                        Element::Token(self.leaf(end))
+                    }
+                }
+            } else {
+                let gap_end = [next_token, next_comment, Some(end)]
+                    .into_iter()
+                    .flatten()
+                    .filter(|&offset| offset > self.offset())
+                    .min()
+                    .unwrap();
+                Element::Trivia(TriviaKind::Whitespace, self.leaf(gap_end))
+            };
+            children.push(element);
+        }
+    }
+
+    fn node(&mut self, form: &Form<'a>) -> Node<'a> {
+        let mut children = Vec::new();
+        for child in form.value.children() {
+            self.fill(child.range.0.offset as usize, &mut children);
+            children.push(Element::Node(self.node(child)));
+        }
+        self.fill(form.range.1.offset as usize, &mut children);
+        Node {
+            kind: NodeKind::from(&form.value),
+            range: form.range,
+            children,
+        }
+    }
+}
+
+/// Reads the source into a concrete syntax tree that keeps whitespace, comments and discarded
+/// forms as trivia, unlike [`read_str`](crate::read_str). The returned root node covers the
+/// whole source, `root.to_string() == src`.
+pub fn read_str_lossless(src: &str) -> Result<Node<'_>, ReadError> {
+    let (_, (tokens, comments)) =
+        tokenize_with_comments(Span::new(src)).map_err(|err| lex_error(src, err))?;
+    let forms = read_all(&tokens)?;
+    let start = Location {
+        line: 1,
+        col: 1,
+        offset: 0,
+    };
+    let root = Located {
+        range: (start, advance(start, src)),
+        value: AST::Root(forms),
+    };
+    let mut builder = Builder {
+        src,
+        tokens: &tokens,
+        comments: &comments,
+        location: start,
+    };
+    Ok(builder.node(&root))
+}
+
+/// Replaces the bytes in `range` of a source with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn apply(&self, src: &str) -> String {
+        let mut edited = src.to_owned();
+        edited.replace_range(self.range.clone(), &self.text);
+        edited
+    }
+}
+
+impl Element<'_> {
+    pub fn range(&self) -> (Location, Location) {
+        match self {
+            Element::Node(node) => node.range,
+            Element::Token(text) | Element::Trivia(_, text) => text.range,
+        }
+    }
+}
+
+/// The innermost node covering the byte `offset`, such as the form under an editor's cursor.
+/// An offset on a delimiter, whitespace, a comment or a discarded form gives the node they are
+/// in. Ranges end before their end offset, so the offset right after `(a)` is not in it, and
+/// offsets outside of `cst` give `None`.
+pub fn node_at<'n, 'a>(cst: &'n Node<'a>, offset: usize) -> Option<&'n Node<'a>> {
+    let covers = |(start, end): (Location, Location)| {
+        (start.offset as usize..end.offset as usize).contains(&offset)
+    };
+    if !covers(cst.range) {
+        return None;
+    }
+    let mut node = cst;
+    while let Some(child) = node.children.iter().find_map(|child| match child {
+        Element::Node(child) if covers(child.range) => Some(child),
+        _ => None,
+    }) {
+        node = child;
+    }
+    Some(node)
+}
+
+// a copy of the element with its locations shifted and its text sliced again from `src`
+fn relocate<'a>(
+    element: &Element,
+    src: &'a str,
+    shift: &dyn Fn(Location) -> Location,
+) -> Element<'a> {
+    let leaf = |text: &Located<&str>| {
+        let range = (shift(text.range.0), shift(text.range.1));
+        Located {
+            range,
+            value: &src[range.0.offset as usize..range.1.offset as usize],
+        }
+    };
+    match element {
+        Element::Node(node) => Element::Node(Node {
+            kind: node.kind,
+            range: (shift(node.range.0), shift(node.range.1)),
+            children: node
+                .children
+                .iter()
+                .map(|child| relocate(child, src, shift))
+                .collect(),
+        }),
+        Element::Token(text) => Element::Token(leaf(text)),
+        Element::Trivia(kind, text) => Element::Trivia(*kind, leaf(text)),
+    }
+}
+
+/// Updates the tree read from a source after `edit` turned that source into `new_src`. Only the
+/// top-level forms next to the edit are read again, the others are carried over. When the edited
+/// part cannot be read on its own, e.g. it opens a string that ends further on, the whole of
+/// `new_src` is read instead, so the tree is always the one [`read_str_lossless`] would give.
+pub fn reparse<'a>(old: &Node, new_src: &'a str, edit: &TextEdit) -> Result<Node<'a>, ReadError> {
+    let touches = |element: &Element| {
+        let (start, end) = element.range();
+        start.offset as usize <= edit.range.end && edit.range.start <= end.offset as usize
+    };
+    let is_whitespace =
+        |element: &Element| matches!(element, Element::Trivia(TriviaKind::Whitespace, _));
+    let children = &old.children;
+    let (Some(mut first), Some(mut last)) = (
+        children.iter().position(touches),
+        children.iter().rposition(touches),
+    ) else {
+        return read_str_lossless(new_src);
+    };
+    // whitespace around the edited part is read with it, to merge with any it now starts or ends with
+    if first > 0 && is_whitespace(&children[first - 1]) {
+        first -= 1;
+    }
+    if last + 1 < children.len() && is_whitespace(&children[last + 1]) {
+        last += 1;
+    }
+    let old_start = children[first].range().0;
+    let old_end = children[last].range().1;
+    let new_end_offset = old_end.offset as usize + edit.text.len() - edit.range.len();
+    let edited = &new_src[old_start.offset as usize..new_end_offset];
+    // a `#!` line is only skipped at the very start of a file
+    if old.kind != NodeKind::Root || (old_start.offset > 0 && edited.starts_with("#!")) {
+        return read_str_lossless(new_src);
+    }
+    let Ok(edited_root) = read_str_lossless(edited) else {
+        return read_str_lossless(new_src);
+    };
+    let new_end = advance(old_start, edited);
+
+    // `edited` was read as if it started at 1:1
+    let from_edited = |location: Location| Location {
+        line: old_start.line + location.line - 1,
+        col: match location.line {
+            1 => old_start.col + location.col - 1,
+            _ => location.col,
+        },
+        offset: old_start.offset + location.offset,
+    };
+    let after_edited = |location: Location| Location {
+        line: new_end.line + location.line - old_end.line,
+        col: if location.line == old_end.line {
+            new_end.col + location.col - old_end.col
+        } else {
+            location.col
+        },
+        offset: new_end.offset + location.offset - old_end.offset,
+    };
+    let unchanged = |location: Location| location;
+    let children = children[..first]
+        .iter()
+        .map(|child| relocate(child, new_src, &unchanged))
+        .chain(
+            edited_root
+                .children
+                .iter()
+                .map(|child| relocate(child, new_src, &from_edited)),
+        )
+        .chain(
+            children[last + 1..]
+                .iter()
+                .map(|child| relocate(child, new_src, &after_edited)),
+        )
+        .collect();
+    Ok(Node {
+        kind: NodeKind::Root,
+        range: (old.range.0, after_edited(old.range.1)),
+        children,
+    })
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use location::Located;
+
+use crate::ast::Symbol;
+use crate::reader::{Form, ReadError};
+use crate::{is_builtin_tag, AST};
+
+/// Turns the value of a tagged literal, such as the vector of `#point [1 2]`, into the form the
+/// literal stands for, like a function in Clojure's `*data-readers*`.
+pub type DataReader = Box<dyn Fn(Form) -> Form>;
+
+/// Replaces every tagged literal in the forms with what the reader for its tag makes of its
+/// value, innermost literals first. What a reader returns takes the range of the whole literal.
+///
+/// Tags without a reader, other than the built-in `#inst` and `#uuid`, which are kept as read, are
+/// given whole to the default reader, and fail when there is none.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use parser::{ast::Symbol, read_str, read_tagged_literals, DataReader, Form, AST};
+///
+/// fn first(form: Form) -> Form {
+///     match form.value {
+///         AST::Vector(mut forms) => forms.remove(0),
+///         _ => form,
+///     }
+/// }
+///
+/// let mut readers: HashMap<Symbol, DataReader> = HashMap::new();
+/// readers.insert(Symbol { ns: Some("my".into()), name: "first".into() }, Box::new(first));
+///
+/// let forms = read_str("(f #my/first [1 2] #inst \"2020\")").unwrap();
+/// let forms = read_tagged_literals(forms, &readers, None).unwrap();
+/// assert_eq!(forms[0].to_string(), "(f 1 #inst \"2020\")");
+///
+/// let forms = read_str("#my/last [1 2]").unwrap();
+/// let err = read_tagged_literals(forms, &readers, None).unwrap_err();
+/// assert_eq!(err.message, "no reader for tag #my/last");
+/// ```
+pub fn read_tagged_literals<'a>(
+    forms: Vec<Form<'a>>,
+    readers: &HashMap<Symbol, DataReader>,
+    default: Option<&DataReader>,
+) -> Result<Vec<Form<'a>>, ReadError> {
+    forms
+        .into_iter()
+        .map(|form| read(form, readers, default))
+        .collect()
+}
+
+fn read<'a>(
+    mut form: Form<'a>,
+    readers: &HashMap<Symbol, DataReader>,
+    default: Option<&DataReader>,
+) -> Result<Form<'a>, ReadError> {
+    form.value
+        .try_map_children(|form| read(form, readers, default))?;
+    let Located {
+        range,
+        value: AST::TaggedLiteral(tag, value),
+    } = form
+    else {
+        return Ok(form);
+    };
+    let value = match (readers.get(&tag), default) {
+        (Some(reader), _) => reader(*value).value,
+        (None, _) if is_builtin_tag(&tag) => AST::TaggedLiteral(tag, value),
+        (None, Some(default)) => {
+            let literal = Located {
+                range,
+                value: AST::TaggedLiteral(tag, value),
+            };
+            default(literal).value
+        }
+        (None, None) => {
+            return Err(ReadError {
+                location: range.0,
+                message: format!("no reader for tag #{}", tag),
+                related: None,
+                end: Some(range.1),
+            })
+        }
+    };
+    Ok(Located { range, value })
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeSet;
+
+use crate::ast::Keyword;
+use crate::reader::{Form, ReadError};
+use crate::AST;
+
+/// Replaces every reader conditional in the forms, however deeply nested, with the first of its
+/// clauses for one of the features or for `:default`, as Clojure reads them for a platform. The
+/// elements of a `#?@` clause are spliced into the enclosing form, and a conditional with no
+/// such clause reads as nothing.
+///
+/// ```
+/// use std::collections::BTreeSet;
+///
+/// use parser::{read_str, select_features};
+///
+/// let forms = read_str("[#?(:clj 1 :cljs 2) #?@(:default [3 4]) #?(:cljr 5)]").unwrap();
+/// let features = BTreeSet::from(["clj".to_owned()]);
+/// let forms = select_features(forms, &features).unwrap();
+/// assert_eq!(forms[0].to_string(), "[1 3 4]");
+/// ```
+pub fn select_features<'a>(
+    forms: Vec<Form<'a>>,
+    features: &BTreeSet<String>,
+) -> Result<Vec<Form<'a>>, ReadError> {
+    splice(forms, features)?
+        .into_iter()
+        .map(|form| select(form, features))
+        .collect()
+}
+
+fn is_selected(feature: &Keyword, features: &BTreeSet<String>) -> bool {
+    feature.ns.is_none()
+        && !feature.auto_resolved
+        && (feature.name == "default" || features.contains(feature.name.as_ref()))
+}
+
+// replaces the reader conditionals among the forms with what they read as, any number of forms
+fn splice<'a>(
+    forms: Vec<Form<'a>>,
+    features: &BTreeSet<String>,
+) -> Result<Vec<Form<'a>>, ReadError> {
+    let mut spliced = Vec::with_capacity(forms.len());
+    for form in forms {
+        let AST::ReaderConditional(splicing, clauses) = form.value else {
+            spliced.push(form);
+            continue;
+        };
+        let Some((_, form)) = clauses
+            .into_iter()
+            .find(|(feature, _)| is_selected(feature, features))
+        else {
+            continue;
+        };
+        match form.value {
+            AST::List(forms) | AST::Vector(forms) if splicing => {
+                spliced.extend(splice(forms, features)?)
+            }
+            _ => spliced.extend(splice(vec![form], features)?),
+        }
+    }
+    Ok(spliced)
+}
+
+// a form that is not a reader conditional stays a single form once the ones in it are resolved
+fn select<'a>(mut form: Form<'a>, features: &BTreeSet<String>) -> Result<Form<'a>, ReadError> {
+    match &mut form.value {
+        AST::AnonymousFn(forms)
+        | AST::List(forms)
+        | AST::Vector(forms)
+        | AST::Set(forms)
+        | AST::Map(forms)
+        | AST::Root(forms)
+        | AST::Metadata(forms, _) => *forms = splice(std::mem::take(forms), features)?,
+        _ => {}
+    }
+    match &form.value {
+        AST::Map(forms) if forms.len() % 2 != 0 => {
+            return Err(ReadError {
+                location: form.range.0,
+                message: "map literal must contain an even number of forms".to_owned(),
+                related: None,
+                end: Some(form.range.1),
+            })
+        }
+        AST::Metadata(metas, _) if metas.is_empty() => {
+            let AST::Metadata(_, form) = form.value else {
+                unreachable!()
+            };
+            return select(select_one(*form, features)?, features);
+        }
+        _ => {}
+    }
+    form.value
+        .try_map_children(|form| select(select_one(form, features)?, features))?;
+    Ok(form)
+}
+
+// what a quote, deref, tag or metadata applies to has to stay a single form
+fn select_one<'a>(form: Form<'a>, features: &BTreeSet<String>) -> Result<Form<'a>, ReadError> {
+    let range = form.range;
+    let mut forms = splice(vec![form], features)?;
+    match forms.pop() {
+        Some(selected) if forms.is_empty() => Ok(selected),
+        _ => Err(ReadError {
+            location: range.0,
+            message: "reader conditional must read as a single form here".to_owned(),
+            related: None,
+            end: Some(range.1),
+        }),
+    }
+}
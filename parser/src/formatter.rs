@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use crate::cst::{Element, Node, NodeKind, TriviaKind};
+use crate::{read_str_lossless, ReadError};
+
+/// How the lines of a list are indented after the line its head symbol is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    /// Two spaces past the opening paren, e.g. the body of `defn` or `let`.
+    Body,
+    /// Under the first argument when it follows the head on the same line, otherwise under the
+    /// head. This is how calls without a rule are indented.
+    Align,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Rules by head symbol. A qualified head such as `clojure.core/let` also matches its name.
+    pub indents: HashMap<String, Indent>,
+}
+
+// heads of forms with a body, after cljfmt's defaults
+const BODY_INDENTED: &str =
+    "binding case catch comment cond condp def defmacro defmethod defmulti \
+    defn defn- defprotocol defrecord defschema deftype do doseq dotimes doto extend-protocol \
+    extend-type finally fn for if if-let if-not if-some let letfn locking loop ns proxy reify try \
+    when when-first when-let when-not when-some with-open with-redefs";
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indents: BODY_INDENTED
+                .split_whitespace()
+                .map(|name| (name.to_owned(), Indent::Body))
+                .collect(),
+        }
+    }
+}
+
+// where a node's children have started so far in the output, to indent its next line
+struct Frame {
+    kind: NodeKind,
+    start_col: usize,
+    inner_col: Option<usize>,
+    // the line the head is on, and its text when it is an atom
+    head: Option<(usize, Option<String>)>,
+    first_arg: Option<(usize, usize)>,
+}
+
+struct Writer<'o> {
+    options: &'o FormatOptions,
+    out: String,
+    line: usize,
+    col: usize,
+}
+
+impl Writer<'_> {
+    fn push(&mut self, text: &str) {
+        self.out.push_str(text);
+        match text.rsplit_once('\n') {
+            Some((before, last_line)) => {
+                self.line += before.matches('\n').count() + 1;
+                self.col = last_line.chars().count();
+            }
+            None => self.col += text.chars().count(),
+        }
+    }
+
+    fn indent(&self, frame: &Frame) -> usize {
+        let inner_col = frame.inner_col.unwrap_or(frame.start_col);
+        match frame.kind {
+            NodeKind::Root => 0,
+            NodeKind::List | NodeKind::Comment => {
+                let Some((head_line, head)) = &frame.head else {
+                    return inner_col;
+                };
+                let rule = head.as_deref().and_then(|head| {
+                    let indents = &self.options.indents;
+                    let name = head.rsplit_once('/').map_or(head, |(_, name)| name);
+                    indents.get(head).or_else(|| indents.get(name))
+                });
+                match (rule, frame.first_arg) {
+                    (Some(Indent::Body), _) => frame.start_col + 2,
+                    (_, Some((line, col))) if line == *head_line => col,
+                    _ => inner_col,
+                }
+            }
+            _ => inner_col,
+        }
+    }
+
+    // trailing whitespace is dropped from the lines the trivia ends, the line it starts is indented
+    fn line_break(&mut self, whitespace: &str, frame: &Frame) {
+        let mut lines = whitespace.split('\n').peekable();
+        while let Some(line) = lines.next() {
+            if lines.peek().is_none() {
+                let indent = self.indent(frame);
+                self.push(&" ".repeat(indent));
+                self.push(line.trim_start_matches([' ', '\t']));
+            } else {
+                let (line, cr) = match line.strip_suffix('\r') {
+                    Some(line) => (line, "\r"),
+                    None => (line, ""),
+                };
+                let line = if self.out.is_empty() || self.out.ends_with('\n') {
+                    line.trim_matches([' ', '\t'])
+                } else {
+                    line.trim_end_matches([' ', '\t'])
+                };
+                self.push(line);
+                self.push(cr);
+                self.push("\n");
+            }
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        let mut frame = Frame {
+            kind: node.kind,
+            start_col: self.col,
+            inner_col: None,
+            head: None,
+            first_arg: None,
+        };
+        for child in &node.children {
+            match child {
+                Element::Token(token) => {
+                    self.push(token.value);
+                    if frame.head.is_none() {
+                        frame.inner_col = Some(self.col);
+                    }
+                }
+                Element::Trivia(TriviaKind::Whitespace, text) if text.value.contains('\n') => {
+                    self.line_break(text.value, &frame)
+                }
+                Element::Trivia(_, text) => self.push(text.value),
+                Element::Node(child) => {
+                    if frame.head.is_none() {
+                        let text = (child.kind == NodeKind::Atom).then(|| child.to_string());
+                        frame.head = Some((self.line, text));
+                    } else if frame.first_arg.is_none() {
+                        frame.first_arg = Some((self.line, self.col));
+                    }
+                    self.node(child);
+                }
+            }
+        }
+    }
+}
+
+/// Re-indents the source with [`FormatOptions::default`]. See [`format_with`].
+pub fn format(src: &str) -> Result<String, ReadError> {
+    format_with(src, &FormatOptions::default())
+}
+
+/// Re-indents every line of the source after the forms it is nested in, and drops trailing
+/// whitespace. Everything else, comments and blank lines included, is kept as written, so
+/// formatting formatted source changes nothing.
+pub fn format_with(src: &str, options: &FormatOptions) -> Result<String, ReadError> {
+    let root = read_str_lossless(src)?;
+    let mut writer = Writer {
+        options,
+        out: String::with_capacity(src.len()),
+        line: 0,
+        col: 0,
+    };
+    writer.node(&root);
+    Ok(writer.out)
+}
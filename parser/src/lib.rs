@@ -1,28 +1,65 @@
+pub mod anon_args;
+pub mod arity;
 pub mod ast;
+pub mod cst;
+pub mod data_readers;
+pub mod features;
+pub mod formatter;
+pub mod printer;
+pub mod reader;
+pub mod syntax_quote;
+pub mod visit;
 
+pub use anon_args::read_anon_args;
+pub use arity::{fn_arities, Arity};
 pub use ast::AST;
+pub use cst::{node_at, read_str_lossless, reparse, TextEdit};
+pub use data_readers::{read_tagged_literals, DataReader};
+pub use features::select_features;
+pub use formatter::format;
+pub use printer::write_form;
+pub use reader::{
+    read_all, read_edn, read_str, read_str_recovering, read_str_streaming, read_str_with,
+    read_str_with_comments, read_str_with_resolver, Comment, CommentedForm, CommentedForms,
+    Diagnosed, Form, ParseOptions, ReadError, Recovered, Streamed,
+};
+pub use syntax_quote::{resolve_syntax_quotes, SymbolicResolver, SyntaxQuoteResolver};
+pub use visit::{walk, Visitor};
+
 use lexer::Token;
-use location::{Located};
+use location::{Located, Span};
 use token_combinator::{
-    alt, delimited, many0, many0_count, map, map_res, preceded, tuple,
-    TokenParseError, TokenParseErrorKind, TokenParseResult, TokenParser,
+    alt, boxed, cut, delimited, flat_map, many0, many1, map, preceded, recover, terminated, tuple,
+    verify, BoxedParser, TokenParseError, TokenParseErrorKind, TokenParseResult, TokenParser,
 };
 
-type Tokens<'a> = &'a [Located<Token<'a>>];
+// 't is the token slice, 'a the source text the tokens (and so the AST) borrow from
+type Tokens<'t, 'a> = &'t [Located<Token<'a>>];
 
-type ParseResult<'a> = TokenParseResult<'a, Located<Token<'a>>, Located<AST<'a>>>;
-type NotLocatedParseResult<'a> = TokenParseResult<'a, Located<Token<'a>>, AST<'a>>;
+type ParseResult<'t, 'a> = TokenParseResult<'t, Located<Token<'a>>, Located<AST<'a>>>;
+type NotLocatedParseResult<'t, 'a> = TokenParseResult<'t, Located<Token<'a>>, AST<'a>>;
+type ParseErrors<'a> = Vec<TokenParseError<Located<Token<'a>>>>;
 
 use lexer::token::parser::*;
 
-fn located<'a>(
-    mut parser: impl TokenParser<'a, Located<Token<'a>>, AST<'a>>,
-) -> impl FnMut(&'a [Located<Token<'a>>]) -> TokenParseResult<'a, Located<Token<'a>>, Located<AST<'a>>>
-{
-    move |tokens: &'a [Located<Token<'a>>]| {
-        let from = tokens[0].range;
+fn located<'t, 'a: 't>(
+    mut parser: impl TokenParser<'t, Located<Token<'a>>, AST<'a>>,
+) -> impl FnMut(Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    move |tokens: Tokens<'t, 'a>| {
+        let from = match tokens.first() {
+            Some(token) => token.range,
+            None => {
+                return Err(TokenParseError::from_error_kind(
+                    TokenParseErrorKind::NotEnoughToken,
+                ))
+            }
+        };
         let (rest, output) = parser.parse(tokens)?;
-        let to = rest.get(0).unwrap_or(tokens.last().unwrap()).range;
+        // the range ends where the last consumed token does
+        let to = match tokens.len() - rest.len() {
+            0 => from,
+            consumed => tokens[consumed - 1].range,
+        };
         Ok((
             rest,
             Located {
@@ -33,165 +70,424 @@ fn located<'a>(
     }
 }
 
-fn parse_metadata(tokens: Tokens) -> ParseResult {
-    located(map(preceded(hat, parse_form), |form| {
-        AST::Metadata(Box::new(form))
-    }))(tokens)
+fn fragment(span: Span<'_>) -> &str {
+    span.fragment()
 }
 
-fn parse_symbol(tokens: Tokens) -> ParseResult {
-    located(map(symbol, |symbol_str| {
-        let splited = symbol_str.split('/').collect::<Vec<_>>();
-        if splited.len() == 1 {
-            let name = splited[0];
-            return AST::Symbol(ast::Symbol { ns: None, name });
-        } else if splited.len() == 2 {
-            let ns = splited[0];
-            let name = splited[1];
-            return AST::Symbol(ast::Symbol { name, ns: Some(ns) });
-        } else {
-            unreachable!()
+fn parse_metadata<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(|tokens: Tokens<'t, 'a>| {
+        let (rest, (metas, form)) = tuple((many1(preceded(hat, next_form)), next_form))(tokens)?;
+        if let Some(meta) = metas.iter().find(|meta| {
+            !matches!(
+                meta.value,
+                AST::Symbol(_) | AST::Keyword(_) | AST::StringLiteral(_) | AST::Map(_)
+            )
+        }) {
+            return Err(invalid_form(
+                "metadata must be a symbol, keyword, string or map",
+                form_span(tokens, meta),
+            ));
+        }
+        Ok((rest, AST::Metadata(metas, Box::new(form))))
+    })(tokens)
+}
+
+fn parse_symbol<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(map(map(symbol_value, fragment), |symbol_str| {
+        // the lexer only lets through `/`, `name`, `ns/name` and `ns//`
+        match symbol_str.split_once('/') {
+            Some((ns, name)) if !ns.is_empty() => AST::Symbol(ast::Symbol {
+                ns: Some(ns.into()),
+                name: name.into(),
+            }),
+            _ => AST::Symbol(ast::Symbol {
+                ns: None,
+                name: symbol_str.into(),
+            }),
         }
     }))(tokens)
 }
 
-fn parse_atom_deref(tokens: Tokens) -> ParseResult {
-    located(map(preceded(at, parse_symbol), |sym_ast| {
+fn parse_deref<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(map(preceded(at, next_form), |form| AST::Deref(Box::new(form))))(tokens)
+}
+
+fn parse_var_quote<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(map(preceded(var_quote, cut(parse_symbol)), |sym_ast| {
         if let AST::Symbol(sym) = sym_ast.value {
-            AST::AtomDeref(sym)
+            AST::VarQuote(sym)
         } else {
             unreachable!()
         }
     }))(tokens)
 }
 
-fn parse_unquoted_symbol(tokens: Tokens) -> ParseResult {
-    located(map(preceded(tilde, parse_symbol), |sym_ast| {
-        if let AST::Symbol(sym) = sym_ast.value {
-            AST::Unquoted(sym)
-        } else {
-            unreachable!();
-        }
+fn parse_unquoted_form<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(map(preceded(tilde, next_form), |form| {
+        AST::Unquoted(Box::new(form))
     }))(tokens)
 }
 
-fn parse_unquoted_splicing_symbol(tokens: Tokens) -> ParseResult {
-    located(map(preceded(tilde_at, parse_symbol), |sym_ast| {
-        if let AST::Symbol(sym) = sym_ast.value {
-            AST::UnquotedSplicing(sym)
-        } else {
-            unreachable!();
-        }
+fn parse_unquoted_splicing_form<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(map(preceded(tilde_at, next_form), |form| {
+        AST::UnquotedSplicing(Box::new(form))
     }))(tokens)
 }
 
-fn parse_and(tokens: Tokens) -> ParseResult {
+fn parse_and<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
     located(map(and, |_| AST::And))(tokens)
 }
 
-fn parse_keyword(tokens: Tokens) -> ParseResult {
-    located(map(keyword, |keyword_str| {
-        let name = if keyword_str.starts_with("::") {
-            &keyword_str[2..]
-        } else {
-            &keyword_str[1..]
+fn parse_keyword<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(map(map(keyword_value, fragment), |keyword_str| {
+        let (auto_resolved, name) = match keyword_str.strip_prefix("::") {
+            Some(name) => (true, name),
+            None => (false, &keyword_str[1..]),
         };
-        let splited = name.split('/').collect::<Vec<_>>();
-        if splited.len() == 1 {
-            let name = splited[0];
-            return AST::Keyword(ast::Keyword { ns: None, name });
-        } else if splited.len() == 2 {
-            let ns = splited[0];
-            let name = splited[1];
-            return AST::Keyword(ast::Keyword { name, ns: Some(ns) });
-        } else {
-            unreachable!()
-        }
+        let keyword = match name.split_once('/') {
+            Some((ns, name)) => ast::Keyword {
+                ns: Some(ns.into()),
+                name: name.into(),
+                auto_resolved,
+            },
+            None => ast::Keyword {
+                ns: None,
+                name: name.into(),
+                auto_resolved,
+            },
+        };
+        AST::Keyword(keyword)
     }))(tokens)
 }
 
-fn parse_char_literal(tokens: Tokens) -> ParseResult {
+fn parse_char_literal<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
     located(map(char_literal, |c| AST::CharLiteral(*c)))(tokens)
 }
 
-fn parse_string_literal(tokens: Tokens) -> ParseResult {
-    located(map(string_literal, |str| AST::StringLiteral(&str)))(tokens)
+// decodes the escapes the lexer skipped over: `\t \b \n \f \r \" \' \\`, `\uXXXX` and octal up to `\377`
+fn unescape(raw: &str) -> Result<String, String> {
+    let mut decoded = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        // the lexer guarantees a character after every backslash
+        let c = match chars.next().unwrap() {
+            't' => '\t',
+            'b' => '\u{8}',
+            'n' => '\n',
+            'f' => '\u{c}',
+            'r' => '\r',
+            c @ ('"' | '\'' | '\\') => c,
+            'u' => {
+                let digits: String = chars.by_ref().take(4).collect();
+                let valid = digits.len() == 4 && digits.chars().all(|d| d.is_ascii_hexdigit());
+                let code = u32::from_str_radix(&digits, 16).ok().filter(|_| valid);
+                match code.and_then(char::from_u32) {
+                    Some(c) => c,
+                    _ => return Err(format!("invalid unicode escape `\\u{}`", digits)),
+                }
+            }
+            first @ '0'..='7' => {
+                let mut digits = first.to_string();
+                while digits.len() < 3 {
+                    match chars.next_if(|c| ('0'..='7').contains(c)) {
+                        Some(d) => digits.push(d),
+                        None => break,
+                    }
+                }
+                match u32::from_str_radix(&digits, 8) {
+                    Ok(code) if code <= 0o377 => char::from_u32(code).unwrap(),
+                    _ => return Err(format!("octal escape `\\{}` is out of range", digits)),
+                }
+            }
+            c => return Err(format!("unsupported escape character `\\{}`", c)),
+        };
+        decoded.push(c);
+    }
+    Ok(decoded)
 }
 
-fn parse_integer_literal(tokens: Tokens) -> ParseResult {
+fn parse_string_literal<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(|tokens: Tokens<'t, 'a>| {
+        let (rest, raw) = string_literal_value(tokens)?;
+        match unescape(fragment(raw)) {
+            Ok(decoded) => Ok((rest, AST::StringLiteral(decoded))),
+            Err(message) => Err(invalid_form(&message, (0, 1))),
+        }
+    })(tokens)
+}
+
+fn parse_integer_literal<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
     located(map(integer_literal, |i| AST::IntegerLiteral(*i)))(tokens)
 }
 
-fn parse_float_literal(tokens: Tokens) -> ParseResult {
+fn parse_float_literal<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
     located(map(float_literal, |f| AST::FloatLiteral(*f)))(tokens)
 }
 
-fn parse_list(tokens: Tokens) -> ParseResult {
+fn parse_ratio_literal<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(map(ratio_literal, |(n, d)| AST::RatioLiteral(*n, *d)))(tokens)
+}
+
+fn parse_big_int_literal<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(map(big_int_literal_value, |n| {
+        AST::BigIntLiteral(fragment(n).into())
+    }))(tokens)
+}
+
+fn parse_big_decimal_literal<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(map(big_decimal_literal_value, |n| {
+        AST::BigDecimalLiteral(fragment(n).into())
+    }))(tokens)
+}
+
+fn parse_list<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
     located(map(
-        delimited(l_paren, many0(parse_form), r_paren),
+        delimited(l_paren, parse_forms, r_paren),
         |forms| AST::List(forms),
     ))(tokens)
 }
 
-fn parse_vector(tokens: Tokens) -> ParseResult {
+fn parse_vector<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
     located(map(
-        delimited(l_bracket, many0(parse_form), r_bracket),
+        delimited(l_bracket, parse_forms, r_bracket),
         |forms| AST::Vector(forms),
     ))(tokens)
 }
 
-fn parse_map(tokens: Tokens) -> ParseResult {
-    located(map_res(
-        delimited(l_brace, many0(parse_form), r_brace),
-        |res| match res {
-            Ok((rest, kvs)) => {
-                if kvs.len() % 2 != 0 {
-                    return Err(TokenParseError {
-                        errors: vec![TokenParseErrorKind::Other(
-                            "map must have even number of forms".to_owned(),
-                        )],
-                        tokens_consumed: kvs.len(),
-                    });
+// token indices of a form that has already been parsed from `tokens`, for errors reported after the fact
+fn form_span(tokens: Tokens, form: &Located<AST>) -> (usize, usize) {
+    let start = tokens
+        .iter()
+        .position(|token| token.range.0 == form.range.0)
+        .unwrap_or(0);
+    let len = tokens[start..]
+        .iter()
+        .position(|token| token.range.1 == form.range.1)
+        .unwrap_or(0);
+    (start, start + len + 1)
+}
+
+// a form that was read but is not valid Clojure; fatal so `alt` won't try other readers
+fn invalid_form<'a>(
+    message: &str,
+    (start, end): (usize, usize),
+) -> TokenParseError<Located<Token<'a>>> {
+    TokenParseError::from_error_kind(TokenParseErrorKind::Other(message.to_owned()))
+        .with_tokens_consumed(start)
+        .with_span(start, end)
+        .into_fatal()
+}
+
+fn parse_map<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(|tokens: Tokens<'t, 'a>| {
+        let (rest, kvs) = delimited(l_brace, parse_forms, r_brace)(tokens)?;
+        if kvs.len() % 2 != 0 {
+            // points at the closing brace, where the missing value was expected
+            let closing = tokens.len() - rest.len() - 1;
+            return Err(invalid_form(
+                "map literal must contain an even number of forms",
+                (closing, closing + 1),
+            ));
+        }
+        Ok((rest, AST::Map(kvs)))
+    })(tokens)
+}
+
+// `#:ns{:a 1}` qualifies unqualified keys with `ns`, while `#::{:a 1}` and `#::alias{:a 1}`
+// make them auto-resolved. Qualified keys are left alone, except `:_/a` which drops its namespace.
+fn parse_namespaced_map<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(|tokens: Tokens<'t, 'a>| {
+        let (rest, (prefix, map)) = tuple((namespaced_map_value, cut(parse_map)))(tokens)?;
+        let prefix = &fragment(prefix)[2..];
+        let (auto_resolved, ns) = match prefix.strip_prefix(':') {
+            Some(alias) => (true, (!alias.is_empty()).then_some(alias)),
+            None if prefix.is_empty() => {
+                return Err(invalid_form(
+                    "namespaced map must specify a namespace",
+                    (0, 1),
+                ))
+            }
+            None => (false, Some(prefix)),
+        };
+        let AST::Map(mut forms) = map.value else {
+            unreachable!()
+        };
+        for key in forms.iter_mut().step_by(2) {
+            match &mut key.value {
+                AST::Keyword(keyword)
+                    if keyword.ns.as_deref() == Some("_") && !keyword.auto_resolved =>
+                {
+                    keyword.ns = None
                 }
-                Ok((rest, AST::Map(kvs)))
+                AST::Keyword(keyword) if keyword.ns.is_none() && !keyword.auto_resolved => {
+                    keyword.ns = ns.map(Into::into);
+                    keyword.auto_resolved = auto_resolved;
+                }
+                AST::Symbol(symbol) if symbol.ns.as_deref() == Some("_") => symbol.ns = None,
+                // symbols carry no auto-resolved flag, so only `#:ns` qualifies them
+                AST::Symbol(symbol) if symbol.ns.is_none() && !auto_resolved => {
+                    symbol.ns = ns.map(Into::into)
+                }
+                _ => {}
             }
-            Err(err) => Err(err),
-        },
-    ))(tokens)
+        }
+        Ok((rest, AST::Map(forms)))
+    })(tokens)
 }
 
-fn parse_set(tokens: Tokens) -> ParseResult {
+// duplicate elements are left for the reader to find, see `ParseOptions::strict_sets`
+fn parse_set<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
     located(map(
-        tuple((sharp, delimited(l_brace, many0(parse_form), r_brace))),
+        tuple((sharp, delimited(l_brace, parse_forms, r_brace))),
         |(_, forms)| AST::Set(forms),
     ))(tokens)
 }
 
-fn parse_regex_literal(tokens: Tokens) -> ParseResult {
-    located(map(preceded(sharp, string_literal), |str| {
-        AST::RegexLiteral(&str)
+fn parse_regex_literal<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(map(regex_literal_value, |str| {
+        AST::RegexLiteral(fragment(str).into())
     }))(tokens)
 }
 
-fn parse_anonymous_fn(tokens: Tokens) -> ParseResult {
-    located(map(preceded(sharp, many0(parse_list)), |list| {
-        AST::AnonymousFn(list)
-    }))(tokens)
+fn find_anonymous_fn<'b, 'a>(form: &'b Located<AST<'a>>) -> Option<&'b Located<AST<'a>>> {
+    match form.value {
+        AST::AnonymousFn(_) => Some(form),
+        _ => form.children().into_iter().find_map(find_anonymous_fn),
+    }
+}
+
+fn parse_anonymous_fn<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(|tokens: Tokens<'t, 'a>| {
+        let (rest, body) = preceded(sharp, delimited(l_paren, parse_forms, r_paren))(tokens)?;
+        if let Some(nested) = body.iter().find_map(find_anonymous_fn) {
+            return Err(invalid_form(
+                "nested #()s are not allowed",
+                form_span(tokens, nested),
+            ));
+        }
+        Ok((rest, AST::AnonymousFn(body)))
+    })(tokens)
+}
+
+/// The symbol of a tagged literal, without its `#`. Tags start with a letter; `#?` and `#?@` are
+/// reader conditionals.
+pub fn parse_tag<'t, 'a>(
+    tokens: Tokens<'t, 'a>,
+) -> TokenParseResult<'t, Located<Token<'a>>, ast::Symbol<'a>> {
+    verify(
+        map(parse_symbol, |sym_ast| match sym_ast.value {
+            AST::Symbol(sym) => sym,
+            _ => unreachable!(),
+        }),
+        |sym: &ast::Symbol| {
+            sym.ns
+                .as_deref()
+                .unwrap_or(&sym.name)
+                .starts_with(char::is_alphabetic)
+        },
+    )(tokens)
+}
+
+/// Reads the value of a tagged literal, see [`tagged`].
+pub type ValueReader<'t, 'a> = BoxedParser<'t, Located<Token<'a>>, Located<AST<'a>>>;
+
+/// Reads a tagged literal: `tag_parser` reads its tag, and then the value is read with the reader
+/// `dispatch` picks for that tag. A tag it has no reader for fails with `no reader for tag #foo`,
+/// pointing at the tag.
+pub fn tagged<'t, 'a: 't>(
+    mut tag_parser: impl TokenParser<'t, Located<Token<'a>>, ast::Symbol<'a>>,
+    dispatch: impl Fn(&ast::Symbol<'a>) -> Option<ValueReader<'t, 'a>>,
+) -> impl FnMut(Tokens<'t, 'a>) -> NotLocatedParseResult<'t, 'a> {
+    let read_tag = move |tokens: Tokens<'t, 'a>| {
+        let (rest, tag) = tag_parser.parse(tokens)?;
+        match dispatch(&tag) {
+            Some(read_value) => Ok((rest, (tag, read_value))),
+            None => {
+                let message = format!("no reader for tag #{}", tag);
+                Err(invalid_form(&message, (0, tokens.len() - rest.len())))
+            }
+        }
+    };
+    flat_map(read_tag, |(tag, read_value)| {
+        map(read_value, move |value| {
+            AST::TaggedLiteral(tag.clone(), Box::new(value))
+        })
+    })
+}
+
+pub(crate) fn is_builtin_tag(tag: &ast::Symbol) -> bool {
+    tag.ns.is_none() && matches!(tag.name.as_ref(), "inst" | "uuid")
+}
+
+// the tag decides how its form is read: the built-in `#inst` and `#uuid` take a string,
+// any other tag takes whatever form follows
+fn read_builtin_tag<'t, 'a: 't>(tag: &ast::Symbol<'a>) -> Option<ValueReader<'t, 'a>> {
+    Some(if is_builtin_tag(tag) {
+        boxed(cut(parse_string_literal))
+    } else {
+        boxed(cut(next_form))
+    })
+}
+
+fn parse_tagged_literal<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(tagged(preceded(sharp, parse_tag), read_builtin_tag))(tokens)
 }
 
-fn parse_quoted_form(tokens: Tokens) -> ParseResult {
-    located(map(preceded(quote, parse_form), |form| {
+fn parse_reader_conditional<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(|tokens: Tokens<'t, 'a>| {
+        let (rest, (splicing, forms)) = tuple((
+            alt((
+                map(reader_conditional_splicing, |_| true),
+                map(reader_conditional, |_| false),
+            )),
+            cut(delimited(l_paren, parse_forms, r_paren)),
+        ))(tokens)?;
+        if forms.len() % 2 != 0 {
+            let closing = tokens.len() - rest.len() - 1;
+            return Err(invalid_form(
+                "reader conditional must contain feature/form pairs",
+                (closing, closing + 1),
+            ));
+        }
+        let mut clauses = Vec::with_capacity(forms.len() / 2);
+        let mut forms = forms.into_iter();
+        while let (Some(feature), Some(form)) = (forms.next(), forms.next()) {
+            let AST::Keyword(keyword) = feature.value else {
+                return Err(invalid_form(
+                    "reader conditional feature must be a keyword",
+                    form_span(tokens, &feature),
+                ));
+            };
+            if splicing && !matches!(form.value, AST::List(_) | AST::Vector(_)) {
+                return Err(invalid_form(
+                    "spliced form in reader conditional must be a list or vector",
+                    form_span(tokens, &form),
+                ));
+            }
+            clauses.push((keyword, form));
+        }
+        Ok((rest, AST::ReaderConditional(splicing, clauses)))
+    })(tokens)
+}
+
+fn parse_quoted_form<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(map(preceded(quote, next_form), |form| {
         AST::Quoted(Box::new(form))
     }))(tokens)
 }
 
-fn parse_syntax_quoted_form(tokens: Tokens) -> ParseResult {
-    located(map(preceded(syntax_quote, parse_form), |form| {
+fn parse_syntax_quoted_form<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    located(map(preceded(syntax_quote, next_form), |form| {
         AST::SyntaxQuoted(Box::new(form))
     }))(tokens)
 }
 
-pub fn parse_form(tokens: Tokens) -> ParseResult {
+// single-token forms
+fn parse_literal<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
     alt((
         parse_symbol,
         parse_keyword,
@@ -199,37 +495,244 @@ pub fn parse_form(tokens: Tokens) -> ParseResult {
         parse_string_literal,
         parse_integer_literal,
         parse_float_literal,
+        parse_ratio_literal,
+        parse_big_int_literal,
+        parse_big_decimal_literal,
+    ))(tokens)
+}
+
+pub fn parse_form<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    alt((
+        parse_literal,
         parse_list,
         parse_vector,
         parse_map,
+        parse_namespaced_map,
         parse_set,
         parse_regex_literal,
         parse_anonymous_fn,
+        parse_tagged_literal,
+        parse_reader_conditional,
         parse_metadata,
         parse_and,
-        parse_atom_deref,
+        parse_deref,
+        parse_var_quote,
         parse_quoted_form,
-        parse_unquoted_symbol,
-        parse_unquoted_splicing_symbol,
+        parse_unquoted_form,
+        parse_unquoted_splicing_form,
         parse_syntax_quoted_form,
     ))(tokens)
 }
 
-pub fn parse_root(tokens: Tokens) -> NotLocatedParseResult {
+// `#_` drops the form after it. The dropped form may itself be preceded by `#_`,
+// so `#_ #_ a b` drops both `a` and `b`.
+fn discard<'t, 'a>(tokens: Tokens<'t, 'a>) -> TokenParseResult<'t, Located<Token<'a>>, ()> {
+    let mut rest = tokens;
+    while let Ok((after_marker, _)) = sharp_underescore(rest) {
+        let (after_form, _) =
+            next_form(after_marker).map_err(|err| err.relative_to(tokens, after_marker))?;
+        rest = after_form;
+    }
+    Ok((rest, ()))
+}
+
+// the next form that is not discarded
+fn next_form<'t, 'a>(tokens: Tokens<'t, 'a>) -> ParseResult<'t, 'a> {
+    let (rest, _) = discard(tokens)?;
+    parse_form(rest).map_err(|err| err.relative_to(tokens, rest))
+}
+
+// the forms of a collection body, without the discarded ones
+fn parse_forms<'t, 'a>(
+    tokens: Tokens<'t, 'a>,
+) -> TokenParseResult<'t, Located<Token<'a>>, Vec<Located<AST<'a>>>> {
+    terminated(many0(next_form), discard)(tokens)
+}
+
+// the next top-level form, or None when only discarded forms are left
+fn next_root_form<'t, 'a>(
+    tokens: Tokens<'t, 'a>,
+) -> TokenParseResult<'t, Located<Token<'a>>, Option<Located<AST<'a>>>> {
+    let (rest, _) = discard(tokens)?;
+    if rest.is_empty() {
+        return Ok((rest, None));
+    }
+    let (rest, form) = parse_form(rest).map_err(|err| err.relative_to(tokens, rest))?;
+    Ok((rest, Some(form)))
+}
+
+pub fn parse_root<'t, 'a>(tokens: Tokens<'t, 'a>) -> NotLocatedParseResult<'t, 'a> {
+    map(parse_root_forms, AST::Root)(tokens)
+}
+
+// the top-level forms `parse_root` wraps in `AST::Root`
+pub(crate) fn parse_root_forms<'t, 'a>(
+    tokens: Tokens<'t, 'a>,
+) -> TokenParseResult<'t, Located<Token<'a>>, Vec<Located<AST<'a>>>> {
     let mut rest = tokens;
     let mut forms = Vec::new();
-    while !rest.is_empty() {
-        let (rest_tokens, comment_out_count) = many0_count(sharp_underescore)(rest)?;
+    loop {
+        let (rest_tokens, form) =
+            next_root_form(rest).map_err(|err| err.relative_to(tokens, rest))?;
         rest = rest_tokens;
-        for _ in 0..comment_out_count {
-            if !rest.is_empty() {
-                let (rest_tokens, _) = parse_form(rest)?;
-                rest = rest_tokens;
+        match form {
+            Some(form) => forms.push(form),
+            None => break,
+        }
+    }
+    Ok((rest, forms))
+}
+
+fn closing_delimiter<'a>(token: &Token) -> Option<(Token<'a>, &'static str)> {
+    match token {
+        Token::LParen => Some((Token::RParen, ")")),
+        Token::LBracket => Some((Token::RBracket, "]")),
+        Token::LBrace => Some((Token::RBrace, "}")),
+        _ => None,
+    }
+}
+
+// fatal, with `tokens_consumed` at the token where the problem showed
+fn delimiter_error<'a>(
+    kind: TokenParseErrorKind<Located<Token<'a>>>,
+    (start, end): (usize, usize),
+    found_at: usize,
+) -> TokenParseError<Located<Token<'a>>> {
+    TokenParseError {
+        errors: vec![kind],
+        tokens_consumed: found_at,
+        span: Some((start, end)),
+        fatal: true,
+    }
+}
+
+/// Repairs unbalanced delimiters so that only well-formed collections are left to parse, and
+/// reports each repair. A closing delimiter of an outer collection first closes the ones still open
+/// inside it, one that closes no open collection closes the innermost one instead, and one with
+/// nothing open is dropped. Collections still open at the end are closed there. Inserted
+/// delimiters are zero-width.
+pub fn balance_delimiters<'a>(
+    tokens: &[Located<Token<'a>>],
+) -> (Vec<Located<Token<'a>>>, ParseErrors<'a>) {
+    let mut balanced = Vec::with_capacity(tokens.len());
+    let mut errors = Vec::new();
+    // the collections not closed yet: where they were opened and what closes them
+    let mut open: Vec<(usize, Token<'a>, &'static str)> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if let Some((closing, expects)) = closing_delimiter(&token.value) {
+            open.push((i, closing, expects));
+            balanced.push(*token);
+            continue;
+        }
+        if !matches!(token.value, Token::RParen | Token::RBracket | Token::RBrace) {
+            balanced.push(*token);
+            continue;
+        }
+        match open
+            .iter()
+            .rposition(|(_, closing, _)| *closing == token.value)
+        {
+            Some(matching) => {
+                let inside = open.drain(matching + 1..).rev();
+                for (n, (opened, closing, expects)) in inside.enumerate() {
+                    let kind = if n == 0 {
+                        TokenParseErrorKind::MismatchedClose {
+                            open: tokens[opened],
+                            expects,
+                            found: *token,
+                        }
+                    } else {
+                        TokenParseErrorKind::UnclosedOpen {
+                            open: tokens[opened],
+                            expects,
+                        }
+                    };
+                    errors.push(delimiter_error(kind, (opened, i + 1), i));
+                    balanced.push(Located {
+                        range: (token.range.0, token.range.0),
+                        value: closing,
+                    });
+                }
+                open.pop();
+                balanced.push(*token);
             }
+            None => match open.pop() {
+                Some((opened, closing, expects)) => {
+                    let kind = TokenParseErrorKind::MismatchedClose {
+                        open: tokens[opened],
+                        expects,
+                        found: *token,
+                    };
+                    errors.push(delimiter_error(kind, (opened, i + 1), i));
+                    balanced.push(Located {
+                        range: token.range,
+                        value: closing,
+                    });
+                }
+                None => {
+                    let kind = TokenParseErrorKind::UnexpectedClose { found: *token };
+                    errors.push(delimiter_error(kind, (i, i + 1), i));
+                }
+            },
         }
-        let (rest_tokens, form) = parse_form(rest)?;
+    }
+    for (opened, closing, expects) in open.into_iter().rev() {
+        let kind = TokenParseErrorKind::UnclosedOpen {
+            open: tokens[opened],
+            expects,
+        };
+        errors.push(delimiter_error(kind, (opened, opened + 1), tokens.len()));
+        let end = tokens[tokens.len() - 1].range.1;
+        balanced.push(Located {
+            range: (end, end),
+            value: closing,
+        });
+    }
+    (balanced, errors)
+}
+
+// where reading resumes after a top-level form fails: past the token the error points at and out of
+// every collection around it. Expects balanced delimiters.
+fn skip_failed_form<'t, 'a>(
+    tokens: Tokens<'t, 'a>,
+    err: &TokenParseError<Located<Token<'a>>>,
+) -> Tokens<'t, 'a> {
+    let failed_at = err.span.map_or(err.tokens_consumed, |(start, _)| start);
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.value {
+            Token::LParen | Token::LBracket | Token::LBrace => depth += 1,
+            Token::RParen | Token::RBracket | Token::RBrace => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if i >= failed_at && depth == 0 {
+            return &tokens[i + 1..];
+        }
+    }
+    &tokens[tokens.len()..]
+}
+
+/// Like [`parse_root`], but a top-level form that fails to parse is skipped and its error collected,
+/// so the forms after it are still read. Errors point into `tokens`.
+pub fn parse_root_recovering<'t, 'a>(
+    tokens: Tokens<'t, 'a>,
+) -> (Vec<Located<AST<'a>>>, ParseErrors<'a>) {
+    let mut rest = tokens;
+    let mut forms = Vec::new();
+    let mut errors = Vec::new();
+    let mut next = recover(next_root_form, skip_failed_form);
+    while !rest.is_empty() {
+        let consumed = tokens.len() - rest.len();
+        // never fails, errors come back as the output
+        let Ok((rest_tokens, form)) = next(rest) else {
+            unreachable!()
+        };
         rest = rest_tokens;
-        forms.push(form);
+        match form {
+            Ok(Some(form)) => forms.push(form),
+            Ok(None) => {}
+            Err(err) => errors.push(err.with_tokens_consumed(consumed)),
+        }
     }
-    Ok((rest, AST::Root(forms)))
+    (forms, errors)
 }
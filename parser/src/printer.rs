@@ -0,0 +1,161 @@
+use std::fmt::{Display, Formatter, Result, Write};
+
+use location::Located;
+
+use crate::ast::{AnonArg, Keyword, Symbol};
+use crate::AST;
+
+impl Display for Symbol<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str(&self.fullname())
+    }
+}
+
+impl Display for AnonArg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match (self.rest, self.index) {
+            (true, _) => f.write_str("%&"),
+            (false, Some(index)) => write!(f, "%{}", index),
+            (false, None) => f.write_char('%'),
+        }
+    }
+}
+
+impl Display for Keyword<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        // auto-resolved keywords already carry their `::`
+        if !self.auto_resolved {
+            f.write_char(':')?;
+        }
+        f.write_str(&self.fullname())
+    }
+}
+
+fn write_char_literal(f: &mut Formatter<'_>, c: char) -> Result {
+    match c {
+        '\n' => f.write_str("\\newline"),
+        ' ' => f.write_str("\\space"),
+        '\t' => f.write_str("\\tab"),
+        '\u{8}' => f.write_str("\\backspace"),
+        '\u{c}' => f.write_str("\\formfeed"),
+        '\r' => f.write_str("\\return"),
+        c if c.is_control() || c.is_whitespace() => write!(f, "\\u{:04X}", c as u32),
+        c => write!(f, "\\{}", c),
+    }
+}
+
+fn write_string_literal(f: &mut Formatter<'_>, s: &str) -> Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\t' => f.write_str("\\t")?,
+            '\r' => f.write_str("\\r")?,
+            '\u{8}' => f.write_str("\\b")?,
+            '\u{c}' => f.write_str("\\f")?,
+            c if c.is_control() => write!(f, "\\u{:04X}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+fn write_float_literal(f: &mut Formatter<'_>, v: f64) -> Result {
+    if v.is_nan() {
+        f.write_str("##NaN")
+    } else if v.is_infinite() {
+        f.write_str(if v > 0.0 { "##Inf" } else { "##-Inf" })
+    } else {
+        // unlike `{}`, `{:?}` keeps the `.0` of whole numbers so they read back as floats
+        write!(f, "{:?}", v)
+    }
+}
+
+fn write_separated(f: &mut Formatter<'_>, forms: &[Located<AST>], separator: &str) -> Result {
+    for (i, form) in forms.iter().enumerate() {
+        if i > 0 {
+            f.write_str(separator)?;
+        }
+        form.fmt(f)?;
+    }
+    Ok(())
+}
+
+fn write_delimited(
+    f: &mut Formatter<'_>,
+    open: &str,
+    forms: &[Located<AST>],
+    close: &str,
+) -> Result {
+    f.write_str(open)?;
+    write_separated(f, forms, " ")?;
+    f.write_str(close)
+}
+
+/// Renders the form as Clojure source on a single line that reads back as the same form.
+/// Layout, comments and discarded forms are not kept, see [`crate::cst`] for that.
+impl Display for AST<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            AST::IntegerLiteral(i) => write!(f, "{}", i),
+            AST::FloatLiteral(v) => write_float_literal(f, *v),
+            AST::RatioLiteral(n, d) => write!(f, "{}/{}", n, d),
+            AST::BigIntLiteral(s) => write!(f, "{}N", s),
+            AST::BigDecimalLiteral(s) => write!(f, "{}M", s),
+            AST::CharLiteral(c) => write_char_literal(f, *c),
+            AST::StringLiteral(s) => write_string_literal(f, s),
+            AST::RegexLiteral(pattern) => write!(f, "#\"{}\"", pattern),
+            AST::AnonymousFn(forms) => write_delimited(f, "#(", forms, ")"),
+            AST::AnonArg(arg) => arg.fmt(f),
+            AST::List(forms) => write_delimited(f, "(", forms, ")"),
+            AST::Vector(forms) => write_delimited(f, "[", forms, "]"),
+            AST::Set(forms) => write_delimited(f, "#{", forms, "}"),
+            AST::Map(forms) => {
+                f.write_char('{')?;
+                for (i, entry) in forms.chunks(2).enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write_separated(f, entry, " ")?;
+                }
+                f.write_char('}')
+            }
+            AST::Deref(form) => write!(f, "@{}", form),
+            AST::VarQuote(symbol) => write!(f, "#'{}", symbol),
+            AST::Symbol(symbol) => symbol.fmt(f),
+            AST::And => f.write_char('&'),
+            // `~@x` would read back as unquote-splicing
+            AST::Unquoted(form) if matches!(form.value, AST::Deref(_)) => write!(f, "~ {}", form),
+            AST::Unquoted(form) => write!(f, "~{}", form),
+            AST::UnquotedSplicing(form) => write!(f, "~@{}", form),
+            AST::Keyword(keyword) => keyword.fmt(f),
+            AST::Metadata(metas, form) => {
+                for meta in metas {
+                    write!(f, "^{} ", meta)?;
+                }
+                form.fmt(f)
+            }
+            AST::Quoted(form) => write!(f, "'{}", form),
+            AST::SyntaxQuoted(form) => write!(f, "`{}", form),
+            AST::TaggedLiteral(tag, form) => write!(f, "#{} {}", tag, form),
+            AST::ReaderConditional(splicing, clauses) => {
+                f.write_str(if *splicing { "#?@(" } else { "#?(" })?;
+                for (i, (feature, form)) in clauses.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(' ')?;
+                    }
+                    write!(f, "{} {}", feature, form)?;
+                }
+                f.write_char(')')
+            }
+            AST::Root(forms) => write_separated(f, forms, "\n"),
+        }
+    }
+}
+
+/// The form as Clojure source, see the [`Display`] impl of [`AST`].
+pub fn write_form(form: &Located<AST>) -> String {
+    form.to_string()
+}
@@ -0,0 +1,562 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Debug, Formatter};
+
+use lexer::{tokenize, tokenize_with_comments, Token};
+use location::{Located, Location, Span};
+use thiserror::Error;
+
+use token_combinator::{streaming, Needed, TokenParseError, TokenParseErrorKind};
+
+use crate::anon_args::read_anon_args;
+use crate::ast::Symbol;
+use crate::data_readers::{read_tagged_literals, DataReader};
+use crate::features::select_features;
+use crate::syntax_quote::{resolve_syntax_quotes, SyntaxQuoteResolver};
+use crate::{balance_delimiters, parse_root_forms, parse_root_recovering, AST};
+
+pub type Form<'a> = Located<AST<'a>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{message} at line {}, column {}", location.line, location.col)]
+pub struct ReadError {
+    pub location: Location,
+    pub message: String,
+    /// Another position the error is about, such as where the collection a mismatched closing
+    /// delimiter was meant for was opened.
+    pub related: Option<Location>,
+    /// Where the error ends, when it is about a whole form, such as a duplicate set element, so
+    /// an editor can highlight it.
+    pub end: Option<Location>,
+}
+
+fn location_of(span: Span) -> Location {
+    Location {
+        line: span.location_line(),
+        col: span.get_column() as u32,
+        offset: span.location_offset() as u32,
+    }
+}
+
+pub(crate) fn lex_error(src: &str, err: nom::Err<nom::error::Error<Span>>) -> ReadError {
+    match err {
+        nom::Err::Error(err) | nom::Err::Failure(err) => ReadError {
+            location: location_of(err.input),
+            message: match err.input.fragment().chars().next() {
+                // the string lexer only fails when the closing quote is missing
+                Some('"') => "unterminated string".to_owned(),
+                Some('\\') => "invalid character literal".to_owned(),
+                Some('#') if err.input.fragment().starts_with("#\"") => {
+                    "unterminated regex".to_owned()
+                }
+                Some(c) => format!("unexpected character `{}`", c),
+                None => "unexpected end of input".to_owned(),
+            },
+            related: None,
+            end: None,
+        },
+        nom::Err::Incomplete(_) => ReadError {
+            location: location_of(Span::new(src)),
+            message: "unexpected end of input".to_owned(),
+            related: None,
+            end: None,
+        },
+    }
+}
+
+/// Tokenizes and parses the whole source, returning its top-level forms in order.
+/// Comments and a leading `#!` line are skipped.
+pub fn read_str(src: &str) -> Result<Vec<Form<'_>>, ReadError> {
+    let (_, tokens) = tokenize(Span::new(src)).map_err(|err| lex_error(src, err))?;
+    read_all(&tokens)
+}
+
+/// Like [`read_str`], but every syntax quote is passed through the resolver, see
+/// [`SyntaxQuoteResolver`].
+pub fn read_str_with_resolver<'a, R: SyntaxQuoteResolver<'a> + ?Sized>(
+    src: &'a str,
+    resolver: &mut R,
+) -> Result<Vec<Form<'a>>, ReadError> {
+    let forms = read_str(src)?;
+    Ok(forms
+        .into_iter()
+        .map(|form| resolve_syntax_quotes(form, resolver))
+        .collect())
+}
+
+// the reader macro, quoted for messages, of a form that EDN, the data subset of Clojure, has no syntax for
+fn non_edn_macro(form: &AST) -> Option<&'static str> {
+    Some(match form {
+        AST::AnonymousFn(_) => "`#()`",
+        AST::Deref(_) => "`@`",
+        AST::Quoted(_) => "`'`",
+        AST::SyntaxQuoted(_) => "`` ` ``",
+        AST::Unquoted(_) => "`~`",
+        AST::UnquotedSplicing(_) => "`~@`",
+        AST::VarQuote(_) => "`#'`",
+        AST::RegexLiteral(_) => "`#\"\"`",
+        AST::Metadata(..) => "`^`",
+        AST::ReaderConditional(false, _) => "`#?`",
+        AST::ReaderConditional(true, _) => "`#?@`",
+        _ => return None,
+    })
+}
+
+fn find_non_edn<'b, 'a>(form: &'b Form<'a>) -> Option<(&'b Form<'a>, &'static str)> {
+    match non_edn_macro(&form.value) {
+        Some(name) => Some((form, name)),
+        None => form.children().into_iter().find_map(find_non_edn),
+    }
+}
+
+/// Reads EDN, the data subset of Clojure used for configuration and data exchange: collections,
+/// tagged literals, symbols, keywords and scalars. Code-only reader macros such as `#()`, `@`,
+/// `` ` `` and `~` fail with an error at the first one.
+pub fn read_edn(src: &str) -> Result<Vec<Form<'_>>, ReadError> {
+    let forms = read_str(src)?;
+    check_edn(&forms)?;
+    Ok(forms)
+}
+
+fn check_edn(forms: &[Form]) -> Result<(), ReadError> {
+    match forms.iter().find_map(find_non_edn) {
+        Some((form, name)) => Err(ReadError {
+            location: form.range.0,
+            message: format!("{} is not valid in EDN", name),
+            related: None,
+            end: Some(form.range.1),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// How [`read_str_with`] reads, in place of a function for every variant of [`read_str`].
+pub struct ParseOptions<'r> {
+    /// Duplicate keys in a map literal, such as `{:a 1 :a 2}`, fail the read as they do in
+    /// Clojure, rather than being reported in [`Diagnosed::diagnostics`].
+    pub strict_maps: bool,
+    /// The same for duplicate elements in a set literal, such as `#{1 1}`.
+    pub strict_sets: bool,
+    /// Collect the comments into [`Diagnosed::comments`], see [`read_str_with_comments`].
+    pub preserve_comments: bool,
+    /// Source that stops in the middle of a form reads as [`Diagnosed::incomplete`] rather than
+    /// as an error, see [`read_str_streaming`].
+    pub streaming: bool,
+    /// Reject code-only reader macros, see [`read_edn`].
+    pub edn: bool,
+    /// Read the `%` arguments in `#()` bodies as [`AST::AnonArg`]s, see [`read_anon_args`].
+    pub anon_args: bool,
+    /// Platforms to read reader conditionals for, such as `"clj"`, see [`select_features`].
+    /// When empty, every conditional is kept with all its clauses, for tools such as linters that
+    /// look at all of them.
+    pub features: BTreeSet<String>,
+    /// Readers for tagged literals by tag, such as `my/point` for `#my/point [1 2]`, see
+    /// [`read_tagged_literals`]. A reader for `#inst` or `#uuid` takes the place of the built-in
+    /// one.
+    pub data_readers: HashMap<Symbol<'r>, DataReader>,
+    /// Given the whole literal when its tag has no reader. By default it keeps the literal as read;
+    /// without one, such tags fail the read.
+    pub default_data_reader: Option<DataReader>,
+}
+
+impl Debug for ParseOptions<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // readers are closures, so only their tags are shown
+        let data_readers: Vec<_> = self.data_readers.keys().collect();
+        f.debug_struct("ParseOptions")
+            .field("strict_maps", &self.strict_maps)
+            .field("strict_sets", &self.strict_sets)
+            .field("preserve_comments", &self.preserve_comments)
+            .field("streaming", &self.streaming)
+            .field("edn", &self.edn)
+            .field("anon_args", &self.anon_args)
+            .field("features", &self.features)
+            .field("data_readers", &data_readers)
+            .field("default_data_reader", &self.default_data_reader.is_some())
+            .finish()
+    }
+}
+
+/// Reads like [`read_str`], which accepts duplicate map keys but not duplicate set elements, and
+/// any tag.
+impl Default for ParseOptions<'_> {
+    fn default() -> Self {
+        ParseOptions {
+            strict_maps: false,
+            strict_sets: true,
+            preserve_comments: false,
+            streaming: false,
+            edn: false,
+            anon_args: false,
+            features: BTreeSet::new(),
+            data_readers: HashMap::new(),
+            default_data_reader: Some(Box::new(|form| form)),
+        }
+    }
+}
+
+/// What [`read_str_with`] read, and what it found wrong with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnosed<'a> {
+    pub forms: Vec<Form<'a>>,
+    /// In source order.
+    pub diagnostics: Vec<ReadError>,
+    /// Every comment in the source, in order, when [`ParseOptions::preserve_comments`] is set.
+    pub comments: Vec<Comment<'a>>,
+    /// When [`ParseOptions::streaming`] is set and the source stops inside a form, what more
+    /// input is needed. Nothing else is read then.
+    pub incomplete: Option<Needed>,
+}
+
+fn duplicate_error(message: &str, (first, duplicate): (&Form, &Form)) -> ReadError {
+    ReadError {
+        location: duplicate.range.0,
+        message: message.to_owned(),
+        related: Some(first.range.0),
+        end: Some(duplicate.range.1),
+    }
+}
+
+// each form with the same value as an earlier one, such as a repeated set element or map key,
+// paired with the first of them
+fn duplicates<'b, 'a>(
+    forms: impl IntoIterator<Item = &'b Form<'a>>,
+) -> Vec<(&'b Form<'a>, &'b Form<'a>)> {
+    let mut distinct: Vec<&Form> = Vec::new();
+    let mut found = Vec::new();
+    for form in forms {
+        match distinct.iter().find(|prev| prev.same_value(form)) {
+            Some(first) => found.push((*first, form)),
+            None => distinct.push(form),
+        }
+    }
+    found
+}
+
+// repeated set elements and map keys, such as the second `:a` of `{:a 1 "a" 2 :a 3}`, wherever
+// they are nested, sorted into errors and diagnostics by how strict the options are about them
+fn find_duplicates(
+    form: &Form,
+    options: &ParseOptions,
+    errors: &mut Vec<ReadError>,
+    diagnostics: &mut Vec<ReadError>,
+) {
+    let (found, message, strict) = match &form.value {
+        AST::Set(forms) => (
+            duplicates(forms),
+            "duplicate element in set literal",
+            options.strict_sets,
+        ),
+        AST::Map(forms) => (
+            duplicates(forms.iter().step_by(2)),
+            "duplicate key in map literal",
+            options.strict_maps,
+        ),
+        _ => (Vec::new(), "", false),
+    };
+    let found = found.into_iter().map(|pair| duplicate_error(message, pair));
+    if strict {
+        errors.extend(found);
+    } else {
+        diagnostics.extend(found);
+    }
+    for child in form.children() {
+        find_duplicates(child, options, errors, diagnostics);
+    }
+}
+
+// the first duplicate the options are strict about as the error, otherwise all of them in source order
+fn check_duplicates(forms: &[Form], options: &ParseOptions) -> Result<Vec<ReadError>, ReadError> {
+    let (mut errors, mut diagnostics) = (Vec::new(), Vec::new());
+    for form in forms {
+        find_duplicates(form, options, &mut errors, &mut diagnostics);
+    }
+    if let Some(err) = errors.into_iter().min_by_key(|err| err.location.offset) {
+        return Err(err);
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.location.offset);
+    Ok(diagnostics)
+}
+
+fn comments(src: &str) -> Result<Vec<Comment<'_>>, ReadError> {
+    let (_, (_, comments)) =
+        tokenize_with_comments(Span::new(src)).map_err(|err| lex_error(src, err))?;
+    Ok(comments
+        .into_iter()
+        .map(|comment| Located {
+            range: comment.range,
+            value: *comment.value.fragment(),
+        })
+        .collect())
+}
+
+/// Reads the source as the options ask, [`ParseOptions::default`] reading like [`read_str`].
+/// Checks that are not strict report every problem they find as a diagnostic, otherwise the first
+/// one is the error.
+pub fn read_str_with<'a>(src: &'a str, options: &ParseOptions) -> Result<Diagnosed<'a>, ReadError> {
+    let forms = if options.streaming {
+        match stream(src, options)? {
+            Streamed::Complete(forms) => forms,
+            Streamed::Incomplete(needed) => {
+                return Ok(Diagnosed {
+                    forms: Vec::new(),
+                    diagnostics: Vec::new(),
+                    comments: Vec::new(),
+                    incomplete: Some(needed),
+                })
+            }
+        }
+    } else {
+        let (_, tokens) = tokenize(Span::new(src)).map_err(|err| lex_error(src, err))?;
+        parse_all(&tokens)?
+    };
+    if options.edn {
+        check_edn(&forms)?;
+    }
+    let forms = if options.features.is_empty() {
+        forms
+    } else {
+        select_features(forms, &options.features)?
+    };
+    let forms = if options.anon_args {
+        read_anon_args(forms)?
+    } else {
+        forms
+    };
+    let default_data_reader = options.default_data_reader.as_ref();
+    let forms = read_tagged_literals(forms, &options.data_readers, default_data_reader)?;
+    let diagnostics = check_duplicates(&forms, options)?;
+    let comments = if options.preserve_comments {
+        comments(src)?
+    } else {
+        Vec::new()
+    };
+    Ok(Diagnosed {
+        forms,
+        diagnostics,
+        comments,
+        incomplete: None,
+    })
+}
+
+/// A `;` comment or the `#!` line, without its line ending.
+pub type Comment<'a> = Located<&'a str>;
+
+/// A top-level form with the comments before it and inside it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CommentedForm<'a> {
+    pub comments: Vec<Comment<'a>>,
+    pub form: Form<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CommentedForms<'a> {
+    pub forms: Vec<CommentedForm<'a>>,
+    /// Comments after the last form.
+    pub trailing: Vec<Comment<'a>>,
+}
+
+/// Like [`read_str`], but keeps the comments, each attached to the top-level form it
+/// precedes or sits inside.
+pub fn read_str_with_comments(src: &str) -> Result<CommentedForms<'_>, ReadError> {
+    let (_, (tokens, comments)) =
+        tokenize_with_comments(Span::new(src)).map_err(|err| lex_error(src, err))?;
+    let mut comments = comments
+        .into_iter()
+        .map(|comment| Located {
+            range: comment.range,
+            value: *comment.value.fragment(),
+        })
+        .peekable();
+    let forms = read_all(&tokens)?
+        .into_iter()
+        .map(|form| CommentedForm {
+            comments: std::iter::from_fn(|| {
+                comments.next_if(|comment| comment.range.0.offset < form.range.1.offset)
+            })
+            .collect(),
+            form,
+        })
+        .collect();
+    Ok(CommentedForms {
+        forms,
+        trailing: comments.collect(),
+    })
+}
+
+fn parse_error<'a>(
+    tokens: &[Located<Token<'a>>],
+    err: TokenParseError<Located<Token<'a>>>,
+) -> ReadError {
+    let index = err.span.map_or(err.tokens_consumed, |(start, _)| start);
+    // errors past the last token are reported where that token ends
+    let at_index = match (tokens.get(index), tokens.last()) {
+        (Some(token), _) => token.range.0,
+        (None, Some(last)) => last.range.1,
+        (None, None) => Location {
+            line: 1,
+            col: 1,
+            offset: 0,
+        },
+    };
+    // delimiter errors carry their tokens, which may not be in `tokens` once repaired
+    let (location, related) = match err.errors.first() {
+        Some(TokenParseErrorKind::MismatchedClose { open, found, .. }) => {
+            (found.range.0, Some(open.range.0))
+        }
+        Some(TokenParseErrorKind::UnexpectedClose { found }) => (found.range.0, None),
+        Some(TokenParseErrorKind::UnclosedOpen { open, .. }) => (open.range.0, None),
+        _ => (at_index, None),
+    };
+    let message = match err.errors.first() {
+        Some(kind) => kind.to_string(),
+        None => "malformed input".to_owned(),
+    };
+    ReadError {
+        location,
+        message,
+        related,
+        end: None,
+    }
+}
+
+/// Parses already tokenized source into its top-level forms.
+pub fn read_all<'a>(tokens: &[Located<Token<'a>>]) -> Result<Vec<Form<'a>>, ReadError> {
+    let forms = parse_all(tokens)?;
+    check_duplicates(&forms, &ParseOptions::default())?;
+    Ok(forms)
+}
+
+// `read_all` without the duplicate checks, which are up to the caller
+fn parse_all<'a>(tokens: &[Located<Token<'a>>]) -> Result<Vec<Form<'a>>, ReadError> {
+    match parse_root_forms(tokens) {
+        Ok((_, forms)) => Ok(forms),
+        Err(err) => {
+            // an unbalanced delimiter at or before the failure explains it better than whatever
+            // the parser expected there
+            let failed_at = err.span.map_or(err.tokens_consumed, |(start, _)| start);
+            let (_, delimiter_errors) = balance_delimiters(tokens);
+            let err = delimiter_errors
+                .into_iter()
+                .find(|delimiter_err| {
+                    delimiter_err
+                        .span
+                        .is_some_and(|(start, _)| start <= failed_at)
+                })
+                .unwrap_or(err);
+            Err(parse_error(tokens, err))
+        }
+    }
+}
+
+/// The forms [`read_str_recovering`] could read, and every error it found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recovered<'a> {
+    pub forms: Vec<Form<'a>>,
+    /// In source order.
+    pub diagnostics: Vec<ReadError>,
+}
+
+/// Like [`read_str`], but keeps reading after an error, for source that is still being typed.
+/// Missing and mismatched delimiters are repaired as [`crate::balance_delimiters`] does, so the
+/// forms around them keep their structure, and top-level forms that still fail are left out. A lexer
+/// error, such as an unterminated string, is reported on its own with no forms.
+pub fn read_str_recovering(src: &str) -> Recovered<'_> {
+    let tokens = match tokenize(Span::new(src)) {
+        Ok((_, tokens)) => tokens,
+        Err(err) => {
+            return Recovered {
+                forms: Vec::new(),
+                diagnostics: vec![lex_error(src, err)],
+            }
+        }
+    };
+    let (tokens, delimiter_errors) = balance_delimiters(&tokens);
+    let (forms, errors) = parse_root_recovering(&tokens);
+    let mut diagnostics: Vec<_> = delimiter_errors
+        .into_iter()
+        .chain(errors)
+        .map(|err| parse_error(&tokens, err))
+        .collect();
+    // a form with a duplicate `read_str` fails on is left out like one that failed to parse
+    let options = ParseOptions::default();
+    let forms = forms
+        .into_iter()
+        .filter(|form| {
+            let checked = check_duplicates(std::slice::from_ref(form), &options);
+            checked.map_err(|err| diagnostics.push(err)).is_ok()
+        })
+        .collect();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.location.offset);
+    Recovered { forms, diagnostics }
+}
+
+/// What [`read_str_streaming`] read.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Streamed<'a> {
+    Complete(Vec<Form<'a>>),
+    /// The source stops inside a form that more input could finish.
+    Incomplete(Needed),
+}
+
+// strings, regexes and characters cut off by the end of the source
+fn lex_incomplete(err: &nom::Err<nom::error::Error<Span>>) -> bool {
+    match err {
+        nom::Err::Error(err) | nom::Err::Failure(err) => {
+            let rest = *err.input.fragment();
+            rest.starts_with('"') || rest.starts_with("#\"") || rest == "\\"
+        }
+        nom::Err::Incomplete(_) => true,
+    }
+}
+
+/// Like [`read_str`], but source that stops in the middle of a form, like a REPL line still being
+/// typed, reads as [`Streamed::Incomplete`] rather than as an error. When only delimiters are
+/// missing, their number is the [`Needed::Size`]. Errors are left for source no more input fixes.
+pub fn read_str_streaming(src: &str) -> Result<Streamed<'_>, ReadError> {
+    let options = ParseOptions::default();
+    let streamed = stream(src, &options)?;
+    if let Streamed::Complete(forms) = &streamed {
+        check_duplicates(forms, &options)?;
+    }
+    Ok(streamed)
+}
+
+// `read_str_streaming` leaving the duplicates in complete source to the caller, while those in
+// unfinished source are checked as the options ask, since its forms are not returned
+fn stream<'a>(src: &'a str, options: &ParseOptions) -> Result<Streamed<'a>, ReadError> {
+    let tokens = match tokenize(Span::new(src)) {
+        Ok((_, tokens)) => tokens,
+        Err(err) if lex_incomplete(&err) => return Ok(Streamed::Incomplete(Needed::Unknown)),
+        Err(err) => return Err(lex_error(src, err)),
+    };
+    let (balanced, delimiter_errors) = balance_delimiters(&tokens);
+    // only collections still open at the end can be closed by more input
+    if let Some(err) = delimiter_errors
+        .iter()
+        .find(|err| err.tokens_consumed < tokens.len())
+    {
+        return Err(parse_error(&tokens, err.clone()));
+    }
+    if delimiter_errors.is_empty() {
+        return match streaming(parse_root_forms)(&tokens) {
+            Ok((_, forms)) => Ok(Streamed::Complete(forms)),
+            Err(err) => match err.errors.first() {
+                Some(TokenParseErrorKind::Incomplete(needed)) => Ok(Streamed::Incomplete(*needed)),
+                _ => Err(parse_error(&tokens, err)),
+            },
+        };
+    }
+    // the inserted delimiters all come after the source, so failing there needs more input too
+    match parse_root_forms(&balanced) {
+        Err(err) if err.span.map_or(err.tokens_consumed, |(start, _)| start) < tokens.len() => {
+            Err(parse_error(&balanced, err))
+        }
+        Ok((_, forms)) => {
+            check_duplicates(&forms, options)?;
+            Ok(Streamed::Incomplete(Needed::Size(delimiter_errors.len())))
+        }
+        Err(_) => Ok(Streamed::Incomplete(Needed::Size(delimiter_errors.len()))),
+    }
+}
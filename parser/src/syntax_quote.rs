@@ -0,0 +1,102 @@
+use crate::ast::Symbol;
+use crate::reader::Form;
+use crate::AST;
+
+/// Hooks called by [`resolve_syntax_quotes`] for the forms of a syntax quote, so a caller with
+/// namespace context can decide what they stand for. They return their form unchanged by default,
+/// see [`SymbolicResolver`].
+///
+/// Names taken from the source are borrowed from it; a resolver that makes up names of its own,
+/// such as gensyms, gives them as `Cow::Owned`.
+///
+/// ```
+/// use parser::{ast::Symbol, read_str_with_resolver, Form, SyntaxQuoteResolver, AST};
+///
+/// struct Qualify(&'static str);
+///
+/// impl<'a> SyntaxQuoteResolver<'a> for Qualify {
+///     fn resolve_symbol(&mut self, form: Form<'a>, symbol: &Symbol<'a>) -> Form<'a> {
+///         match &symbol.ns {
+///             Some(_) => form,
+///             None => Form {
+///                 value: AST::Symbol(Symbol {
+///                     ns: Some(self.0.into()),
+///                     name: symbol.name.clone(),
+///                 }),
+///                 ..form
+///             },
+///         }
+///     }
+/// }
+///
+/// let forms = read_str_with_resolver("(f `(g ~x))", &mut Qualify("user")).unwrap();
+/// assert_eq!(forms[0].to_string(), "(f `(user/g ~x))");
+/// ```
+#[allow(unused_variables)]
+pub trait SyntaxQuoteResolver<'a> {
+    /// Called before the forms of every syntax quote, nested ones included, e.g. to give it its
+    /// own auto-gensyms.
+    fn enter_syntax_quote(&mut self) {}
+    /// A symbol other than an auto-gensym, qualified or not.
+    fn resolve_symbol(&mut self, form: Form<'a>, symbol: &Symbol<'a>) -> Form<'a> {
+        form
+    }
+    /// An unqualified symbol ending in `#`, such as `x#`. Its name keeps the `#`.
+    fn resolve_gensym(&mut self, form: Form<'a>, symbol: &Symbol<'a>) -> Form<'a> {
+        form
+    }
+    /// `~x` or `~@x`. What they unquote is evaluated rather than quoted, so its symbols are left
+    /// alone; only the syntax quotes nested in it are resolved, before this is called.
+    fn resolve_unquote(&mut self, form: Form<'a>) -> Form<'a> {
+        form
+    }
+}
+
+/// Leaves symbols, auto-gensyms and unquotes as they were written, which is how
+/// [`crate::read_str`] reads them.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SymbolicResolver;
+
+impl SyntaxQuoteResolver<'_> for SymbolicResolver {}
+
+/// Rebuilds the form with every syntax quote in it, however deeply nested, passed through the
+/// resolver. Forms outside of syntax quotes are kept as they are.
+pub fn resolve_syntax_quotes<'a, R: SyntaxQuoteResolver<'a> + ?Sized>(
+    form: Form<'a>,
+    resolver: &mut R,
+) -> Form<'a> {
+    resolve(form, resolver, false)
+}
+
+fn resolve<'a, R: SyntaxQuoteResolver<'a> + ?Sized>(
+    mut form: Form<'a>,
+    resolver: &mut R,
+    quoted: bool,
+) -> Form<'a> {
+    match &form.value {
+        AST::Symbol(symbol) if quoted => {
+            let symbol = symbol.clone();
+            if symbol.ns.is_none() && symbol.name.len() > 1 && symbol.name.ends_with('#') {
+                resolver.resolve_gensym(form, &symbol)
+            } else {
+                resolver.resolve_symbol(form, &symbol)
+            }
+        }
+        AST::Unquoted(_) | AST::UnquotedSplicing(_) if quoted => {
+            form.value
+                .map_children(|form| resolve(form, resolver, false));
+            resolver.resolve_unquote(form)
+        }
+        AST::SyntaxQuoted(_) => {
+            resolver.enter_syntax_quote();
+            form.value
+                .map_children(|form| resolve(form, resolver, true));
+            form
+        }
+        _ => {
+            form.value
+                .map_children(|form| resolve(form, resolver, quoted));
+            form
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::ast::{AnonArg, Keyword, Symbol};
+use crate::reader::Form;
+use crate::AST;
+
+/// Hooks called by [`walk`], one per kind of form. They do nothing by default, so a visitor only
+/// overrides the kinds it cares about; [`walk`] takes care of descending into nested forms.
+///
+/// ```
+/// use parser::{ast::Symbol, read_str, walk, Form, Visitor};
+///
+/// #[derive(Default)]
+/// struct Symbols<'a>(Vec<Symbol<'a>>);
+///
+/// impl<'a> Visitor<'a> for Symbols<'a> {
+///     fn visit_symbol(&mut self, _: &Form<'a>, symbol: &Symbol<'a>) {
+///         self.0.push(symbol.clone());
+///     }
+/// }
+///
+/// let mut symbols = Symbols::default();
+/// for form in read_str("(defn f [x] (inc x))").unwrap() {
+///     walk(&form, &mut symbols);
+/// }
+/// let names: Vec<_> = symbols.0.iter().map(|s| s.name.as_ref()).collect();
+/// assert_eq!(names, ["defn", "f", "x", "inc", "x"]);
+/// ```
+#[allow(unused_variables)]
+pub trait Visitor<'a> {
+    /// Called for every form, before the hook for its kind.
+    fn visit_form(&mut self, form: &Form<'a>) {}
+    fn visit_list(&mut self, form: &Form<'a>, items: &[Form<'a>]) {}
+    fn visit_vector(&mut self, form: &Form<'a>, items: &[Form<'a>]) {}
+    /// `forms` are the keys and values, flat and in source order.
+    fn visit_map(&mut self, form: &Form<'a>, forms: &[Form<'a>]) {}
+    fn visit_set(&mut self, form: &Form<'a>, items: &[Form<'a>]) {}
+    fn visit_anonymous_fn(&mut self, form: &Form<'a>, body: &[Form<'a>]) {}
+    fn visit_anon_arg(&mut self, form: &Form<'a>, arg: &AnonArg) {}
+    fn visit_symbol(&mut self, form: &Form<'a>, symbol: &Symbol<'a>) {}
+    fn visit_keyword(&mut self, form: &Form<'a>, keyword: &Keyword<'a>) {}
+    /// Integers, floats, ratios and arbitrary precision numbers.
+    fn visit_number(&mut self, form: &Form<'a>) {}
+    fn visit_string(&mut self, form: &Form<'a>, value: &str) {}
+    fn visit_char(&mut self, form: &Form<'a>, value: char) {}
+    fn visit_regex(&mut self, form: &Form<'a>, pattern: &str) {}
+    fn visit_quote(&mut self, form: &Form<'a>, quoted: &Form<'a>) {}
+    fn visit_syntax_quote(&mut self, form: &Form<'a>, quoted: &Form<'a>) {}
+    fn visit_unquote(&mut self, form: &Form<'a>, unquoted: &Form<'a>) {}
+    fn visit_unquote_splicing(&mut self, form: &Form<'a>, unquoted: &Form<'a>) {}
+    fn visit_deref(&mut self, form: &Form<'a>, target: &Form<'a>) {}
+    fn visit_var_quote(&mut self, form: &Form<'a>, var: &Symbol<'a>) {}
+    fn visit_metadata(&mut self, form: &Form<'a>, metas: &[Form<'a>], target: &Form<'a>) {}
+    fn visit_tagged_literal(&mut self, form: &Form<'a>, tag: &Symbol<'a>, value: &Form<'a>) {}
+    fn visit_reader_conditional(
+        &mut self,
+        form: &Form<'a>,
+        splicing: bool,
+        clauses: &[(Keyword<'a>, Form<'a>)],
+    ) {
+    }
+}
+
+/// Calls the visitor's hooks for `form` and then, in source order, for every form nested in it.
+pub fn walk<'a, V: Visitor<'a> + ?Sized>(form: &Form<'a>, visitor: &mut V) {
+    visitor.visit_form(form);
+    match &form.value {
+        AST::List(items) => visitor.visit_list(form, items),
+        AST::Vector(items) => visitor.visit_vector(form, items),
+        AST::Map(forms) => visitor.visit_map(form, forms),
+        AST::Set(items) => visitor.visit_set(form, items),
+        AST::AnonymousFn(body) => visitor.visit_anonymous_fn(form, body),
+        AST::AnonArg(arg) => visitor.visit_anon_arg(form, arg),
+        AST::Symbol(symbol) => visitor.visit_symbol(form, symbol),
+        AST::Keyword(keyword) => visitor.visit_keyword(form, keyword),
+        AST::IntegerLiteral(_)
+        | AST::FloatLiteral(_)
+        | AST::RatioLiteral(..)
+        | AST::BigIntLiteral(_)
+        | AST::BigDecimalLiteral(_) => visitor.visit_number(form),
+        AST::StringLiteral(value) => visitor.visit_string(form, value),
+        AST::CharLiteral(value) => visitor.visit_char(form, *value),
+        AST::RegexLiteral(pattern) => visitor.visit_regex(form, pattern),
+        AST::Quoted(quoted) => visitor.visit_quote(form, quoted),
+        AST::SyntaxQuoted(quoted) => visitor.visit_syntax_quote(form, quoted),
+        AST::Unquoted(unquoted) => visitor.visit_unquote(form, unquoted),
+        AST::UnquotedSplicing(unquoted) => visitor.visit_unquote_splicing(form, unquoted),
+        AST::Deref(target) => visitor.visit_deref(form, target),
+        AST::VarQuote(var) => visitor.visit_var_quote(form, var),
+        AST::Metadata(metas, target) => visitor.visit_metadata(form, metas, target),
+        AST::TaggedLiteral(tag, value) => visitor.visit_tagged_literal(form, tag, value),
+        AST::ReaderConditional(splicing, clauses) => {
+            visitor.visit_reader_conditional(form, *splicing, clauses)
+        }
+        AST::And | AST::Root(_) => {}
+    }
+    for child in form.value.children() {
+        walk(child, visitor);
+    }
+}
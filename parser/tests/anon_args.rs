@@ -0,0 +1,55 @@
+use parser::{ast::AnonArg, read_anon_args, read_str, read_str_with, ParseOptions, AST};
+
+fn anon_fn_body(src: &str) -> Vec<AST<'_>> {
+    let options = ParseOptions {
+        anon_args: true,
+        ..ParseOptions::default()
+    };
+    let mut forms = read_str_with(src, &options).unwrap().forms;
+    let AST::AnonymousFn(body) = forms.remove(0).value else {
+        panic!("expected anonymous fn")
+    };
+    body.into_iter().map(|form| form.value).collect()
+}
+
+fn arg(index: Option<u32>, rest: bool) -> AST<'static> {
+    AST::AnonArg(AnonArg { index, rest })
+}
+
+#[test]
+fn percent_symbols_in_anonymous_fns_are_args() {
+    let body = anon_fn_body("#(apply f % %2 %10 %&)");
+    assert_eq!(
+        body[2..],
+        [
+            arg(None, false),
+            arg(Some(2), false),
+            arg(Some(10), false),
+            arg(None, true)
+        ]
+    );
+    // however deeply they are nested in the body, and written back as they were read
+    let body = anon_fn_body("#(let [[a b] %1] {:a '% :b [%&]})");
+    assert!(matches!(&body[1], AST::Vector(items) if items[1].value == arg(Some(1), false)));
+    let AST::Map(forms) = &body[2] else {
+        panic!("expected map")
+    };
+    assert!(matches!(&forms[1].value, AST::Quoted(form) if form.value == arg(None, false)));
+    assert_eq!(body[2].to_string(), "{:a '%, :b [%&]}");
+}
+
+#[test]
+fn percent_symbols_outside_anonymous_fns_are_symbols() {
+    let forms = read_anon_args(read_str("(fn [%] (+ % ns/%1))").unwrap()).unwrap();
+    assert_eq!(forms, read_str("(fn [%] (+ % ns/%1))").unwrap());
+    assert_eq!(anon_fn_body("#(+ ns/% %)")[1].to_string(), "ns/%");
+}
+
+#[test]
+fn malformed_args_are_invalid() {
+    for src in ["#(%a)", "#(%0)", "#(%-1)", "#(%1.5)"] {
+        let err = read_anon_args(read_str(src).unwrap()).unwrap_err();
+        assert_eq!(err.message, "arg literal must be %, %& or %integer");
+        assert_eq!(err.location.offset, 2, "reading {:?}", src);
+    }
+}
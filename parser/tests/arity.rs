@@ -0,0 +1,59 @@
+use parser::{fn_arities, read_str, read_str_with, Arity, ParseOptions};
+
+fn arities(src: &str) -> Vec<(usize, bool)> {
+    let form = read_str(src).unwrap().remove(0);
+    fn_arities(&form)
+        .into_iter()
+        .map(|Arity { fixed, variadic }| (fixed, variadic))
+        .collect()
+}
+
+#[test]
+fn single_arity_fns() {
+    assert_eq!(arities("(fn [] 1)"), [(0, false)]);
+    assert_eq!(arities("(fn named [a [b c] {:keys [d]}] d)"), [(3, false)]);
+    assert_eq!(arities("(defn f [a & more] more)"), [(1, true)]);
+    let src = "(defn- f \"doc\" {:private true} [& xs] xs)";
+    assert_eq!(arities(src), [(0, true)]);
+    assert_eq!(arities("(clojure.core/fn [a b])"), [(2, false)]);
+    // an `&` at the end of a line
+    assert_eq!(arities("(fn [a &\n b] a)"), [(1, true)]);
+    assert_eq!(arities("(fn [a\n &\n\n more] a)"), [(1, true)]);
+    // metadata on the name or the parameters is skipped
+    let src = "(defn ^:private f ^String [^long n] n)";
+    assert_eq!(arities(src), [(1, false)]);
+}
+
+#[test]
+fn multi_arity_fns() {
+    let src = "(defmacro m \"doc\" ([a] a) (^:x [a b] b) ([a b & cs] cs) {:trailing :attrs})";
+    assert_eq!(arities(src), [(1, false), (2, false), (2, true)]);
+    assert_eq!(arities("(fn f ([] 0) ([x] x))"), [(0, false), (1, false)]);
+}
+
+#[test]
+fn other_forms_have_no_arities() {
+    for src in ["(def f 1)", "(f [x] x)", "[fn [x]]", "()", "(fn)"] {
+        assert_eq!(arities(src), [], "reading {:?}", src);
+    }
+}
+
+#[test]
+fn anonymous_fns_have_the_arity_of_their_args() {
+    let options = ParseOptions {
+        anon_args: true,
+        ..ParseOptions::default()
+    };
+    for (src, arity) in [
+        ("#(rand)", (0, false)),
+        ("#(inc %)", (1, false)),
+        ("#(+ % [%3])", (3, false)),
+        ("#(apply f %2 %&)", (2, true)),
+    ] {
+        let form = read_str_with(src, &options).unwrap().forms.remove(0);
+        let Arity { fixed, variadic } = fn_arities(&form)[0];
+        assert_eq!((fixed, variadic), arity, "reading {:?}", src);
+        // the same without reading the args as such
+        assert_eq!(arities(src), [arity]);
+    }
+}
@@ -0,0 +1,206 @@
+use std::fs::read_to_string;
+
+use location::Location;
+use parser::{
+    cst::{Element, Node, NodeKind, TriviaKind},
+    node_at, read_str_lossless, reparse, TextEdit,
+};
+
+fn trivia<'a>(node: &Node<'a>) -> Vec<(TriviaKind, &'a str)> {
+    node.children
+        .iter()
+        .filter_map(|child| match child {
+            Element::Trivia(kind, text) => Some((*kind, text.value)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn nodes<'n, 'a>(node: &'n Node<'a>) -> Vec<&'n Node<'a>> {
+    node.children
+        .iter()
+        .filter_map(|child| match child {
+            Element::Node(node) => Some(node),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn lossless_round_trip() {
+    let sources = [
+        read_to_string("../examples/handler.clj").unwrap(),
+        read_to_string("../examples/simple-source-for-analyzer.clj").unwrap(),
+        "#!/usr/bin/env bb\n(ns a) ; trailing\n\n,,#_ #_ (x ; in discard\n) y\n^:m #{1\t2}"
+            .to_owned(),
+        "  \r\n(comment (foo))\n'[a `(b ~c ~@d) @e #'f]\n#?(:clj 1 #_2 :cljs 3)".to_owned(),
+        "".to_owned(),
+    ];
+    for src in &sources {
+        let root = read_str_lossless(src).unwrap();
+        assert_eq!(&root.to_string(), src);
+    }
+}
+
+#[test]
+fn lossless_keeps_trivia() {
+    let root = read_str_lossless("; about\n(f #_ x, 1) ; done\n").unwrap();
+    assert_eq!(root.kind, NodeKind::Root);
+    assert_eq!(
+        trivia(&root),
+        vec![
+            (TriviaKind::Comment, "; about"),
+            (TriviaKind::Whitespace, "\n"),
+            (TriviaKind::Whitespace, " "),
+            (TriviaKind::Comment, "; done"),
+            (TriviaKind::Whitespace, "\n"),
+        ]
+    );
+    let list = nodes(&root)[0];
+    assert_eq!(list.kind, NodeKind::List);
+    assert_eq!(
+        trivia(list),
+        vec![
+            (TriviaKind::Whitespace, " "),
+            (TriviaKind::Discard, "#_ x"),
+            (TriviaKind::Whitespace, ", "),
+        ]
+    );
+    let atoms: Vec<_> = nodes(list).iter().map(|node| node.to_string()).collect();
+    assert_eq!(atoms, vec!["f", "1"]);
+}
+
+#[test]
+fn lossless_marks_comment_forms() {
+    let root = read_str_lossless("(comment (f 1)) (clojure.core/comment) (comments) ()").unwrap();
+    let kinds: Vec<_> = nodes(&root).iter().map(|node| node.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            NodeKind::Comment,
+            NodeKind::Comment,
+            NodeKind::List,
+            NodeKind::List
+        ]
+    );
+    // what a comment form comments out is still read
+    assert_eq!(nodes(nodes(&root)[0])[1].kind, NodeKind::List);
+}
+
+#[test]
+fn lossless_nodes_keep_their_spans() {
+    let root = read_str_lossless("(a\n  ^:m [b])").unwrap();
+    let list = nodes(&root)[0];
+    let metadata = nodes(list)[1];
+    assert_eq!(metadata.kind, NodeKind::Metadata);
+    assert_eq!(metadata.to_string(), "^:m [b]");
+    assert_eq!(
+        metadata.range.0,
+        Location {
+            line: 2,
+            col: 3,
+            offset: 5
+        }
+    );
+    let vector = nodes(metadata)[1];
+    assert_eq!(vector.kind, NodeKind::Vector);
+    let Element::Token(bracket) = &vector.children[0] else {
+        panic!("expected the opening bracket");
+    };
+    assert_eq!(bracket.value, "[");
+    assert_eq!(
+        bracket.range.1,
+        Location {
+            line: 2,
+            col: 8,
+            offset: 10
+        }
+    );
+}
+
+#[test]
+fn lossless_reports_read_errors() {
+    assert!(read_str_lossless("(a ; unclosed").is_err());
+}
+
+fn assert_reparses(src: &str, range: std::ops::Range<usize>, text: &str) {
+    let old = read_str_lossless(src).unwrap();
+    let edit = TextEdit {
+        range,
+        text: text.to_owned(),
+    };
+    let new_src = edit.apply(src);
+    let expected = read_str_lossless(&new_src);
+    assert_eq!(
+        reparse(&old, &new_src, &edit),
+        expected,
+        "reparsing {:?}",
+        new_src
+    );
+}
+
+#[test]
+fn reparse_matches_a_full_read() {
+    let src = "(ns a)\n\n(defn f [x]\n  (inc x)) ; f\n\n:k \"s\" [1 2]\n";
+    let edits = [
+        // inside a form
+        (22..25, "dec"),
+        // appending to a form it touches
+        (6..6, " x"),
+        // a new form between others
+        (7..7, "(def y 2)\n"),
+        // removing the line break after a comment
+        (34..35, ""),
+        // turning a form into a comment
+        (37..37, ";"),
+        // on the first and the last line
+        (0..0, "#!/usr/bin/env bb\n"),
+        (src.len()..src.len(), ":end"),
+        // joining two forms
+        (39..40, ""),
+        // everything
+        (0..src.len(), "()"),
+    ];
+    for (range, text) in edits {
+        assert_reparses(src, range, text);
+    }
+    assert_reparses("", 0..0, "(a)");
+    assert_reparses("a\nb", 1..1, "  c  ");
+}
+
+#[test]
+fn reparse_falls_back_to_a_full_read() {
+    // the string opened here ends in the next form
+    assert_reparses("(a) (b \"c\")", 3..3, "\"");
+    // the discard now applies to the next form
+    assert_reparses("(a)\n(b)", 3..3, " #_");
+    // a `#!` only starts a comment at the start of the source
+    assert_reparses("(a)\n(b)", 4..4, "#!x\n");
+    // still broken after the edit
+    assert_reparses("(a) (b)", 1..2, "(");
+}
+
+#[test]
+fn node_at_finds_the_innermost_node_under_an_offset() {
+    let src = "(defn f [x]\n  ;; inc\n  (inc #_y x))";
+    let root = read_str_lossless(src).unwrap();
+    let at = |needle: &str| {
+        let node = node_at(&root, src.find(needle).unwrap()).unwrap();
+        (node.kind, node.to_string())
+    };
+    assert_eq!(at("defn"), (NodeKind::Atom, "defn".to_owned()));
+    // the vector the offset is on, delimiters included
+    assert_eq!(at("[x]"), (NodeKind::Vector, "[x]".to_owned()));
+    assert_eq!(at("x]"), (NodeKind::Atom, "x".to_owned()));
+    // whitespace, comments and discarded forms are in the node around them
+    let list = (NodeKind::List, "(inc #_y x)".to_owned());
+    assert_eq!(at(" #_y"), list);
+    assert_eq!(at("y x"), list);
+    assert_eq!(at(";; inc").1, src);
+    assert_eq!(at("f [").0, NodeKind::Atom);
+
+    assert_eq!(node_at(&root, 0).unwrap().kind, NodeKind::List);
+    assert!(node_at(&root, src.len()).is_none());
+    let spaced = read_str_lossless("  a  ").unwrap();
+    assert_eq!(node_at(&spaced, 4).unwrap().kind, NodeKind::Root);
+}
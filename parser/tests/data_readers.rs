@@ -0,0 +1,111 @@
+use location::Located;
+use parser::{
+    ast::{Keyword, Symbol},
+    read_str, read_str_with, DataReader, Form, ParseOptions, AST,
+};
+
+// `#my/point [x y]` reads as `{:x x, :y y}`
+fn point(form: Form) -> Form {
+    let AST::Vector(coords) = form.value else {
+        return form;
+    };
+    let entries = ["x", "y"]
+        .into_iter()
+        .zip(coords)
+        .flat_map(|(name, coord)| {
+            let key = Located {
+                range: coord.range,
+                value: AST::Keyword(Keyword {
+                    ns: None,
+                    name: name.into(),
+                    auto_resolved: false,
+                }),
+            };
+            [key, coord]
+        });
+    Located {
+        range: form.range,
+        value: AST::Map(entries.collect()),
+    }
+}
+
+// `#my/tag x` reads as the symbol `my/tag`
+fn tag_name(form: Form) -> Form {
+    let AST::TaggedLiteral(tag, _) = &form.value else {
+        return form;
+    };
+    let value = AST::Symbol(tag.clone());
+    Located { value, ..form }
+}
+
+fn options<'r>(readers: Vec<(&'r str, DataReader)>) -> ParseOptions<'r> {
+    ParseOptions {
+        data_readers: readers
+            .into_iter()
+            .map(|(name, reader)| {
+                (
+                    Symbol {
+                        ns: Some("my".into()),
+                        name: name.into(),
+                    },
+                    reader,
+                )
+            })
+            .collect(),
+        ..ParseOptions::default()
+    }
+}
+
+fn written(forms: &[Form]) -> Vec<String> {
+    forms.iter().map(|form| form.to_string()).collect()
+}
+
+#[test]
+fn data_readers_read_the_values_of_their_tags() {
+    let options = options(vec![("point", Box::new(point))]);
+    let src = "(draw #my/point [1 2] #my/point [#my/point [3 4] 5])";
+    let read = read_str_with(src, &options).unwrap();
+    // inner literals are read first
+    assert_eq!(
+        written(&read.forms),
+        ["(draw {:x 1, :y 2} {:x {:x 3, :y 4}, :y 5})"]
+    );
+    // what a reader returns spans the whole literal
+    let AST::List(items) = &read.forms[0].value else {
+        unreachable!()
+    };
+    assert_eq!((items[1].range.0.offset, items[1].range.1.offset), (6, 21));
+
+    // duplicates are looked for in what the readers return
+    let read = read_str_with("{{:x 1, :y 2} 0 #my/point [1 2] 0}", &options).unwrap();
+    assert_eq!(read.diagnostics[0].message, "duplicate key in map literal");
+}
+
+#[test]
+fn tags_without_a_reader_go_to_the_default_reader() {
+    let src = "[#my/point [1 2] #other 3 #inst \"2020\"]";
+    // by default they are kept as read, like read_str does
+    let read = read_str_with(src, &options(vec![])).unwrap();
+    assert_eq!(read.forms, read_str(src).unwrap());
+
+    let options_with_default = ParseOptions {
+        default_data_reader: Some(Box::new(tag_name)),
+        ..options(vec![("point", Box::new(point))])
+    };
+    let read = read_str_with(src, &options_with_default).unwrap();
+    assert_eq!(
+        written(&read.forms),
+        ["[{:x 1, :y 2} other #inst \"2020\"]"]
+    );
+
+    let strict = ParseOptions {
+        default_data_reader: None,
+        ..options(vec![("point", Box::new(point))])
+    };
+    let err = read_str_with(src, &strict).unwrap_err();
+    assert_eq!(err.message, "no reader for tag #other");
+    assert_eq!(err.location.offset, 17);
+    // the built-in tags need no reader
+    let read = read_str_with("#inst \"2020\" #uuid \"0\"", &strict).unwrap();
+    assert_eq!(read.forms.len(), 2);
+}
@@ -0,0 +1,58 @@
+use std::collections::BTreeSet;
+
+use parser::{read_str, read_str_with, select_features, ParseOptions};
+
+fn read_for(src: &str, features: &[&str]) -> Vec<String> {
+    let options = ParseOptions {
+        features: features.iter().map(|feature| feature.to_string()).collect(),
+        ..ParseOptions::default()
+    };
+    let read = read_str_with(src, &options).unwrap();
+    read.forms.iter().map(|form| form.to_string()).collect()
+}
+
+#[test]
+fn reader_conditionals_read_as_the_clause_for_a_feature() {
+    let src = "(ns app #?(:clj (:import X) :cljs (:require y))) #?(:cljs 1 :default 2)";
+    assert_eq!(read_for(src, &["clj"]), ["(ns app (:import X))", "2"]);
+    assert_eq!(read_for(src, &["cljs"]), ["(ns app (:require y))", "1"]);
+    // the first clause for any of the features wins, nested conditionals included
+    assert_eq!(
+        read_for("#?(:cljr 1 :clj #?(:cljs 2 :clj 3))", &["clj", "cljr"]),
+        ["1"]
+    );
+    // without the features, every clause is kept
+    let forms = read_str_with(src, &ParseOptions::default()).unwrap().forms;
+    assert_eq!(forms, read_str(src).unwrap());
+}
+
+#[test]
+fn conditionals_without_a_clause_read_as_nothing() {
+    let src = "[1 #?(:cljs 2) 3] #?(:cljs x) {:a #?(:cljs 1) #?(:cljs :b) 2}";
+    assert_eq!(read_for(src, &["clj"]), ["[1 3]", "{:a 2}"]);
+    assert_eq!(read_for("#?(:cljs x)", &["clj"]), Vec::<String>::new());
+}
+
+#[test]
+fn splicing_conditionals_splice_their_clause() {
+    assert_eq!(
+        read_for("(list 0 #?@(:clj [1 2] :cljs (3)) 4)", &["cljs"]),
+        ["(list 0 3 4)"]
+    );
+    assert_eq!(read_for("#{#?@(:clj [1 2])}", &["clj"]), ["#{1 2}"]);
+}
+
+#[test]
+fn forms_that_need_a_form_fail_without_one() {
+    let features = BTreeSet::from(["clj".to_owned()]);
+    let not_single = "reader conditional must read as a single form here";
+    let odd_map = "map literal must contain an even number of forms";
+    for (src, message) in [
+        ("'#?(:cljs x)", not_single),
+        ("@#?@(:clj [a b])", not_single),
+        ("{:a #?(:cljs 1)}", odd_map),
+    ] {
+        let err = select_features(read_str(src).unwrap(), &features).unwrap_err();
+        assert_eq!(err.message, message, "reading {:?}", src);
+    }
+}
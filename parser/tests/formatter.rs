@@ -0,0 +1,90 @@
+use parser::{
+    format,
+    formatter::{format_with, FormatOptions, Indent},
+};
+
+fn assert_formats(src: &str, expected: &str) {
+    let formatted = format(src).unwrap();
+    assert_eq!(formatted, expected);
+    assert_eq!(
+        format(&formatted).unwrap(),
+        formatted,
+        "formatting is idempotent"
+    );
+}
+
+#[test]
+fn body_forms_are_indented_two_spaces() {
+    assert_formats(
+        "(defn f [x]\n(let [y 1]\n     (+ x y)))",
+        "(defn f [x]\n  (let [y 1]\n    (+ x y)))",
+    );
+    assert_formats(
+        "(clojure.core/when ok\n      (run))",
+        "(clojure.core/when ok\n  (run))",
+    );
+    assert_formats(
+        "(comment\n(run)\n    (stop))",
+        "(comment\n  (run)\n  (stop))",
+    );
+}
+
+#[test]
+fn calls_align_with_their_first_argument() {
+    assert_formats("(foo a\nb\n  c)", "(foo a\n     b\n     c)");
+    assert_formats("(foo\n   a\nb)", "(foo\n a\n b)");
+    assert_formats(
+        "(-> x\n(assoc :a 1)\n      (dissoc :b))",
+        "(-> x\n    (assoc :a 1)\n    (dissoc :b))",
+    );
+    assert_formats("((comp f g) x\ny)", "((comp f g) x\n            y)");
+}
+
+#[test]
+fn collections_align_with_their_first_element() {
+    assert_formats(
+        "{:a 1\n:bb 2\n    :c {:d 3\n:e 4}}",
+        "{:a 1\n :bb 2\n :c {:d 3\n     :e 4}}",
+    );
+    assert_formats("[1\n2] #{1\n2}", "[1\n 2] #{1\n      2}");
+    assert_formats("(foo [a\nb])", "(foo [a\n      b])");
+}
+
+#[test]
+fn comments_blank_lines_and_strings_are_kept() {
+    assert_formats(
+        ";; header\n\n\n(defn f []   \n; note\n      \"multi\n   line\"\n\n  #_ (x\n y) 1)   \n",
+        ";; header\n\n\n(defn f []\n  ; note\n  \"multi\n   line\"\n\n  #_ (x\n y) 1)\n",
+    );
+}
+
+#[test]
+fn indent_rules_are_configurable() {
+    let mut options = FormatOptions::default();
+    options.indents.insert("my-let".to_owned(), Indent::Body);
+    options.indents.insert("when".to_owned(), Indent::Align);
+    let formatted = format_with("(my-let [a 1]\na)\n(when a\nb)", &options).unwrap();
+    assert_eq!(formatted, "(my-let [a 1]\n  a)\n(when a\n      b)");
+}
+
+#[test]
+fn format_reports_read_errors() {
+    assert!(format("(defn f [x]").is_err());
+}
+
+#[test]
+fn formatting_real_sources_is_idempotent() {
+    for path in [
+        "../examples/handler.clj",
+        "../examples/simple-source-for-analyzer.clj",
+    ] {
+        let src = std::fs::read_to_string(path).unwrap();
+        let formatted = format(&src).unwrap();
+        assert_eq!(
+            format(&formatted).unwrap(),
+            formatted,
+            "formatting {}",
+            path
+        );
+    }
+}
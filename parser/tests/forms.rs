@@ -0,0 +1,616 @@
+use lexer::{token::parser::sharp, tokenize};
+use location::{Located, Span};
+use parser::{
+    ast::{Keyword, Symbol},
+    parse_form, parse_tag, read_str, tagged, Form, AST,
+};
+use token_combinator::{boxed, cut, preceded, verify};
+
+fn read_one(src: &str) -> AST<'_> {
+    let mut forms = read_str(src).unwrap();
+    assert_eq!(forms.len(), 1, "expected a single form in {:?}", src);
+    forms.remove(0).value
+}
+
+#[test]
+fn keywords() {
+    assert_eq!(
+        read_one(":foo"),
+        AST::Keyword(Keyword {
+            ns: None,
+            name: "foo".into(),
+            auto_resolved: false
+        })
+    );
+    assert_eq!(
+        read_one(":foo/bar"),
+        AST::Keyword(Keyword {
+            ns: Some("foo".into()),
+            name: "bar".into(),
+            auto_resolved: false
+        })
+    );
+    assert_eq!(
+        read_one("::foo"),
+        AST::Keyword(Keyword {
+            ns: None,
+            name: "foo".into(),
+            auto_resolved: true
+        })
+    );
+    assert_eq!(
+        read_one("::alias/foo"),
+        AST::Keyword(Keyword {
+            ns: Some("alias".into()),
+            name: "foo".into(),
+            auto_resolved: true
+        })
+    );
+    assert_eq!(
+        read_one(":1"),
+        AST::Keyword(Keyword {
+            ns: None,
+            name: "1".into(),
+            auto_resolved: false
+        })
+    );
+}
+
+#[test]
+fn keyword_fullname() {
+    let fullnames = read_str(":a :a/b ::a ::a/b")
+        .unwrap()
+        .into_iter()
+        .map(|form| form.keyword_or_none().unwrap().fullname())
+        .collect::<Vec<_>>();
+    assert_eq!(fullnames, vec!["a", "a/b", "::a", "::a/b"]);
+}
+
+#[test]
+fn bare_colon_is_invalid() {
+    let err = read_str("[:]").unwrap_err();
+    assert_eq!(err.location.col, 2);
+    assert!(read_str(":").is_err());
+    assert!(read_str(":::foo").is_err());
+}
+
+#[test]
+fn invalid_keywords() {
+    assert!(read_str(":a/b/c").is_err());
+    assert!(read_str(":a/").is_err());
+    assert!(read_str("::a/").is_err());
+}
+
+fn symbol<'a>(ns: Option<&'a str>, name: &'a str) -> AST<'a> {
+    AST::Symbol(Symbol {
+        ns: ns.map(Into::into),
+        name: name.into(),
+    })
+}
+
+#[test]
+fn symbols() {
+    assert_eq!(read_one("map"), symbol(None, "map"));
+    assert_eq!(
+        read_one("clojure.core/map"),
+        symbol(Some("clojure.core"), "map")
+    );
+    assert_eq!(read_one("a.b/c"), symbol(Some("a.b"), "c"));
+    assert_eq!(read_one("/"), symbol(None, "/"));
+    assert_eq!(
+        read_one("clojure.core//"),
+        symbol(Some("clojure.core"), "/")
+    );
+    for name in [
+        "*ns*", "+", "swap!", "-main", "foo_bar", "x'", "valid?", "<=", "->>", "=", "..",
+    ] {
+        assert_eq!(read_one(name), symbol(None, name));
+    }
+}
+
+#[test]
+fn invalid_symbols() {
+    assert!(read_str("a/b/c").is_err());
+    assert!(read_str("a/").is_err());
+    assert!(read_str("1abc").is_err());
+    assert!(read_str("//").is_err());
+}
+
+#[test]
+fn collections() {
+    let forms = read_str("(a b) [a [b]] {:a 1, :b 2} #{a b}").unwrap();
+    assert!(matches!(&forms[0].value, AST::List(items) if items.len() == 2));
+    assert!(matches!(&forms[1].value, AST::Vector(items) if items.len() == 2));
+    assert!(matches!(&forms[2].value, AST::Map(items) if items.len() == 4));
+    assert!(matches!(&forms[3].value, AST::Set(items) if items.len() == 2));
+    assert!(matches!(&read_one("()"), AST::List(items) if items.is_empty()));
+    assert!(matches!(&read_one("#{}"), AST::Set(items) if items.is_empty()));
+}
+
+#[test]
+fn map_entries() {
+    let map = read_one("{:a 1 :b [2]}");
+    let entries = map.map_entries_or_none().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].0.keyword_or_none().unwrap().name, "a");
+    assert_eq!(entries[0].1.integer_or_none(), Some(&1));
+    assert!(entries[1].1.vector_or_none().is_some());
+}
+
+#[test]
+fn map_with_odd_forms_is_invalid() {
+    let err = read_str("[{:a 1 :b}]").unwrap_err();
+    assert_eq!(
+        err.message,
+        "map literal must contain an even number of forms"
+    );
+    assert_eq!(err.location.col, 10);
+}
+
+#[test]
+fn set_with_duplicates_is_invalid() {
+    let err = read_str("#{1 [2] 3\n  [2]}").unwrap_err();
+    assert_eq!(err.message, "duplicate element in set literal");
+    assert_eq!((err.location.line, err.location.col), (2, 3));
+    assert!(read_str("#{#{1 2} #{2 1}}").is_err());
+    assert!(read_str("#{[1 2] [2 1]}").is_ok());
+}
+
+fn integers(forms: &[Located<AST>]) -> Vec<i64> {
+    forms
+        .iter()
+        .map(|form| *form.integer_or_none().unwrap())
+        .collect()
+}
+
+#[test]
+fn discard() {
+    assert_eq!(integers(&read_str("1 #_2 3").unwrap()), vec![1, 3]);
+    assert_eq!(integers(&read_str("#_ #_ 1 2 3").unwrap()), vec![3]);
+    assert_eq!(integers(&read_str("#_ #{1} 2").unwrap()), vec![2]);
+    assert_eq!(read_str("#_ (a b)").unwrap(), vec![]);
+    assert_eq!(
+        integers(read_one("[1 #_2 3]").vector_or_none().unwrap()),
+        vec![1, 3]
+    );
+    assert_eq!(
+        integers(read_one("[1 #_ #_ 2 3 4 #_5]").vector_or_none().unwrap()),
+        vec![1, 4]
+    );
+    assert_eq!(
+        read_one("{:a #_ 1 2}").map_entries_or_none().unwrap().len(),
+        1
+    );
+    assert!(
+        matches!(read_one("'#_ a b"), AST::Quoted(quoted) if quoted.symbol_or_none().unwrap().name == "b")
+    );
+}
+
+#[test]
+fn discard_without_form_is_invalid() {
+    assert!(read_str("#_").is_err());
+    assert!(read_str("[1 #_]").is_err());
+}
+
+// strips one level of quote-like wrapping, returning its name and the wrapped form
+fn unwrap_quote<'b, 'a>(ast: &'b AST<'a>) -> (&'static str, &'b AST<'a>) {
+    match ast {
+        AST::Quoted(form) => ("quote", &form.value),
+        AST::SyntaxQuoted(form) => ("syntax-quote", &form.value),
+        AST::Unquoted(form) => ("unquote", &form.value),
+        AST::UnquotedSplicing(form) => ("unquote-splicing", &form.value),
+        _ => panic!("{:?} is not quoted", ast),
+    }
+}
+
+#[test]
+fn quote_sugar() {
+    let quoted = read_one("'(a b)");
+    let (kind, form) = unwrap_quote(&quoted);
+    assert_eq!(kind, "quote");
+    assert_eq!(form.list_or_none().unwrap().len(), 2);
+
+    let syntax_quoted = read_one("`(a ~b ~@c ~(d))");
+    let (kind, form) = unwrap_quote(&syntax_quoted);
+    assert_eq!(kind, "syntax-quote");
+    let items = form.list_or_none().unwrap();
+    assert_eq!(unwrap_quote(&items[1]), ("unquote", &symbol(None, "b")));
+    assert_eq!(
+        unwrap_quote(&items[2]),
+        ("unquote-splicing", &symbol(None, "c"))
+    );
+    assert!(unwrap_quote(&items[3]).1.list_or_none().is_some());
+}
+
+#[test]
+fn nested_quote_sugar() {
+    let form = read_one("``~~x");
+    let (outer, form) = unwrap_quote(&form);
+    let (inner, form) = unwrap_quote(form);
+    let (unquote, form) = unwrap_quote(form);
+    let (unquote_again, form) = unwrap_quote(form);
+    assert_eq!(
+        [outer, inner, unquote, unquote_again],
+        ["syntax-quote", "syntax-quote", "unquote", "unquote"]
+    );
+    assert_eq!(form, &symbol(None, "x"));
+    assert_eq!(unwrap_quote(&read_one("'~@[1]")).0, "quote");
+}
+
+#[test]
+fn deref() {
+    let AST::Deref(form) = read_one("@x") else {
+        panic!("expected deref")
+    };
+    assert_eq!(form.value, symbol(None, "x"));
+    let AST::Deref(form) = read_one("@(atom 1)") else {
+        panic!("expected deref")
+    };
+    assert!(form.list_or_none().is_some());
+    let AST::Deref(form) = read_one("@@x") else {
+        panic!("expected deref")
+    };
+    assert!(matches!(&form.value, AST::Deref(inner) if inner.value == symbol(None, "x")));
+}
+
+#[test]
+fn var_quote() {
+    assert_eq!(
+        read_one("#'clojure.core/map"),
+        AST::VarQuote(Symbol {
+            ns: Some("clojure.core".into()),
+            name: "map".into()
+        })
+    );
+    let err = read_str("#'(a)").unwrap_err();
+    assert_eq!(err.message, "expected symbol, found (");
+    assert_eq!(err.location.col, 3);
+}
+
+#[test]
+fn anonymous_fn() {
+    let AST::AnonymousFn(body) = read_one("#(+ % %2 %&)") else {
+        panic!("expected anonymous fn")
+    };
+    assert_eq!(
+        body.into_iter().map(|form| form.value).collect::<Vec<_>>(),
+        vec![
+            symbol(None, "+"),
+            symbol(None, "%"),
+            symbol(None, "%2"),
+            symbol(None, "%&")
+        ]
+    );
+    assert!(matches!(read_one("#()"), AST::AnonymousFn(body) if body.is_empty()));
+    assert!(matches!(read_one("#{}"), AST::Set(_)));
+}
+
+#[test]
+fn nested_anonymous_fn_is_invalid() {
+    let err = read_str("#(map #(inc %) %)").unwrap_err();
+    assert_eq!(err.message, "nested #()s are not allowed");
+    assert_eq!(err.location.col, 7);
+    assert!(read_str("#(do [#(a)])").is_err());
+    assert!(read_str("(#(a) #(b))").is_ok());
+}
+
+#[test]
+fn metadata() {
+    let AST::Metadata(metas, form) = read_one("^:dynamic *x*") else {
+        panic!("expected metadata")
+    };
+    assert_eq!(metas.len(), 1);
+    assert_eq!(metas[0].keyword_or_none().unwrap().name, "dynamic");
+    assert_eq!(form.value, symbol(None, "*x*"));
+
+    for src in ["^{:a 1} x", "^String x", "^\"tag\" x"] {
+        assert!(matches!(read_one(src), AST::Metadata(metas, _) if metas.len() == 1));
+    }
+}
+
+#[test]
+fn stacked_metadata() {
+    let AST::Metadata(metas, form) = read_one("^:private ^String ^{:doc \"d\"} [a]") else {
+        panic!("expected metadata")
+    };
+    assert!(matches!(metas[0].value, AST::Keyword(_)));
+    assert!(matches!(metas[1].value, AST::Symbol(_)));
+    assert!(matches!(metas[2].value, AST::Map(_)));
+    assert!(form.vector_or_none().is_some());
+
+    let defn = read_one("(defn ^:private f [^long n])");
+    let items = defn.list_or_none().unwrap();
+    assert!(matches!(&items[1].value, AST::Metadata(_, name) if name.value == symbol(None, "f")));
+}
+
+#[test]
+fn invalid_metadata() {
+    let err = read_str("^1 x").unwrap_err();
+    assert_eq!(
+        err.message,
+        "metadata must be a symbol, keyword, string or map"
+    );
+    assert!(read_str("^:a").is_err());
+}
+
+#[test]
+fn tagged_literals() {
+    let AST::TaggedLiteral(tag, value) = read_one("#inst  \"2020-01-01\"") else {
+        panic!("expected tagged literal")
+    };
+    assert_eq!(tag.name, "inst");
+    assert_eq!(value.value, AST::StringLiteral("2020-01-01".to_owned()));
+
+    let AST::TaggedLiteral(tag, _) = read_one("#uuid \"f81d4fae-7dec-11d0-a765-00a0c91e6bf6\"")
+    else {
+        panic!("expected tagged literal")
+    };
+    assert_eq!(tag.name, "uuid");
+
+    let AST::TaggedLiteral(tag, value) = read_one("#my.app/point [1 2]") else {
+        panic!("expected tagged literal")
+    };
+    assert_eq!(
+        (tag.ns.as_deref(), tag.name.as_ref()),
+        (Some("my.app"), "point")
+    );
+    assert!(value.vector_or_none().is_some());
+}
+
+#[test]
+fn invalid_tagged_literals() {
+    let err = read_str("#inst 1").unwrap_err();
+    assert_eq!(err.message, "expected string literal, found 1");
+    assert!(read_str("#foo").is_err());
+}
+
+#[test]
+fn tagged_reads_values_with_the_reader_for_their_tag() {
+    let src = "#point [1 2] #color 1 #foo 1";
+    let (_, tokens) = tokenize(Span::new(src)).unwrap();
+    let mut tagged_literal = tagged(preceded(sharp, parse_tag), |tag: &Symbol| {
+        match tag.name.as_ref() {
+            "point" => Some(boxed(parse_form)),
+            "color" => Some(boxed(cut(verify(parse_form, |form: &Form| {
+                matches!(form.value, AST::Keyword(_))
+            })))),
+            _ => None,
+        }
+    });
+    let (rest, point) = tagged_literal(&tokens).unwrap();
+    let AST::TaggedLiteral(tag, value) = point else {
+        panic!("expected tagged literal")
+    };
+    assert_eq!(tag.name, "point");
+    assert!(value.vector_or_none().is_some());
+
+    // errors point into the literal: at the value, or at the tag with no reader
+    let err = tagged_literal(rest).unwrap_err();
+    assert_eq!((err.tokens_consumed, err.fatal), (2, true));
+    let err = tagged_literal(&rest[3..]).unwrap_err();
+    assert_eq!(err.errors[0].to_string(), "no reader for tag #foo");
+    assert_eq!((err.span, err.fatal), (Some((0, 2)), true));
+}
+
+#[test]
+fn reader_conditionals() {
+    let AST::ReaderConditional(splicing, clauses) = read_one("#?(:clj 1 :cljs 2 :default 3)")
+    else {
+        panic!("expected reader conditional")
+    };
+    assert!(!splicing);
+    assert_eq!(
+        clauses
+            .iter()
+            .map(|(feature, form)| (feature.name.as_ref(), *form.integer_or_none().unwrap()))
+            .collect::<Vec<_>>(),
+        vec![("clj", 1), ("cljs", 2), ("default", 3)]
+    );
+
+    let vector = read_one("[#?@(:clj [a b])]");
+    let AST::ReaderConditional(splicing, clauses) = &vector.vector_or_none().unwrap()[0].value
+    else {
+        panic!("expected reader conditional")
+    };
+    assert!(*splicing);
+    assert!(clauses[0].1.vector_or_none().is_some());
+    assert!(
+        matches!(read_one("#?()"), AST::ReaderConditional(false, clauses) if clauses.is_empty())
+    );
+}
+
+#[test]
+fn invalid_reader_conditionals() {
+    let err = read_str("#?(:clj 1 :cljs)").unwrap_err();
+    assert_eq!(
+        err.message,
+        "reader conditional must contain feature/form pairs"
+    );
+    let err = read_str("#?(clj 1)").unwrap_err();
+    assert_eq!(err.message, "reader conditional feature must be a keyword");
+    assert_eq!(err.location.col, 4);
+    let err = read_str("#?@(:clj 1)").unwrap_err();
+    assert_eq!(
+        err.message,
+        "spliced form in reader conditional must be a list or vector"
+    );
+    assert!(read_str("#?[:clj 1]").is_err());
+}
+
+fn map_keys(src: &str) -> Vec<String> {
+    read_one(src)
+        .map_entries_or_none()
+        .unwrap()
+        .into_iter()
+        .map(|(key, _)| match &key.value {
+            AST::Keyword(keyword) => keyword.fullname(),
+            AST::Symbol(symbol) => symbol.fullname(),
+            other => format!("{:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn namespaced_maps() {
+    assert_eq!(
+        map_keys("#:person{:name \"x\" :db/id 1 :_/kind 2 age 3 \"s\" 4}"),
+        vec![
+            "person/name",
+            "db/id",
+            "kind",
+            "person/age",
+            "StringLiteral(\"s\")"
+        ]
+    );
+    assert_eq!(map_keys("#::{:a 1 b 2}"), vec!["::a", "b"]);
+    assert_eq!(map_keys("#::alias{:a 1 ::b 2}"), vec!["::alias/a", "::b"]);
+}
+
+#[test]
+fn invalid_namespaced_maps() {
+    let err = read_str("#:{:a 1}").unwrap_err();
+    assert_eq!(err.message, "namespaced map must specify a namespace");
+    assert!(read_str("#:person[:a 1]").is_err());
+    assert!(read_str("#:person{:a}").is_err());
+}
+
+#[test]
+fn numbers() {
+    let cases = [
+        ("42", AST::IntegerLiteral(42)),
+        ("-1", AST::IntegerLiteral(-1)),
+        ("+7", AST::IntegerLiteral(7)),
+        ("0", AST::IntegerLiteral(0)),
+        ("0x1F", AST::IntegerLiteral(31)),
+        ("-0XfF", AST::IntegerLiteral(-255)),
+        ("017", AST::IntegerLiteral(15)),
+        ("2r1010", AST::IntegerLiteral(10)),
+        ("36rZZ", AST::IntegerLiteral(1295)),
+        ("1.5", AST::FloatLiteral(1.5)),
+        ("-2.", AST::FloatLiteral(-2.0)),
+        ("1e10", AST::FloatLiteral(1e10)),
+        ("2.5E-3", AST::FloatLiteral(2.5e-3)),
+        ("22/7", AST::RatioLiteral(22, 7)),
+        ("-1/2", AST::RatioLiteral(-1, 2)),
+        ("10N", AST::BigIntLiteral("10".into())),
+        (
+            "99999999999999999999",
+            AST::BigIntLiteral("99999999999999999999".into()),
+        ),
+        ("1.5M", AST::BigDecimalLiteral("1.5".into())),
+        ("1M", AST::BigDecimalLiteral("1".into())),
+    ];
+    for (src, expected) in cases {
+        assert_eq!(read_one(src), expected, "reading {}", src);
+    }
+}
+
+#[test]
+fn signs_and_slashes_next_to_numbers() {
+    assert_eq!(read_one("-"), symbol(None, "-"));
+    assert_eq!(read_one("->x"), symbol(None, "->x"));
+    let forms = read_str("(/ 22 7)").unwrap();
+    let items = forms[0].list_or_none().unwrap();
+    assert_eq!(items[0].value, symbol(None, "/"));
+    assert_eq!(items[1].value, AST::IntegerLiteral(22));
+}
+
+#[test]
+fn invalid_numbers() {
+    for src in ["09", "1/0", "1/", "1.5.3", "2r102", "0x", "1.5N"] {
+        assert!(read_str(src).is_err(), "{} should not read", src);
+    }
+}
+
+fn string(src: &str) -> String {
+    match read_one(src) {
+        AST::StringLiteral(s) => s,
+        other => panic!("expected a string, found {:?}", other),
+    }
+}
+
+#[test]
+fn string_escapes() {
+    assert_eq!(string(r#""a\tb\nc\r\\ \"q\"""#), "a\tb\nc\r\\ \"q\"");
+    assert_eq!(string(r#""\b\f\'""#), "\u{8}\u{c}'");
+    assert_eq!(string(r#""\u0041\u00e9\u3042""#), "A\u{e9}\u{3042}");
+    assert_eq!(string(r#""\101\0\377x""#), "A\0\u{ff}x");
+}
+
+#[test]
+fn multiline_string() {
+    let forms = read_str("\"first\n  second\" :next").unwrap();
+    assert_eq!(
+        forms[0].value,
+        AST::StringLiteral("first\n  second".to_owned())
+    );
+    assert_eq!(forms[0].range.1.line, 2);
+    assert_eq!(forms[1].range.0.line, 2);
+}
+
+#[test]
+fn invalid_string_escapes() {
+    let err = read_str(r#"(str "a\qb")"#).unwrap_err();
+    assert_eq!(err.message, "unsupported escape character `\\q`");
+    assert_eq!(err.location.col, 6);
+    for src in [r#""\u00""#, r#""\u00G1""#, r#""\400""#] {
+        assert!(read_str(src).is_err(), "{} should not read", src);
+    }
+}
+
+#[test]
+fn char_literals() {
+    let cases = [
+        (r"\a", 'a'),
+        (r"\(", '('),
+        (r"\,", ','),
+        (r"\newline", '\n'),
+        (r"\space", ' '),
+        (r"\tab", '\t'),
+        (r"\return", '\r'),
+        (r"\formfeed", '\u{c}'),
+        (r"\backspace", '\u{8}'),
+        (r"\A", 'A'),
+        (r"\あ", '\u{3042}'),
+        (r"\o101", 'A'),
+        (r"\u", 'u'),
+        (r"\o", 'o'),
+    ];
+    for (src, expected) in cases {
+        assert_eq!(read_one(src), AST::CharLiteral(expected), "reading {}", src);
+    }
+    let forms = read_str(r"(\a\b \c)").unwrap();
+    let items = forms[0].list_or_none().unwrap();
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[1].value, AST::CharLiteral('b'));
+}
+
+#[test]
+fn invalid_char_literals() {
+    let err = read_str(r"(str \uXYZ)").unwrap_err();
+    assert_eq!(err.message, "invalid character literal");
+    assert_eq!(err.location.col, 6);
+    for src in ["\\", "\\ a", "\\\n", r"\ab", r"\u004", r"\o400", r"\o8"] {
+        assert!(read_str(src).is_err(), "{:?} should not read", src);
+    }
+}
+
+#[test]
+fn regex_literals() {
+    assert_eq!(read_one(r#"#"\d+""#), AST::RegexLiteral(r"\d+".into()));
+    assert_eq!(
+        read_one(r#"#"a\"b\\""#),
+        AST::RegexLiteral(r#"a\"b\\"#.into())
+    );
+    let forms = read_str(r#"(re-find #"[a-z]\s" "x y")"#).unwrap();
+    let items = forms[0].list_or_none().unwrap();
+    assert_eq!(items[1].value, AST::RegexLiteral(r"[a-z]\s".into()));
+    assert_eq!(items[2].value, AST::StringLiteral("x y".to_owned()));
+}
+
+#[test]
+fn unterminated_regex() {
+    let err = read_str(r#"(re-find #"\d+)"#).unwrap_err();
+    assert_eq!(err.message, "unterminated regex");
+    assert_eq!(err.location.col, 10);
+}
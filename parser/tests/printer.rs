@@ -0,0 +1,86 @@
+use std::fs::read_to_string;
+
+use parser::{read_str, write_form, AST};
+
+fn rewrite(src: &str) -> String {
+    let forms = read_str(src).unwrap();
+    let written: Vec<_> = forms.iter().map(write_form).collect();
+    written.join("\n")
+}
+
+fn assert_round_trip(src: &str) {
+    let forms = read_str(src).unwrap();
+    let written = rewrite(src);
+    let reread = read_str(&written).unwrap();
+    assert_eq!(
+        reread.len(),
+        forms.len(),
+        "{} was written as {}",
+        src,
+        written
+    );
+    for (reread, form) in reread.iter().zip(&forms) {
+        assert!(
+            reread.same_value(form),
+            "{} was written as {}",
+            src,
+            written
+        );
+    }
+}
+
+#[test]
+fn write_form_is_canonical() {
+    let cases = [
+        ("(defn  f [x]\n  (inc x))", "(defn f [x] (inc x))"),
+        ("{:a 1 :b/c 2 ::d 3}", "{:a 1, :b/c 2, ::d 3}"),
+        ("#{1} #(+ % 1) #\"\\d\"", "#{1}\n#(+ % 1)\n#\"\\d\""),
+        ("'a `(b ~c ~@d) @e #'f/g", "'a\n`(b ~c ~@d)\n@e\n#'f/g"),
+        ("^:m ^{:k 1} [& xs]", "^:m ^{:k 1} [& xs]"),
+        (
+            "#inst \"2020\" #?@(:clj [1] :cljs [2])",
+            "#inst \"2020\"\n#?@(:clj [1] :cljs [2])",
+        ),
+        (
+            "1.0 1e100 -2.5 3/4 10N 1.5M 0x10",
+            "1.0\n1e100\n-2.5\n3/4\n10N\n1.5M\n16",
+        ),
+        (
+            "\\a \\newline \\u0041 \\space",
+            "\\a\n\\newline\n\\A\n\\space",
+        ),
+        (r#""a\"b\\c\n\td\u0001""#, r#""a\"b\\c\n\td\u0001""#),
+        ("#:p{:a 1}", "{:p/a 1}"),
+    ];
+    for (src, expected) in cases {
+        assert_eq!(rewrite(src), expected, "writing {}", src);
+    }
+}
+
+#[test]
+fn written_forms_read_back_the_same() {
+    assert_round_trip(&read_to_string("../examples/handler.clj").unwrap());
+    assert_round_trip(&read_to_string("../examples/simple-source-for-analyzer.clj").unwrap());
+    assert_round_trip("`(a ~ @b ~(deref c))");
+    assert_round_trip("\"multi\nline\" \\tab \\o101 #_ignored (x)");
+}
+
+#[test]
+fn non_finite_floats_read_back() {
+    assert_eq!(
+        rewrite("[1e400 -1e400 ##Inf ##-Inf]"),
+        "[##Inf ##-Inf ##Inf ##-Inf]"
+    );
+    assert_round_trip("[1e400 -1e400]");
+    // NaN is not equal to itself, so it can only be checked to read back as a NaN
+    let written = rewrite("##NaN");
+    let form = read_str(&written).unwrap().remove(0);
+    assert!(matches!(form.value, AST::FloatLiteral(v) if v.is_nan()));
+}
+
+#[test]
+fn display_matches_write_form() {
+    let form = read_str("(str :a \"b\")").unwrap().remove(0);
+    assert_eq!(form.to_string(), write_form(&form));
+    assert_eq!(form.value.to_string(), "(str :a \"b\")");
+}
@@ -0,0 +1,364 @@
+use location::Location;
+use parser::{
+    ast::Symbol, read_edn, read_str, read_str_lossless, read_str_recovering, read_str_streaming, read_str_with,
+    read_str_with_comments, write_form, ParseOptions, Streamed, AST,
+};
+use token_combinator::Needed;
+
+#[test]
+fn read_str_returns_top_level_forms() {
+    let forms = read_str("\n  (foo bar)\n  :baz  \n").unwrap();
+    assert_eq!(forms.len(), 2);
+    assert!(matches!(&forms[0].value, AST::List(items) if items.len() == 2));
+    assert_eq!(forms[0].range.0, Location { line: 2, col: 3, offset: 3 });
+    assert_eq!(forms[0].range.1, Location { line: 2, col: 12, offset: 12 });
+    assert!(matches!(&forms[1].value, AST::Keyword(k) if k.name == "baz"));
+}
+
+#[test]
+fn read_str_on_empty_source() {
+    assert_eq!(read_str("").unwrap(), vec![]);
+    assert_eq!(read_str("  ; only a comment\n").unwrap(), vec![]);
+}
+
+#[test]
+fn read_str_skips_shebang() {
+    let forms = read_str("#!/usr/bin/env bb\n(println \"hi\")").unwrap();
+    assert_eq!(forms.len(), 1);
+    assert_eq!(forms[0].range.0.line, 2);
+}
+
+#[test]
+fn read_str_forms_outlive_tokens() {
+    let src = String::from("clojure.core/map");
+    let form = read_str(&src).unwrap().remove(0);
+    assert_eq!(
+        form.value,
+        AST::Symbol(Symbol {
+            ns: Some("clojure.core".into()),
+            name: "map".into()
+        })
+    );
+}
+
+#[test]
+fn read_str_reports_parse_error_position() {
+    let err = read_str("(foo\n  bar]").unwrap_err();
+    assert_eq!(err.location, Location { line: 2, col: 6, offset: 10 });
+    assert_eq!(err.message, "expected `)`, found `]`");
+    assert_eq!(err.to_string(), "expected `)`, found `]` at line 2, column 6");
+    assert_eq!(err.related, Some(Location { line: 1, col: 1, offset: 0 }));
+}
+
+#[test]
+fn read_str_reports_unterminated_form() {
+    let err = read_str("(foo").unwrap_err();
+    assert_eq!(err.location, Location { line: 1, col: 1, offset: 0 });
+    assert_eq!(err.message, "unclosed `(`, expected `)`");
+    let err = read_str("[(foo]").unwrap_err();
+    assert_eq!(err.message, "expected `)`, found `]`");
+    assert_eq!(err.related, Some(Location { line: 1, col: 2, offset: 1 }));
+}
+
+#[test]
+fn read_str_reports_unexpected_closing_delimiters() {
+    let err = read_str("(foo))").unwrap_err();
+    assert_eq!(err.location, Location { line: 1, col: 6, offset: 5 });
+    assert_eq!(err.message, "unmatched `)`");
+    assert_eq!(err.related, None);
+    // an earlier error is still reported first
+    let err = read_str("{:a} (b]").unwrap_err();
+    assert_eq!(err.message, "map literal must contain an even number of forms");
+}
+
+#[test]
+fn read_str_reports_lex_error_position() {
+    let err = read_str("(foo\n \"bar)").unwrap_err();
+    assert_eq!(err.location.line, 2);
+    assert_eq!(err.location.col, 2);
+    assert_eq!(err.message, "unterminated string");
+}
+
+#[test]
+fn read_str_drops_comments() {
+    let forms = read_str("; leading\n(f \"a ; b\") ; trailing\n:k ;; last").unwrap();
+    assert_eq!(forms.len(), 2);
+    let items = forms[0].list_or_none().unwrap();
+    assert_eq!(items[1].value, AST::StringLiteral("a ; b".to_owned()));
+}
+
+#[test]
+fn read_str_with_comments_attaches_comments_to_forms() {
+    let read = read_str_with_comments(
+        "#!/usr/bin/env bb\n; about f\n(defn f []\n  ; inside\n  1)\n:k ; after k\n;; end\n",
+    )
+    .unwrap();
+    let comments: Vec<Vec<&str>> = read
+        .forms
+        .iter()
+        .map(|form| form.comments.iter().map(|c| c.value).collect())
+        .collect();
+    assert_eq!(
+        comments,
+        vec![vec!["#!/usr/bin/env bb", "; about f", "; inside"], vec![]]
+    );
+    let trailing: Vec<&str> = read.trailing.iter().map(|c| c.value).collect();
+    assert_eq!(trailing, vec!["; after k", ";; end"]);
+    assert_eq!(read.forms[0].comments[2].range.0.line, 4);
+    assert_eq!(read.forms[0].comments[2].range.1.col, 11);
+}
+
+#[test]
+fn read_str_with_comments_ignores_semicolons_in_strings() {
+    let read = read_str_with_comments(r#"(println "; not a comment" \;)"#).unwrap();
+    assert!(read.forms[0].comments.is_empty());
+    assert!(read.trailing.is_empty());
+}
+
+fn recovered(src: &str) -> (Vec<String>, Vec<String>) {
+    let recovered = read_str_recovering(src);
+    (
+        recovered.forms.iter().map(write_form).collect(),
+        recovered
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect(),
+    )
+}
+
+#[test]
+fn read_str_recovering_closes_unclosed_forms() {
+    assert_eq!(
+        recovered("(defn f [x]\n  (inc x)\n\n(defn g [] 1)"),
+        (
+            vec!["(defn f [x] (inc x) (defn g [] 1))".to_owned()],
+            vec!["unclosed `(`, expected `)` at line 1, column 1".to_owned()]
+        )
+    );
+    assert_eq!(
+        recovered("(let [a 1\n  (f a))\n:next"),
+        (
+            vec!["(let [a 1 (f a)])".to_owned(), ":next".to_owned()],
+            vec!["expected `]`, found `)` at line 2, column 8".to_owned()]
+        )
+    );
+}
+
+#[test]
+fn read_str_recovering_reports_mismatched_delimiters() {
+    assert_eq!(
+        recovered("(a [b)] c) d"),
+        (
+            vec!["(a [b])".to_owned(), "c".to_owned(), "d".to_owned()],
+            vec![
+                "expected `]`, found `)` at line 1, column 6".to_owned(),
+                "unmatched `]` at line 1, column 7".to_owned(),
+                "unmatched `)` at line 1, column 10".to_owned()
+            ]
+        )
+    );
+    assert_eq!(
+        recovered("(a b} c"),
+        (
+            vec!["(a b)".to_owned(), "c".to_owned()],
+            vec!["expected `)`, found `}` at line 1, column 5".to_owned()]
+        )
+    );
+    assert_eq!(
+        recovered("(a [b #{c)").1,
+        vec![
+            "unclosed `[`, expected `]` at line 1, column 4",
+            "expected `}`, found `)` at line 1, column 10"
+        ]
+    );
+}
+
+#[test]
+fn read_str_recovering_skips_invalid_forms() {
+    let (forms, diagnostics) = recovered("(ok) {:a} #{1 1} (also ok) '");
+    assert_eq!(forms, vec!["(ok)", "(also ok)"]);
+    assert_eq!(diagnostics.len(), 3);
+    assert!(diagnostics[0].starts_with("map literal must contain an even number of forms"));
+    assert!(diagnostics[1].starts_with("duplicate element in set literal"));
+
+    let (forms, diagnostics) = recovered("(a) #_");
+    assert_eq!(forms, vec!["(a)"]);
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn read_str_recovering_matches_read_str_on_valid_source() {
+    let src = "(ns a) #_(skipped) [1 2] {:k v}";
+    let recovered = read_str_recovering(src);
+    assert_eq!(recovered.forms, read_str(src).unwrap());
+    assert!(recovered.diagnostics.is_empty());
+    let recovered = read_str_recovering("(a \"b");
+    assert!(recovered.forms.is_empty());
+    assert_eq!(recovered.diagnostics, vec![read_str("(a \"b").unwrap_err()]);
+}
+
+#[test]
+fn read_str_streaming_waits_for_unfinished_forms() {
+    assert_eq!(
+        read_str_streaming("(defn f [x]\n  (inc"),
+        Ok(Streamed::Incomplete(Needed::Size(2)))
+    );
+    for src in ["(a) '", "#_", "^:m", "#inst", "\"abc", "#\"a", "(a \\"] {
+        assert_eq!(
+            read_str_streaming(src),
+            Ok(Streamed::Incomplete(Needed::Unknown)),
+            "reading {:?}",
+            src
+        );
+    }
+    assert_eq!(
+        read_str_streaming("(a) [b]"),
+        Ok(Streamed::Complete(read_str("(a) [b]").unwrap()))
+    );
+}
+
+#[test]
+fn read_str_streaming_reports_errors_more_input_cannot_fix() {
+    for src in ["(a]", "a)", "{:a} (b", "#'1 (a"] {
+        assert_eq!(
+            read_str_streaming(src),
+            Err(read_str(src).unwrap_err()),
+            "reading {:?}",
+            src
+        );
+    }
+    // an unterminated form does not hide the errors inside it
+    let err = read_str_streaming("(#{1 1}").unwrap_err();
+    assert_eq!(err.message, "duplicate element in set literal");
+}
+
+#[test]
+fn read_edn_reads_data() {
+    let src =
+        "{:name \"app\" :port 8080 :tags #{a b} :ratio 1.5 :at #inst \"2020\"} [nil true \\c]";
+    assert_eq!(read_edn(src), read_str(src));
+    // discarded forms are EDN too
+    assert_eq!(read_edn("#_ @x (1)").unwrap().len(), 1);
+}
+
+#[test]
+fn read_edn_rejects_code_only_reader_macros() {
+    for (src, name) in [
+        ("#(inc %)", "`#()`"),
+        ("[@a]", "`@`"),
+        ("'a", "`'`"),
+        ("`a", "`` ` ``"),
+        ("(~a)", "`~`"),
+        ("#'a", "`#'`"),
+        ("#\"a\"", "`#\"\"`"),
+        ("^:m a", "`^`"),
+        ("#?(:clj 1)", "`#?`"),
+    ] {
+        let err = read_edn(src).unwrap_err();
+        assert_eq!(
+            err.message,
+            format!("{} is not valid in EDN", name),
+            "reading {:?}",
+            src
+        );
+    }
+    let err = read_edn("{:a [1 `(b ~c)]}").unwrap_err();
+    assert_eq!(err.location, Location { line: 1, col: 8, offset: 7 });
+    assert_eq!(err.message, "`` ` `` is not valid in EDN");
+}
+
+#[test]
+fn duplicate_map_keys_are_diagnostics_unless_strict() {
+    let src = "{:a 1 \"a\" 2 :b {x 1 x 2} :a 3} {1 1 1.0 2 :m #:p{:a 1 :p/a 2}}";
+    let read = read_str_with(src, &ParseOptions::default()).unwrap();
+    assert_eq!(read.forms, read_str(src).unwrap());
+    let found: Vec<_> = read
+        .diagnostics
+        .iter()
+        .map(|err| (err.location.offset, err.related.unwrap().offset))
+        .collect();
+    // `:a` and `"a"`, and `1` and `1.0`, are different keys
+    assert_eq!(found, [(20, 16), (25, 1), (54, 49)]);
+    assert!(read
+        .diagnostics
+        .iter()
+        .all(|err| err.message == "duplicate key in map literal"));
+
+    let strict = ParseOptions {
+        strict_maps: true,
+        ..ParseOptions::default()
+    };
+    let err = read_str_with(src, &strict).unwrap_err();
+    assert_eq!(err.location, Location { line: 1, col: 21, offset: 20 });
+    let read = read_str_with("{:a 1 :b 2}", &strict).unwrap();
+    assert_eq!(read.diagnostics, []);
+}
+
+#[test]
+fn duplicate_set_elements_are_errors_unless_lenient() {
+    let src = "[#{1 [2] \"2\" 3\n  [2]} #{:a :a}]";
+    // the same error read_str fails with, but with the span of the duplicate and the first
+    let err = read_str_with(src, &ParseOptions::default()).unwrap_err();
+    let read_str_err = read_str(src).unwrap_err();
+    assert_eq!(
+        (&err.message, err.location),
+        (&read_str_err.message, read_str_err.location)
+    );
+    assert_eq!(err.related.unwrap().offset, 5);
+
+    let lenient = ParseOptions {
+        strict_sets: false,
+        ..ParseOptions::default()
+    };
+    let read = read_str_with(src, &lenient).unwrap();
+    assert_eq!(read.forms[0].to_string(), "[#{1 [2] \"2\" 3 [2]} #{:a :a}]");
+    let duplicate = &read.diagnostics[0];
+    assert_eq!(duplicate.message, "duplicate element in set literal");
+    assert_eq!(duplicate, &err);
+    assert_eq!(duplicate.location, Location { line: 2, col: 3, offset: 17 });
+    assert_eq!(duplicate.end.unwrap().offset, 20);
+    assert_eq!(read.diagnostics[1].location.offset, 27);
+    // reading leniently leaves the other readers strict
+    assert_eq!(read_str(src).unwrap_err(), err);
+    assert_eq!(read_str_lossless(src).unwrap_err(), err);
+}
+
+#[test]
+fn read_str_with_options_matches_the_read_functions() {
+    let src = "; config\n{:port 8080} #_x [a ^:m b] ; trailing";
+    let read = read_str_with(src, &ParseOptions::default()).unwrap();
+    assert_eq!(read.forms, read_str(src).unwrap());
+    assert_eq!((read.comments.len(), read.incomplete), (0, None));
+
+    let options = ParseOptions {
+        preserve_comments: true,
+        ..ParseOptions::default()
+    };
+    let comments: Vec<_> = read_str_with(src, &options)
+        .unwrap()
+        .comments
+        .iter()
+        .map(|comment| comment.value)
+        .collect();
+    assert_eq!(comments, ["; config", "; trailing"]);
+
+    let options = ParseOptions {
+        streaming: true,
+        ..ParseOptions::default()
+    };
+    let read = read_str_with("(a [b", &options).unwrap();
+    assert_eq!(read.incomplete, Some(Needed::Size(2)));
+    assert!(read.forms.is_empty());
+    assert_eq!(read_str_with(src, &options).unwrap().forms.len(), 2);
+    assert!(read_str_with("(a [b", &ParseOptions::default()).is_err());
+
+    let options = ParseOptions {
+        edn: true,
+        ..ParseOptions::default()
+    };
+    assert_eq!(
+        read_str_with(src, &options).unwrap_err(),
+        read_edn(src).unwrap_err()
+    );
+}
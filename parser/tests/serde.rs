@@ -0,0 +1,48 @@
+#![cfg(feature = "serde")]
+
+use parser::{read_str, read_str_lossless, Form};
+
+#[test]
+fn forms_round_trip_through_json() {
+    let src =
+        r#"(defn f [x] {:a/b #{2.5 3/4 10N} ::c "s\n" :d \c #inst "2020" ^:m [@x 'y `(~z ~@w)]})"#;
+    let forms = read_str(src).unwrap();
+    let json = serde_json::to_string(&forms).unwrap();
+    let deserialized: Vec<Form> = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, forms);
+}
+
+#[test]
+fn escaped_text_round_trips_through_json() {
+    let src = r#"[#"\d+" #"\"q\"" sym :ns/kw]"#;
+    let forms = read_str(src).unwrap();
+    let json = serde_json::to_string(&forms).unwrap();
+    let deserialized: Vec<Form> = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, forms);
+
+    // other writers may escape any char, which can't be borrowed back either
+    let json = json
+        .replace(r#""sym""#, r#""\u0073ym""#)
+        .replace(r#""kw""#, r#""k\u0077""#);
+    let deserialized: Vec<Form> = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, forms);
+}
+
+#[test]
+fn serialized_forms_keep_their_spans() {
+    let forms = read_str("(a\n b)").unwrap();
+    let json = serde_json::to_value(&forms).unwrap();
+    assert_eq!(json[0]["range"][1]["line"], 2);
+    assert_eq!(json[0]["range"][1]["col"], 4);
+    assert_eq!(json[0]["value"]["List"][1]["value"]["Symbol"]["name"], "b");
+}
+
+#[test]
+fn cst_serializes_with_trivia() {
+    let root = read_str_lossless("(a ; note\n b)").unwrap();
+    let json = serde_json::to_value(&root).unwrap();
+    let list = &json["children"][0]["Node"];
+    assert_eq!(list["kind"], "List");
+    assert_eq!(list["children"][3]["Trivia"][0], "Comment");
+    assert_eq!(list["children"][3]["Trivia"][1]["value"], "; note");
+}
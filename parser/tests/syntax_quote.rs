@@ -0,0 +1,94 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use parser::{
+    ast::Symbol, read_str, read_str_with_resolver, Form, SymbolicResolver, SyntaxQuoteResolver, AST,
+};
+
+// qualifies bare symbols with the namespace and numbers the auto-gensyms of each syntax quote
+#[derive(Default)]
+struct Resolver<'a> {
+    ns: &'a str,
+    gensyms: HashMap<String, String>,
+    next_gensym: usize,
+    unquotes: usize,
+}
+
+impl<'a> Resolver<'a> {
+    fn renamed(form: Form<'a>, ns: Option<Cow<'a, str>>, name: Cow<'a, str>) -> Form<'a> {
+        Form {
+            value: AST::Symbol(Symbol { ns, name }),
+            ..form
+        }
+    }
+}
+
+impl<'a> SyntaxQuoteResolver<'a> for Resolver<'a> {
+    fn enter_syntax_quote(&mut self) {
+        self.gensyms.clear();
+    }
+    fn resolve_symbol(&mut self, form: Form<'a>, symbol: &Symbol<'a>) -> Form<'a> {
+        match &symbol.ns {
+            None if symbol.name != "let" => {
+                Self::renamed(form, Some(self.ns.into()), symbol.name.clone())
+            }
+            _ => form,
+        }
+    }
+    fn resolve_gensym(&mut self, form: Form<'a>, symbol: &Symbol<'a>) -> Form<'a> {
+        let next_gensym = &mut self.next_gensym;
+        let name = self
+            .gensyms
+            .entry(symbol.name.to_string())
+            .or_insert_with(|| {
+                *next_gensym += 1;
+                let base = symbol.name.trim_end_matches('#');
+                format!("{}__{}__auto__", base, next_gensym)
+            })
+            .clone();
+        Self::renamed(form, None, name.into())
+    }
+    fn resolve_unquote(&mut self, form: Form<'a>) -> Form<'a> {
+        self.unquotes += 1;
+        form
+    }
+}
+
+fn resolve(src: &str) -> (Vec<String>, usize) {
+    let mut resolver = Resolver {
+        ns: "user",
+        ..Resolver::default()
+    };
+    let forms = read_str_with_resolver(src, &mut resolver).unwrap();
+    let written = forms.iter().map(|form| form.to_string()).collect();
+    (written, resolver.unquotes)
+}
+
+#[test]
+fn resolver_sees_the_forms_of_syntax_quotes() {
+    let (written, unquotes) = resolve("`(let [x# (inc ~y)] (str/join x# ~@zs))");
+    assert_eq!(
+        written,
+        ["`(let [x__1__auto__ (user/inc ~y)] (str/join x__1__auto__ ~@zs))"]
+    );
+    assert_eq!(unquotes, 2);
+
+    // each syntax quote has its own gensyms, and forms outside them are left alone
+    let (written, _) = resolve("(f `x# `x# 'g)");
+    assert_eq!(written, ["(f `x__1__auto__ `x__2__auto__ 'g)"]);
+}
+
+#[test]
+fn unquoted_forms_are_not_resolved() {
+    // but the syntax quotes in them are
+    let (written, unquotes) = resolve("`(a ~(b `c) ^:m [~@d])");
+    assert_eq!(written, ["`(user/a ~(b `user/c) ^:m [~@d])"]);
+    assert_eq!(unquotes, 2);
+}
+
+#[test]
+fn symbolic_resolver_keeps_forms_as_read() {
+    let src = "`(let [x# ~y] #{x# z/w} #?(:clj a))";
+    let forms = read_str_with_resolver(src, &mut SymbolicResolver).unwrap();
+    assert_eq!(forms, read_str(src).unwrap());
+}
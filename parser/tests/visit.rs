@@ -0,0 +1,80 @@
+use parser::{
+    ast::{Keyword, Symbol},
+    read_str, walk, Form, Visitor,
+};
+
+#[derive(Default)]
+struct Events(Vec<String>);
+
+impl<'a> Visitor<'a> for Events {
+    fn visit_list(&mut self, _: &Form<'a>, items: &[Form<'a>]) {
+        self.0.push(format!("list/{}", items.len()));
+    }
+    fn visit_vector(&mut self, _: &Form<'a>, items: &[Form<'a>]) {
+        self.0.push(format!("vector/{}", items.len()));
+    }
+    fn visit_map(&mut self, _: &Form<'a>, forms: &[Form<'a>]) {
+        self.0.push(format!("map/{}", forms.len() / 2));
+    }
+    fn visit_symbol(&mut self, _: &Form<'a>, symbol: &Symbol<'a>) {
+        self.0.push(symbol.fullname());
+    }
+    fn visit_keyword(&mut self, _: &Form<'a>, keyword: &Keyword<'a>) {
+        self.0.push(keyword.fullname());
+    }
+    fn visit_number(&mut self, _: &Form<'a>) {
+        self.0.push("number".to_owned());
+    }
+    fn visit_string(&mut self, _: &Form<'a>, value: &str) {
+        self.0.push(format!("{:?}", value));
+    }
+    fn visit_quote(&mut self, _: &Form<'a>, _: &Form<'a>) {
+        self.0.push("quote".to_owned());
+    }
+    fn visit_metadata(&mut self, _: &Form<'a>, metas: &[Form<'a>], _: &Form<'a>) {
+        self.0.push(format!("meta/{}", metas.len()));
+    }
+}
+
+#[test]
+fn walk_visits_in_document_order() {
+    let mut events = Events::default();
+    let src = "(defn ^:private f [x] {:a 'x} (str/join \"-\" 1.5))";
+    for form in read_str(src).unwrap() {
+        walk(&form, &mut events);
+    }
+    assert_eq!(
+        events.0,
+        vec![
+            "list/5", "defn", "meta/1", "private", "f", "vector/1", "x", "map/1", "a", "quote",
+            "x", "list/3", "str/join", "\"-\"", "number",
+        ]
+    );
+}
+
+#[derive(Default)]
+struct Counts {
+    forms: usize,
+    tagged: usize,
+}
+
+impl<'a> Visitor<'a> for Counts {
+    fn visit_form(&mut self, _: &Form<'a>) {
+        self.forms += 1;
+    }
+    fn visit_tagged_literal(&mut self, _: &Form<'a>, tag: &Symbol<'a>, _: &Form<'a>) {
+        assert_eq!(tag.name, "inst");
+        self.tagged += 1;
+    }
+}
+
+#[test]
+fn walk_calls_only_overridden_hooks() {
+    let mut counts = Counts::default();
+    let forms = read_str("#{#inst \"2020\" @a #'b #?(:clj [c] :cljs d)}").unwrap();
+    walk(&forms[0], &mut counts);
+    // the set, the tagged literal and its string, the deref and `a`, the var quote,
+    // the reader conditional, the vector, `c` and `d`
+    assert_eq!(counts.forms, 10);
+    assert_eq!(counts.tagged, 1);
+}
@@ -13,7 +13,7 @@ use parser::{
 use paste::paste;
 use semantic_ast::*;
 use token_combinator::{
-    alt, context, many0_until_end, map, map_res, opt, permutation, success, tuple, TokenParseError,
+    all_consuming, alt, context, many0_until_end, map, map_result, opt, permutation, success, tuple, TokenParseError,
     TokenParseErrorKind, TokenParseResult, TokenParser, many0,
 };
 
@@ -26,7 +26,7 @@ macro_rules! specific_symbol {
             fn [<$name _symbol>]<'a>(
                 forms: &'a [Located<AST<'a>>]
             ) -> ASTParseResult<'a, &'a Symbol<'a>> {
-                located(map_res(symbol, |result| match result {
+                located(map_result(symbol, |result| match result {
                     Ok((rest, sym)) => {
                         if sym.name == $sym_name {
                             Ok((rest, sym))
@@ -37,6 +37,8 @@ macro_rules! specific_symbol {
                                     found: forms[0].clone(),
                                 }],
                                 tokens_consumed: 0,
+                                span: None,
+                                fatal: false,
                             })
                         }
                     }
@@ -62,7 +64,7 @@ macro_rules! specific_keyword {
             fn [<$name _keyword>]<'a>(
                 forms: &'a [Located<AST<'a>>]
             ) -> ASTParseResult<'a, &'a Keyword<'a>> {
-                located(map_res(keyword, |result| match result {
+                located(map_result(keyword, |result| match result {
                     Ok((rest, keyword)) => {
                         if keyword.name == $key_name {
                             Ok((rest, keyword))
@@ -73,6 +75,8 @@ macro_rules! specific_keyword {
                                     found: forms[0].clone(),
                                 }],
                                 tokens_consumed: 0,
+                                span: None,
+                                fatal: false,
                             })
                         }
                     }
@@ -97,6 +101,8 @@ fn located<'a, O>(
             return Err(TokenParseError {
                 errors: vec![TokenParseErrorKind::NotEnoughToken],
                 tokens_consumed: 0,
+                span: None,
+                fatal: false,
             });
         }
         let from = forms[0].range;
@@ -131,6 +137,8 @@ fn parse_require<'a>(forms_in_ns_list: &'a [Located<AST<'a>>]) -> ASTParseResult
                     "Invalid require vector".to_owned(),
                 )],
                 tokens_consumed: 0,
+                span: None,
+                fatal: false,
             });
         }
         located(map(symbol, |sym| NamespaceOnly {
@@ -150,7 +158,7 @@ fn parse_require<'a>(forms_in_ns_list: &'a [Located<AST<'a>>]) -> ASTParseResult
             tuple((
                 symbol,
                 refer_keyword,
-                map_res(vector, |res| match res {
+                map_result(vector, |res| match res {
                     Ok((_, forms_in_refer_vector)) => map(many0_until_end(symbol), |sym| {
                         sym.into_iter()
                             .map(|x| x.name.to_string())
@@ -235,7 +243,7 @@ fn parse_map_key<'a>(forms_in_map: &'a [Located<AST<'a>>]) -> NotLocatedASTParse
 
 fn parse_type<'a>(forms: &'a [Located<AST<'a>>]) -> ASTParseResult<'a, Type> {
     fn parse_map_type<'a>(forms: &'a [Located<AST<'a>>]) -> NotLocatedASTParseResult<'a, Type> {
-        map_res(parser::ast::parser::map, |res| match res {
+        map_result(parser::ast::parser::map, |res| match res {
             Ok((_, forms_in_map)) => {
                 map(many0_until_end(tuple((parse_map_key, parse_type))), |kvs| {
                     Type::Map(
@@ -268,6 +276,8 @@ fn parse_annotation<'a>(forms: &'a [Located<AST<'a>>]) -> ASTParseResult<'a, Typ
                 found: forms[0].clone(),
             }],
             tokens_consumed: 0,
+            span: None,
+            fatal: false,
         });
     }
     let (rest, ty) = parse_type(rest)?;
@@ -287,12 +297,14 @@ fn parse_expression<'a>(forms: &'a [Located<AST<'a>>]) -> ASTParseResult<'a, Exp
         return Err(TokenParseError {
             errors: vec![TokenParseErrorKind::NotEnoughToken],
             tokens_consumed: 0,
+            span: None,
+            fatal: false,
         });
     }
     let (_, expr) = context(
         "expression",
         located(alt((
-            map_res(parser::ast::parser::list, |res| match res {
+            map_result(parser::ast::parser::list, |res| match res {
                 Ok((rest, forms)) => {
                     if forms.len() == 0 {
                         return Ok((rest, Expression::Unknown));
@@ -352,21 +364,21 @@ fn parse_expression<'a>(forms: &'a [Located<AST<'a>>]) -> ASTParseResult<'a, Exp
             map(float_literal, |v| Expression::FloatLiteral(*v)),
             map(symbol, |sym| Expression::SymbolRef(sym)),
             map(keyword, |key| Expression::Keyword(key)),
-            map_res(parser::ast::parser::vector, |res| match res {
+            map_result(parser::ast::parser::vector, |res| match res {
                 Ok((rest, forms)) => {
                     let (_, exprs) = many0_until_end(parse_expression)(forms)?;
                     Ok((rest, Expression::VectorLiteral(exprs)))
                 }
                 Err(err) => Err(err),
             }),
-            map_res(parser::ast::parser::set, |res| match res {
+            map_result(parser::ast::parser::set, |res| match res {
                 Ok((rest, forms)) => {
                     let (_, exprs) = many0_until_end(parse_expression)(forms)?;
                     Ok((rest, Expression::SetLiteral(exprs)))
                 }
                 Err(err) => Err(err),
             }),
-            map_res(parser::ast::parser::map, |res| match res {
+            map_result(parser::ast::parser::map, |res| match res {
                 Ok((rest, kvs)) => {
                     let (_, map_expr) = map(
                         many0_until_end(tuple((parse_expression, parse_expression))),
@@ -378,7 +390,7 @@ fn parse_expression<'a>(forms: &'a [Located<AST<'a>>]) -> ASTParseResult<'a, Exp
                 }
                 Err(err) => Err(err),
             }),
-            map_res(parser::ast::parser::anonymous_fn, |res| match res {
+            map_result(parser::ast::parser::anonymous_fn, |res| match res {
                 Ok((rest, forms_in_list)) => {
                     let (_, exprs) = many0_until_end(parse_expression)(&forms_in_list)?;
                     Ok((rest, Expression::AnonymousFn(exprs)))
@@ -398,23 +410,40 @@ fn parse_expression_and_box<'a>(
     located(map(parse_expression, |expr| Box::new(expr.value)))(forms)
 }
 
-pub fn parse_metas<'a>(forms: &'a [Located<AST<'a>>]) -> NotLocatedASTParseResult<'a, Vec<Located<Metadata>>> {
-    many0(located(map_res(metadata, |meta| match meta {
-        Ok((rest, ast)) => Ok((rest, Metadata::try_from_ast(ast)?)),
-        Err(err) => Err(err),
-    })))(forms)
+// metadata wraps the form it is attached to, so `parser` runs on that form, which it has to
+// consume whole, and the metas come alongside
+pub fn with_metas<'a, O>(
+    mut parser: impl FnMut(&'a [Located<AST<'a>>]) -> NotLocatedASTParseResult<'a, O>,
+) -> impl FnMut(&'a [Located<AST<'a>>]) -> NotLocatedASTParseResult<'a, (Vec<Located<Metadata<'a>>>, O)> {
+    move |forms: &'a [Located<AST<'a>>]| {
+        if let Ok((rest, (metas, form))) = metadata(forms) {
+            let metas = metas
+                .iter()
+                .map(|meta| {
+                    Ok(Located {
+                        range: meta.range,
+                        value: Metadata::try_from_ast(&meta.value)?,
+                    })
+                })
+                .collect::<Result<Vec<_>, TokenParseError<_>>>()?;
+            let (_, output) = all_consuming(&mut parser)(std::slice::from_ref(form.as_ref()))?;
+            return Ok((rest, (metas, output)));
+        }
+        let (rest, output) = parser(forms)?;
+        Ok((rest, (vec![], output)))
+    }
 }
 
 pub fn parse_function_decl<'a>(forms: &'a [Located<AST<'a>>]) -> ASTParseResult<'a, FunctionDecl> {
-    fn parse_argument<'a>(forms: &'a [Located<AST<'a>>]) -> ASTParseResult<'a, Argument> {
+    fn parse_argument<'a>(forms: &'a [Located<AST<'a>>]) -> ASTParseResult<'a, Argument<'a>> {
         located(map(
             tuple((
-                parse_metas,
                 opt(parser::ast::parser::and),
-                parse_binding,
+                with_metas(parse_binding),
                 opt(parse_annotation),
             )),
-            |(metas, opt_and, binding, ty_annotation)| Argument {
+            |(opt_and, (metas, binding), ty_annotation)| Argument {
+                meta_data: metas.into_iter().map(|meta| meta.value).collect(),
                 is_var_arg: opt_and.is_some(),
                 binding,
                 ty_annotation,
@@ -426,10 +455,10 @@ pub fn parse_function_decl<'a>(forms: &'a [Located<AST<'a>>]) -> ASTParseResult<
         located(map(
             tuple((
                 defn_symbol,
-                symbol,
+                with_metas(symbol),
                 opt(string_literal), // doc string
                 opt(parse_annotation),
-                map_res(vector, |res| match res {
+                map_result(vector, |res| match res {
                     Ok((rest, args_vec)) => {
                         let (_, args) = many0_until_end(parse_argument)(&args_vec)?;
                         Ok((rest, args))
@@ -437,11 +466,11 @@ pub fn parse_function_decl<'a>(forms: &'a [Located<AST<'a>>]) -> ASTParseResult<
                     Err(err) => Err(err),
                 }),
             )),
-            |(_, name_sym, _, , opt_return_type, args)| FunctionDecl {
+            |(_, (metas, name_sym), _, opt_return_type, args)| FunctionDecl {
                 name: name_sym.name.to_string(),
                 return_type: opt_return_type,
                 arguments: args,
-                meta_data
+                meta_data: metas.into_iter().map(|meta| meta.value).collect(),
             },
         )),
     )(forms)
@@ -479,6 +508,32 @@ fn parse_function_test() {
     dbg!(&source);
 }
 
+#[test]
+fn parse_function_decl_keeps_metas() {
+    let source = "(defn ^:private add [^long a ^{:tag Long} b] (+ a b))";
+    let (_, tokens) = lexer::tokenize(Span::from(source)).unwrap();
+    let (_, ast) = parser::parse_form(&tokens).unwrap();
+    let forms_in_list = ast.list_or_none().unwrap();
+    let (_, decl) = parse_function_decl(forms_in_list).unwrap();
+    assert_eq!(
+        decl.value.meta_data,
+        vec![Metadata::Keyword(Keyword {
+            ns: None,
+            name: "private".into(),
+            auto_resolved: false,
+        })]
+    );
+    let arguments = &decl.value.arguments;
+    assert_eq!(
+        arguments[0].value.meta_data,
+        vec![Metadata::Symbol(Symbol {
+            ns: None,
+            name: "long".into(),
+        })]
+    );
+    assert!(matches!(arguments[1].value.meta_data[..], [Metadata::Map(_)]));
+}
+
 fn parse_def<'a>(toplevel_forms: &'a [Located<AST<'a>>]) -> ASTParseResult<'a, Define> {
     let (rest, forms_in_list) = list(toplevel_forms)?;
     let (_, def) = context(
@@ -492,7 +547,7 @@ fn parse_def<'a>(toplevel_forms: &'a [Located<AST<'a>>]) -> ASTParseResult<'a, D
                 parse_expression,
             )),
             |(_, name_sym, ty, _, value)| Define {
-                name: name_sym.name,
+                name: &name_sym.name,
                 ty,
                 value,
             },
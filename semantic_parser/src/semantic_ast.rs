@@ -144,7 +144,8 @@ impl Display for Type {
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct Argument {
+pub struct Argument<'a> {
+    pub meta_data: Vec<Metadata<'a>>,
     pub binding: Located<Binding>,
     pub ty_annotation: Option<Located<Type>>,
     pub is_var_arg: bool
@@ -174,7 +175,7 @@ impl Metadata<'_> {
 pub struct FunctionDecl<'a> {
     pub name: String,
     pub meta_data: Vec<Metadata<'a>>,
-    pub arguments: Vec<Located<Argument>>,
+    pub arguments: Vec<Located<Argument<'a>>>,
     pub return_type: Option<Located<Type>>,
 }
 
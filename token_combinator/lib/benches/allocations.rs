@@ -0,0 +1,75 @@
+// Counts the allocations made by many0 and many0_capacity over a file of several thousand forms.
+// Run with `cargo bench --bench allocations`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use token_combinator::*;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[derive(Debug, Clone, PartialEq, TokenParser)]
+pub enum Token<'a> {
+    LParen,
+    RParen,
+    Symbol(&'a str),
+}
+
+use parser::*;
+
+const FORMS: usize = 5000;
+
+fn measure<O>(name: &str, mut f: impl FnMut() -> O) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let output = f();
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    drop(output);
+    println!(
+        "{:<20} {:>8} allocations {:>10.2?}",
+        name, allocations, elapsed
+    );
+}
+
+fn main() {
+    // (defn f a b c d e f g h) repeated
+    let mut tokens = Vec::new();
+    for _ in 0..FORMS {
+        tokens.push(Token::LParen);
+        tokens.extend(["defn", "f", "a", "b", "c", "d", "e", "f", "g", "h"].map(Token::Symbol));
+        tokens.push(Token::RParen);
+    }
+    let tokens = tokens.as_slice();
+
+    measure("many0", || {
+        many0(delimited(l_paren, many0(symbol), r_paren))(tokens).unwrap()
+    });
+    measure("many0_capacity", || {
+        many0_capacity(
+            delimited(l_paren, many0_capacity(symbol, 10), r_paren),
+            FORMS,
+        )(tokens)
+        .unwrap()
+    });
+}
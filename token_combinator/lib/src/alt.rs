@@ -4,14 +4,78 @@ use crate::*;
 
 pub trait Alt<'a, T, O> {
     fn alt(&mut self, tokens: &'a [T]) -> TokenParseResult<'a, T, O>;
+    fn alt_all(&mut self, tokens: &'a [T]) -> TokenParseResult<'a, T, O>;
 }
 
+/// Tries each parser of the tuple in order. Tuples of up to 23 parsers are supported.
 pub fn alt<'a, T, O, List: Alt<'a, T, O>>(
     mut l: List,
 ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
     move |tokens: &'a [T]| l.alt(tokens)
 }
 
+/// Like `alt`, but merges the top error of every branch that advanced the furthest,
+/// so the error lists everything that could have appeared there.
+pub fn alt_all<'a, T, O, List: Alt<'a, T, O>>(
+    mut l: List,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+    move |tokens: &'a [T]| l.alt_all(tokens)
+}
+
+/// Like `alt`, but when every branch fails, `fallback` runs on the same input instead, e.g. to
+/// skip an unknown form so the rest can still be parsed. A fatal error does not stop it: the
+/// fallback is there for recovery rather than as another branch.
+pub fn alt_or<'a, T, O, List: Alt<'a, T, O>>(
+    mut l: List,
+    mut fallback: impl TokenParser<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+    move |tokens: &'a [T]| l.alt(tokens).or_else(|_| fallback.parse(tokens))
+}
+
+fn merge_errors<T>(errors: Vec<TokenParseError<T>>) -> TokenParseError<T> {
+    let max_consumed = errors
+        .iter()
+        .map(|err| err.tokens_consumed)
+        .max()
+        .unwrap_or(0);
+    let mut merged: Option<TokenParseError<T>> = None;
+    for err in errors
+        .into_iter()
+        .filter(|err| err.tokens_consumed == max_consumed)
+    {
+        let Some(kind) = err.errors.into_iter().next() else {
+            continue;
+        };
+        match &mut merged {
+            None => {
+                merged = Some(TokenParseError {
+                    errors: vec![kind],
+                    tokens_consumed: err.tokens_consumed,
+                    span: err.span,
+                    fatal: false,
+                })
+            }
+            Some(merged) => {
+                let duplicated =
+                    merged
+                        .errors
+                        .iter()
+                        .any(|merged_kind| match (merged_kind, &kind) {
+                            (
+                                TokenParseErrorKind::Expects { expects: a, .. },
+                                TokenParseErrorKind::Expects { expects: b, .. },
+                            ) => a == b,
+                            (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b),
+                        });
+                if !duplicated {
+                    merged.errors.push(kind);
+                }
+            }
+        }
+    }
+    merged.unwrap_or_else(|| TokenParseError::from_error_kind(TokenParseErrorKind::Fail))
+}
+
 macro_rules! alt_trait_impl {
     ($n:expr) => {
       seq!(N in 0..$n {
@@ -25,9 +89,18 @@ macro_rules! alt_trait_impl {
             let mut _max_consumed_tokens_len = 0;
             let mut _max_token_consumed_error: Option<TokenParseError<T>> = None;
             #(
-              match self.N.parse(_tokens) {
+              #[cfg(feature = "trace")]
+              crate::trace::enter(format!("alt branch {}", N), None, _tokens.len());
+              let result = self.N.parse(_tokens);
+              #[cfg(feature = "trace")]
+              crate::trace::exit(&result);
+              match result {
+                Err(err) if err.fatal => return Err(err),
                 Err(err) => {
-                  if err.tokens_consumed >= _max_consumed_tokens_len {
+                  // keeps the earliest branch on ties
+                  if _max_token_consumed_error.is_none()
+                    || err.tokens_consumed > _max_consumed_tokens_len
+                  {
                     _max_consumed_tokens_len = err.tokens_consumed;
                     _max_token_consumed_error = Some(err);
                   }
@@ -38,6 +111,24 @@ macro_rules! alt_trait_impl {
 
             Err(_max_token_consumed_error.unwrap())
           }
+
+          fn alt_all(&mut self, _tokens: &'a [T]) -> TokenParseResult<'a, T, O> {
+            let mut _errors = Vec::new();
+            #(
+              #[cfg(feature = "trace")]
+              crate::trace::enter(format!("alt_all branch {}", N), None, _tokens.len());
+              let result = self.N.parse(_tokens);
+              #[cfg(feature = "trace")]
+              crate::trace::exit(&result);
+              match result {
+                Err(err) if err.fatal => return Err(err),
+                Err(err) => _errors.push(err),
+                result => return result
+              }
+            )*
+
+            Err(merge_errors(_errors))
+          }
       }
       });
     };
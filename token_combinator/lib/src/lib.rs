@@ -1,14 +1,28 @@
+use std::fmt::{Debug, Display};
+use std::marker::PhantomData;
+
 mod alt;
 mod permutation;
+#[cfg(feature = "trace")]
+pub mod trace;
 mod tuple;
 
-pub use alt::alt;
+pub use alt::{alt, alt_all, alt_or};
 pub use permutation::permutation;
 pub use token_combinator_macros::TokenParser;
 pub use tuple::tuple;
 
 // T stands for Token
 // O stands for Output
+// E stands for Error, defaulting to TokenParseError
+
+// how many more tokens an incomplete input needs, like nom's Needed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Needed {
+    Unknown,
+    // at least this many
+    Size(usize),
+}
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TokenParseErrorKind<T> {
@@ -18,12 +32,22 @@ pub enum TokenParseErrorKind<T> {
     InfiniteLoop,
     Context(&'static str),
     Other(String),
+    // the input ended where more tokens could have finished it, see streaming
+    Incomplete(Needed),
+    // unbalanced delimiters, holding the delimiter tokens themselves so both ends can be reported
+    UnexpectedClose { found: T },
+    UnclosedOpen { open: T, expects: &'static str },
+    MismatchedClose { open: T, expects: &'static str, found: T },
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct TokenParseError<T> {
     pub errors: Vec<TokenParseErrorKind<T>>,
     pub tokens_consumed: usize,
+    // start/end token indices of the offending tokens, relative to the same input as tokens_consumed
+    pub span: Option<(usize, usize)>,
+    // set by cut, stops alt, opt and the repetition combinators from backtracking
+    pub fatal: bool,
 }
 
 impl<T> TokenParseError<T> {
@@ -31,12 +55,36 @@ impl<T> TokenParseError<T> {
         TokenParseError {
             errors: vec![kind],
             tokens_consumed: 0,
+            span: None,
+            fatal: false,
         }
     }
+    // moves the error past `tokens_consumed` more tokens, those consumed before the failing parser
+    // was called, so it stays relative to the input of the combinator that called it
     pub fn with_tokens_consumed(self, tokens_consumed: usize) -> Self {
         TokenParseError {
             errors: self.errors,
-            tokens_consumed,
+            tokens_consumed: self.tokens_consumed + tokens_consumed,
+            span: self
+                .span
+                .map(|(start, end)| (start + tokens_consumed, end + tokens_consumed)),
+            fatal: self.fatal,
+        }
+    }
+    // an error from a parser that was handed `rest`, a suffix of `tokens`, made relative to `tokens`
+    pub fn relative_to(self, tokens: &[T], rest: &[T]) -> Self {
+        self.with_tokens_consumed(tokens.len() - rest.len())
+    }
+    pub fn with_span(self, start: usize, end: usize) -> Self {
+        TokenParseError {
+            span: Some((start, end)),
+            ..self
+        }
+    }
+    pub fn into_fatal(self) -> Self {
+        TokenParseError {
+            fatal: true,
+            ..self
         }
     }
     pub fn with_error_appended(self, kind: TokenParseErrorKind<T>) -> Self {
@@ -45,26 +93,276 @@ impl<T> TokenParseError<T> {
         TokenParseError {
             errors,
             tokens_consumed: self.tokens_consumed,
+            span: self.span,
+            fatal: self.fatal,
+        }
+    }
+}
+
+impl<T: Display> Display for TokenParseErrorKind<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenParseErrorKind::Expects { expects, found } => {
+                write!(f, "expected {}, found {}", expects, found)
+            }
+            TokenParseErrorKind::NotEnoughToken => f.write_str("not enough tokens"),
+            TokenParseErrorKind::Fail => f.write_str("failed"),
+            TokenParseErrorKind::InfiniteLoop => f.write_str("infinite loop"),
+            TokenParseErrorKind::Context(context) => write!(f, "in {}", context),
+            TokenParseErrorKind::Other(message) => f.write_str(message),
+            TokenParseErrorKind::Incomplete(Needed::Unknown) => f.write_str("incomplete input"),
+            TokenParseErrorKind::Incomplete(Needed::Size(n)) => {
+                write!(f, "incomplete input, {} more tokens needed", n)
+            }
+            TokenParseErrorKind::UnexpectedClose { found } => write!(f, "unmatched `{}`", found),
+            TokenParseErrorKind::UnclosedOpen { open, expects } => {
+                write!(f, "unclosed `{}`, expected `{}`", open, expects)
+            }
+            TokenParseErrorKind::MismatchedClose { expects, found, .. } => {
+                write!(f, "expected `{}`, found `{}`", expects, found)
+            }
         }
     }
 }
 
-pub type TokenParseResult<'a, T, O> = Result<(&'a [T], O), TokenParseError<T>>;
+// renders the innermost error first, e.g. "expected ident, found | (in vector) (in list) at token 1"
+impl<T: Display> Display for TokenParseError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // alternatives merged by alt_all are listed as "expected one of a, b or c"
+        let alternatives = self
+            .errors
+            .iter()
+            .take_while(|kind| matches!(kind, TokenParseErrorKind::Expects { .. }))
+            .count();
+        let mut skip = 0;
+        if alternatives > 1 {
+            f.write_str("expected one of ")?;
+            for (i, kind) in self.errors[..alternatives].iter().enumerate() {
+                if let TokenParseErrorKind::Expects { expects, .. } = kind {
+                    match i {
+                        0 => write!(f, "{}", expects)?,
+                        _ if i == alternatives - 1 => write!(f, " or {}", expects)?,
+                        _ => write!(f, ", {}", expects)?,
+                    }
+                }
+            }
+            if let TokenParseErrorKind::Expects { found, .. } = &self.errors[0] {
+                write!(f, ", found {}", found)?;
+            }
+            skip = alternatives;
+        }
+        for (i, kind) in self.errors.iter().enumerate().skip(skip) {
+            match kind {
+                TokenParseErrorKind::Context(_) => write!(f, " ({})", kind)?,
+                _ if i == 0 => write!(f, "{}", kind)?,
+                _ => write!(f, "; {}", kind)?,
+            }
+        }
+        write!(f, " at token {}", self.tokens_consumed)
+    }
+}
 
-pub trait TokenParser<'a, T, O> {
-    fn parse(&mut self, tokens: &'a [T]) -> Result<(&'a [T], O), TokenParseError<T>>;
+impl<T: Debug + Display> std::error::Error for TokenParseError<T> {}
+
+impl<T> From<TokenParseErrorKind<T>> for TokenParseError<T> {
+    fn from(kind: TokenParseErrorKind<T>) -> Self {
+        TokenParseError::from_error_kind(kind)
+    }
 }
 
-impl<'a, T, O, F> TokenParser<'a, T, O> for F
+pub type TokenParseResult<'a, T, O, E = TokenParseError<T>> = Result<(&'a [T], O), E>;
+
+pub trait TokenParser<'a, T, O, E = TokenParseError<T>> {
+    fn parse(&mut self, tokens: &'a [T]) -> Result<(&'a [T], O), E>;
+
+    // for top-level call sites: the output alone, failing like all_consuming unless every token
+    // was consumed
+    fn parse_complete(&mut self, tokens: &'a [T]) -> Result<O, E>
+    where
+        T: Clone,
+        E: From<TokenParseError<T>>,
+    {
+        let (rest, output) = self.parse(tokens)?;
+        eof(rest).map_err(|err| err.relative_to(tokens, rest))?;
+        Ok(output)
+    }
+
+    // chaining versions of the free functions, e.g. symbol.map(Expr::Sym).or(keyword.map(Expr::Kw))
+
+    fn map<O2>(
+        mut self,
+        mapper: impl FnMut(O) -> O2,
+    ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2, E>
+    where
+        Self: Sized,
+        T: 'a,
+    {
+        map(move |tokens: &'a [T]| self.parse(tokens), mapper)
+    }
+
+    fn map_res<O2, EMapper>(
+        mut self,
+        mapper: impl FnMut(O) -> Result<O2, EMapper>,
+    ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2, E>
+    where
+        Self: Sized,
+        T: 'a,
+        E: From<EMapper>,
+    {
+        map_res(move |tokens: &'a [T]| self.parse(tokens), mapper)
+    }
+
+    fn and_then<O2>(
+        mut self,
+        mut second: impl TokenParser<'a, T, O2>,
+    ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, (O, O2)>
+    where
+        Self: Sized,
+        T: 'a,
+        E: Into<TokenParseError<T>>,
+    {
+        pair(
+            move |tokens: &'a [T]| self.parse(tokens).map_err(Into::into),
+            move |tokens: &'a [T]| second.parse(tokens),
+        )
+    }
+
+    fn flat_map<O2, P: TokenParser<'a, T, O2>>(
+        mut self,
+        make_parser: impl FnMut(O) -> P,
+    ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2>
+    where
+        Self: Sized,
+        T: 'a,
+        E: Into<TokenParseError<T>>,
+    {
+        flat_map(
+            move |tokens: &'a [T]| self.parse(tokens).map_err(Into::into),
+            make_parser,
+        )
+    }
+
+    fn or(
+        mut self,
+        other: impl TokenParser<'a, T, O>,
+    ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>
+    where
+        Self: Sized,
+        T: 'a,
+        E: Into<TokenParseError<T>>,
+    {
+        alt((
+            move |tokens: &'a [T]| self.parse(tokens).map_err(Into::into),
+            other,
+        ))
+    }
+
+    fn opt(mut self) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Option<O>>
+    where
+        Self: Sized,
+        T: 'a,
+        E: Into<TokenParseError<T>>,
+    {
+        opt(move |tokens: &'a [T]| self.parse(tokens).map_err(Into::into))
+    }
+
+    fn many0(mut self) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>>
+    where
+        Self: Sized,
+        T: 'a,
+        E: Into<TokenParseError<T>>,
+    {
+        many0(move |tokens: &'a [T]| self.parse(tokens).map_err(Into::into))
+    }
+}
+
+impl<'a, T, O, E, F> TokenParser<'a, T, O, E> for F
 where
     T: 'a,
-    F: FnMut(&'a [T]) -> Result<(&'a [T], O), TokenParseError<T>>,
+    F: FnMut(&'a [T]) -> Result<(&'a [T], O), E>,
 {
-    fn parse(&mut self, tokens: &'a [T]) -> Result<(&'a [T], O), TokenParseError<T>> {
+    fn parse(&mut self, tokens: &'a [T]) -> Result<(&'a [T], O), E> {
         self(tokens)
     }
 }
 
+// a parser as a value: it can sit in a struct field or a collection, e.g. a table of reader macro
+// handlers boxed into the same type, and is Clone and Copy when the function is
+#[derive(Debug, Clone, Copy)]
+pub struct Parser<F>(F);
+
+impl<F> Parser<F> {
+    pub fn new(f: F) -> Self {
+        Parser(f)
+    }
+}
+
+impl<'a, T, O, E> Parser<BoxedParser<'a, T, O, E>> {
+    pub fn boxed(parser: impl TokenParser<'a, T, O, E> + 'a) -> Self {
+        Parser(boxed(parser))
+    }
+}
+
+impl<'a, T, O, E, F> TokenParser<'a, T, O, E> for Parser<F>
+where
+    T: 'a,
+    F: FnMut(&'a [T]) -> Result<(&'a [T], O), E>,
+{
+    fn parse(&mut self, tokens: &'a [T]) -> Result<(&'a [T], O), E> {
+        (self.0)(tokens)
+    }
+}
+
+// boxing erases the parser type, so recursive grammars can name their parsers
+pub type BoxedParser<'a, T, O, E = TokenParseError<T>> =
+    Box<dyn FnMut(&'a [T]) -> TokenParseResult<'a, T, O, E> + 'a>;
+
+pub fn boxed<'a, T, O, E>(
+    mut parser: impl TokenParser<'a, T, O, E> + 'a,
+) -> BoxedParser<'a, T, O, E> {
+    Box::new(move |tokens: &'a [T]| parser.parse(tokens))
+}
+
+/// Defers building the parser until it is first used, then reuses it.
+///
+/// Recursive grammars can refer to themselves through `lazy` without recursing forever
+/// while the parsers are being built:
+///
+/// ```
+/// use token_combinator::*;
+///
+/// #[derive(Debug, Clone, PartialEq, TokenParser)]
+/// enum Token<'a> {
+///     LParen,
+///     RParen,
+///     Atom(&'a str),
+/// }
+/// use parser::*;
+///
+/// // the nesting depth of a form
+/// fn depth<'a>() -> BoxedParser<'a, Token<'a>, usize> {
+///     boxed(alt((
+///         map(atom, |_| 0),
+///         map(delimited(l_paren, many0(lazy(depth)), r_paren), |depths| {
+///             depths.into_iter().max().unwrap_or(0) + 1
+///         }),
+///     )))
+/// }
+///
+/// # fn main() {
+/// let tokens = [Token::LParen, Token::LParen, Token::Atom("a"), Token::RParen, Token::RParen];
+/// assert_eq!(depth()(&tokens), Ok((&[] as &[Token], 2)));
+/// # }
+/// ```
+pub fn lazy<'a, T, O, E, P: TokenParser<'a, T, O, E>>(
+    mut make_parser: impl FnMut() -> P,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O, E> {
+    let mut parser: Option<P> = None;
+    move |tokens: &'a [T]| parser.get_or_insert_with(&mut make_parser).parse(tokens)
+}
+
+// how parsers get at the token inside a wrapper W: by reference, so neither W nor the token needs
+// to be Copy or convertible, and comparing kinds (see the derived `kind()`) copies nothing
 pub trait UnwrapToken<T> {
     fn unwrap_token(&self) -> &T;
 }
@@ -75,6 +373,129 @@ impl<T> UnwrapToken<T> for T {
     }
 }
 
+// W (Wrapper) carrying the start/end offsets of a token or of a parsed node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Spanned<W> {
+    pub token: W,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl<W> UnwrapToken<W> for Spanned<W> {
+    fn unwrap_token(&self) -> &W {
+        &self.token
+    }
+}
+
+pub fn spanned<'a, W: 'a, O>(
+    mut parser: impl TokenParser<'a, Spanned<W>, O>,
+) -> impl FnMut(&'a [Spanned<W>]) -> TokenParseResult<'a, Spanned<W>, Spanned<O>> {
+    move |tokens: &'a [Spanned<W>]| {
+        let (rest, output) = parser.parse(tokens)?;
+        let consumed = &tokens[..tokens.len() - rest.len()];
+        let start = tokens.first().map_or(0, |token| token.start);
+        let end = consumed.last().map_or(start, |token| token.end);
+        Ok((
+            rest,
+            Spanned {
+                token: output,
+                start,
+                end,
+            },
+        ))
+    }
+}
+
+// a parsed node with the start/end token indices of what it was parsed from. Unlike Spanned, which
+// takes the offsets the tokens carry, it only counts tokens, so it works for any T.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TokenSpan<O> {
+    pub value: O,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl<O> TokenSpan<O> {
+    // the tokens the value was parsed from, given the slice the indices point into, or None when
+    // that slice is too short to be it
+    pub fn tokens<'a, T>(&self, original: &'a [T]) -> Option<&'a [T]> {
+        original.get(self.start..self.end)
+    }
+}
+
+// `original` is the slice the whole parse started on. A parser is only handed the tokens left to
+// parse, which tell how many come after it but not how many came before, so the start index is
+// counted back from the end of `original`. Every input must be a suffix of it, however deeply
+// `token_span` is nested.
+pub fn token_span<'a, T: 'a, O>(
+    original: &'a [T],
+    mut parser: impl TokenParser<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, TokenSpan<O>> {
+    move |tokens: &'a [T]| {
+        let (rest, value) = parser.parse(tokens)?;
+        Ok((
+            rest,
+            TokenSpan {
+                value,
+                start: original.len() - tokens.len(),
+                end: original.len() - rest.len(),
+            },
+        ))
+    }
+}
+
+// traces the parser to stderr with the `debug` feature: the label, the first tokens it was given
+// and how far it got. Without the feature it is the parser itself.
+#[cfg(feature = "debug")]
+pub fn dbg_parser<'a, T: 'a + Debug, O>(
+    label: &'static str,
+    mut parser: impl TokenParser<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+    move |tokens: &'a [T]| {
+        let head = &tokens[..tokens.len().min(3)];
+        let result = parser.parse(tokens);
+        match &result {
+            Ok((rest, _)) => eprintln!(
+                "{}: {:?} -> ok, {} tokens consumed",
+                label,
+                head,
+                tokens.len() - rest.len()
+            ),
+            Err(err) => eprintln!(
+                "{}: {:?} -> failed, {} tokens consumed: {:?}",
+                label, head, err.tokens_consumed, err.errors
+            ),
+        }
+        result
+    }
+}
+
+#[cfg(not(feature = "debug"))]
+pub fn dbg_parser<'a, T: 'a + Debug, O>(
+    _label: &'static str,
+    mut parser: impl TokenParser<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+    move |tokens: &'a [T]| parser.parse(tokens)
+}
+
+// records the parser and whatever traced parsers and alt branches it runs as an attempt tree with
+// the `trace` feature, see trace::take_attempts. Without the feature it is the parser itself.
+pub fn traced<'a, T: 'a + Debug, O>(
+    label: &'static str,
+    mut parser: impl TokenParser<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+    move |tokens: &'a [T]| {
+        #[cfg(feature = "trace")]
+        trace::enter(label.to_owned(), Some(trace::head(tokens)), tokens.len());
+        #[cfg(not(feature = "trace"))]
+        let _ = label;
+        let result = parser.parse(tokens);
+        #[cfg(feature = "trace")]
+        trace::exit(&result);
+        result
+    }
+}
+
 pub fn context<'a, T: 'a, O>(
     context: &'static str,
     mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
@@ -85,14 +506,37 @@ pub fn context<'a, T: 'a, O>(
     }
 }
 
+// a repetition whose parser, or item and separator, succeeded at `rest` without consuming anything
+fn infinite_loop<T>(tokens: &[T], rest: &[T]) -> TokenParseError<T> {
+    TokenParseError::from_error_kind(TokenParseErrorKind::InfiniteLoop)
+        .with_span(0, 0)
+        .relative_to(tokens, rest)
+}
+
 pub fn many1<'a, T, O>(
+    parser: impl TokenParser<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>>
+where
+    T: 'a,
+{
+    many1_capacity(parser, 0)
+}
+
+pub fn many1_capacity<'a, T, O>(
     mut parser: impl TokenParser<'a, T, O>,
+    capacity: usize,
 ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>>
 where
     T: 'a,
 {
     move |tokens: &'a [T]| {
-        let mut vec = Vec::new();
+        if tokens.is_empty() {
+            return Err(
+                TokenParseError::from_error_kind(TokenParseErrorKind::NotEnoughToken)
+                    .with_span(0, 0),
+            );
+        }
+        let mut vec = Vec::with_capacity(capacity);
         let mut rest = tokens;
         let mut last_len = rest.len();
         let mut succeeded_at_least_once = false;
@@ -100,9 +544,7 @@ where
             match parser.parse(rest) {
                 Ok((rest_tokens, item)) => {
                     if rest_tokens.len() == last_len {
-                        return Err(TokenParseError::from_error_kind(
-                            TokenParseErrorKind::InfiniteLoop,
-                        ));
+                        return Err(infinite_loop(tokens, rest));
                     }
                     last_len = rest_tokens.len();
                     rest = rest_tokens;
@@ -111,11 +553,13 @@ where
                     continue;
                 }
                 Err(err) => {
-                    if succeeded_at_least_once {
-                        break;
-                    } else {
+                    if !succeeded_at_least_once {
                         return Err(err);
                     }
+                    if err.fatal {
+                        return Err(err.relative_to(tokens, rest));
+                    }
+                    break;
                 }
             }
         }
@@ -123,29 +567,97 @@ where
     }
 }
 
+pub struct IterMany<'a, T, O, P> {
+    tokens: &'a [T],
+    rest: &'a [T],
+    parser: P,
+    finished: bool,
+    _output: PhantomData<O>,
+}
+
+impl<'a, T, O, P> IterMany<'a, T, O, P> {
+    pub fn rest(&self) -> &'a [T] {
+        self.rest
+    }
+}
+
+impl<'a, T, O, P> Iterator for IterMany<'a, T, O, P>
+where
+    P: TokenParser<'a, T, O>,
+{
+    type Item = Result<O, TokenParseError<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished || self.rest.is_empty() {
+            return None;
+        }
+        let consumed = self.tokens.len() - self.rest.len();
+        match self.parser.parse(self.rest) {
+            Ok((rest, _)) if rest.len() == self.rest.len() => {
+                self.finished = true;
+                Some(Err(TokenParseError::from_error_kind(
+                    TokenParseErrorKind::InfiniteLoop,
+                )
+                .with_tokens_consumed(consumed)))
+            }
+            Ok((rest, item)) => {
+                self.rest = rest;
+                Some(Ok(item))
+            }
+            Err(err) => {
+                // stops at the first error
+                self.finished = true;
+                Some(Err(err.with_tokens_consumed(consumed)))
+            }
+        }
+    }
+}
+
+pub fn iter_many<'a, T, O, P: TokenParser<'a, T, O>>(
+    tokens: &'a [T],
+    parser: P,
+) -> IterMany<'a, T, O, P> {
+    IterMany {
+        tokens,
+        rest: tokens,
+        parser,
+        finished: false,
+        _output: PhantomData,
+    }
+}
+
 pub fn many0<'a, T, O>(
+    parser: impl TokenParser<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>>
+where
+    T: 'a,
+{
+    many0_capacity(parser, 0)
+}
+
+pub fn many0_capacity<'a, T, O>(
     mut parser: impl TokenParser<'a, T, O>,
+    capacity: usize,
 ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>>
 where
     T: 'a,
 {
     move |tokens: &'a [T]| {
-        let mut vec = Vec::new();
+        let mut vec = Vec::with_capacity(capacity);
         let mut rest = tokens;
         let mut last_len = rest.len();
         while rest.len() > 0 {
             match parser.parse(rest) {
                 Ok((rest_tokens, item)) => {
                     if rest_tokens.len() == last_len {
-                        return Err(TokenParseError::from_error_kind(
-                            TokenParseErrorKind::InfiniteLoop,
-                        ));
+                        return Err(infinite_loop(tokens, rest));
                     }
                     last_len = rest_tokens.len();
                     rest = rest_tokens;
                     vec.push(item);
                     continue;
                 }
+                Err(err) if err.fatal => return Err(err.relative_to(tokens, rest)),
                 _ => break,
             }
         }
@@ -153,6 +665,67 @@ where
     }
 }
 
+pub fn fold_many0<'a, T, O, Acc>(
+    mut parser: impl TokenParser<'a, T, O>,
+    mut init: impl FnMut() -> Acc,
+    mut acc: impl FnMut(Acc, O) -> Acc,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Acc>
+where
+    T: 'a,
+{
+    move |tokens: &'a [T]| {
+        let mut result = init();
+        let mut rest = tokens;
+        while !rest.is_empty() {
+            match parser.parse(rest) {
+                Ok((rest_tokens, item)) => {
+                    if rest_tokens.len() == rest.len() {
+                        return Err(infinite_loop(tokens, rest));
+                    }
+                    rest = rest_tokens;
+                    result = acc(result, item);
+                }
+                Err(err) if err.fatal => return Err(err.relative_to(tokens, rest)),
+                Err(_) => break,
+            }
+        }
+        Ok((rest, result))
+    }
+}
+
+pub fn fold_many1<'a, T, O, Acc>(
+    mut parser: impl TokenParser<'a, T, O>,
+    mut init: impl FnMut() -> Acc,
+    mut acc: impl FnMut(Acc, O) -> Acc,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Acc>
+where
+    T: 'a,
+{
+    move |tokens: &'a [T]| {
+        if tokens.is_empty() {
+            return Err(TokenParseError::from_error_kind(
+                TokenParseErrorKind::NotEnoughToken,
+            ));
+        }
+        let (mut rest, first) = parser.parse(tokens)?;
+        let mut result = acc(init(), first);
+        while !rest.is_empty() {
+            match parser.parse(rest) {
+                Ok((rest_tokens, item)) => {
+                    if rest_tokens.len() == rest.len() {
+                        return Err(infinite_loop(tokens, rest));
+                    }
+                    rest = rest_tokens;
+                    result = acc(result, item);
+                }
+                Err(err) if err.fatal => return Err(err.relative_to(tokens, rest)),
+                Err(_) => break,
+            }
+        }
+        Ok((rest, result))
+    }
+}
+
 pub fn many0_until_end<'a, T, O>(
     mut parser: impl TokenParser<'a, T, O>,
 ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>>
@@ -167,9 +740,7 @@ where
             match parser.parse(rest) {
                 Ok((rest_tokens, item)) => {
                     if rest_tokens.len() == last_len {
-                        return Err(TokenParseError::from_error_kind(
-                            TokenParseErrorKind::InfiniteLoop,
-                        ));
+                        return Err(infinite_loop(tokens, rest));
                     }
                     last_len = rest_tokens.len();
                     rest = rest_tokens;
@@ -181,7 +752,95 @@ where
 
                     continue;
                 }
-                Err(err) => return Err(err),
+                Err(err) => return Err(err.relative_to(tokens, rest)),
+            }
+        }
+        Ok((rest, vec))
+    }
+}
+
+pub fn many_till<'a, T, O, OTill>(
+    mut parser: impl TokenParser<'a, T, O>,
+    mut till: impl TokenParser<'a, T, OTill>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, (Vec<O>, OTill)>
+where
+    T: 'a,
+{
+    move |tokens: &'a [T]| {
+        let mut vec = Vec::new();
+        let mut rest = tokens;
+        loop {
+            match till.parse(rest) {
+                Ok((rest_tokens, end)) => return Ok((rest_tokens, (vec, end))),
+                Err(err) if err.fatal => return Err(err.relative_to(tokens, rest)),
+                Err(_) => {}
+            }
+            match parser.parse(rest) {
+                Ok((rest_tokens, item)) => {
+                    if rest_tokens.len() == rest.len() {
+                        return Err(infinite_loop(tokens, rest));
+                    }
+                    rest = rest_tokens;
+                    vec.push(item);
+                }
+                Err(err) => return Err(err.relative_to(tokens, rest)),
+            }
+        }
+    }
+}
+
+pub fn many_m_n<'a, T, O>(
+    m: usize,
+    n: usize,
+    mut parser: impl TokenParser<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>>
+where
+    T: 'a,
+{
+    move |tokens: &'a [T]| {
+        if m > n {
+            return Err(TokenParseError::from_error_kind(TokenParseErrorKind::Fail));
+        }
+        let mut vec = Vec::new();
+        let mut rest = tokens;
+        while vec.len() < n {
+            match parser.parse(rest) {
+                Ok((rest_tokens, item)) => {
+                    if rest_tokens.len() == rest.len() {
+                        return Err(infinite_loop(tokens, rest));
+                    }
+                    rest = rest_tokens;
+                    vec.push(item);
+                }
+                Err(err) => {
+                    if vec.len() < m || err.fatal {
+                        return Err(err.relative_to(tokens, rest));
+                    }
+                    break;
+                }
+            }
+        }
+        Ok((rest, vec))
+    }
+}
+
+pub fn count<'a, T, O>(
+    mut parser: impl TokenParser<'a, T, O>,
+    n: usize,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>>
+where
+    T: 'a,
+{
+    move |tokens: &'a [T]| {
+        let mut vec = Vec::with_capacity(n);
+        let mut rest = tokens;
+        for _ in 0..n {
+            match parser.parse(rest) {
+                Ok((rest_tokens, item)) => {
+                    rest = rest_tokens;
+                    vec.push(item);
+                }
+                Err(err) => return Err(err.relative_to(tokens, rest)),
             }
         }
         Ok((rest, vec))
@@ -193,10 +852,53 @@ pub fn opt<'a, T, O>(
 ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Option<O>> {
     move |tokens: &'a [T]| match parser.parse(tokens) {
         Ok((rest, output)) => Ok((rest, Some(output))),
+        Err(err) if err.fatal => Err(err),
         Err(_) => Ok((tokens, None)),
     }
 }
 
+// opt with a fallback: a clone of `default` when the parser fails, e.g. the empty docstring of a defn
+// without one
+pub fn opt_or<'a, T: 'a, O: Clone>(
+    parser: impl TokenParser<'a, T, O>,
+    default: O,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+    map(opt(parser), move |output| {
+        output.unwrap_or_else(|| default.clone())
+    })
+}
+
+pub type OptConsumed<T, O> = (Option<O>, Option<TokenParseError<T>>);
+
+pub fn opt_consumed<'a, T, O>(
+    mut parser: impl TokenParser<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, OptConsumed<T, O>> {
+    move |tokens: &'a [T]| match parser.parse(tokens) {
+        Ok((rest, output)) => Ok((rest, (Some(output), None))),
+        Err(err) if err.fatal => Err(err),
+        // backtracks, but hands the inner error back so a started-but-malformed form can be reported
+        Err(err) => Ok((tokens, (None, Some(err)))),
+    }
+}
+
+pub fn cut<'a, T, O>(
+    mut parser: impl TokenParser<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+    move |tokens: &'a [T]| parser.parse(tokens).map_err(TokenParseError::into_fatal)
+}
+
+// the explicit counterpart of cut: on any failure, fatal or not, `skip` picks where parsing resumes and
+// the error becomes the output, so one bad item does not end the whole parse
+pub fn recover<'a, T: 'a, O>(
+    mut parser: impl TokenParser<'a, T, O>,
+    mut skip: impl FnMut(&'a [T], &TokenParseError<T>) -> &'a [T],
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Result<O, TokenParseError<T>>> {
+    move |tokens: &'a [T]| match parser.parse(tokens) {
+        Ok((rest, output)) => Ok((rest, Ok(output))),
+        Err(err) => Ok((skip(tokens, &err), Err(err))),
+    }
+}
+
 pub fn delimited<'a, T: 'a, O1, O2, O3>(
     mut l: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O1>,
     mut main: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2>,
@@ -204,20 +906,54 @@ pub fn delimited<'a, T: 'a, O1, O2, O3>(
 ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2> {
     move |tokens: &'a [T]| {
         let (rest, _) = l(tokens)?;
-        let (rest, result) = main(rest)?;
-        let (rest, _) = r(rest)?;
+        let (rest, result) = main(rest).map_err(|err| err.relative_to(tokens, rest))?;
+        let (rest, _) = r(rest).map_err(|err| err.relative_to(tokens, rest))?;
 
         Ok((rest, result))
     }
 }
 
+pub type OpenEnded<T, O> = (O, Option<TokenParseError<T>>);
+
+// like delimited, but when only `r` fails, keeps the output of `main` and hands the error of `r`
+// back as a diagnostic, so the items of a collection that is not closed yet are not lost.
+// The tokens `r` failed on are left in place.
+pub fn delimited_open_ended<'a, T: 'a, O1, O2, O3>(
+    mut l: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O1>,
+    mut main: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2>,
+    mut r: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O3>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, OpenEnded<T, O2>> {
+    move |tokens: &'a [T]| {
+        let (rest, _) = l(tokens)?;
+        let (rest, result) = main(rest).map_err(|err| err.relative_to(tokens, rest))?;
+        match r(rest) {
+            Ok((rest, _)) => Ok((rest, (result, None))),
+            Err(err) if err.fatal => Err(err.relative_to(tokens, rest)),
+            Err(err) => {
+                let missing_close = err.relative_to(tokens, rest);
+                Ok((rest, (result, Some(missing_close))))
+            }
+        }
+    }
+}
+
+// for token streams that keep whitespace and comments as tokens: skips any number of `whitespace`
+// tokens on both sides of `parser`, so rules need not handle them between every element.
+// The clojure lexer drops them from its tokens already.
+pub fn ws<'a, T: 'a, O, OWs>(
+    whitespace: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, OWs> + Clone,
+    parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+    delimited(many0(whitespace.clone()), parser, many0(whitespace))
+}
+
 pub fn preceded<'a, O1, T: 'a, O2>(
     mut first: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O1>,
     mut second: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2>,
 ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2> {
     move |tokens: &'a [T]| {
         let (rest, _) = first(tokens)?;
-        let (rest, result) = second(rest)?;
+        let (rest, result) = second(rest).map_err(|err| err.relative_to(tokens, rest))?;
 
         Ok((rest, result))
     }
@@ -229,32 +965,61 @@ pub fn terminated<'a, T: 'a, O1, O2>(
 ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O1> {
     move |tokens: &'a [T]| {
         let (rest, result) = first(tokens)?;
-        let (rest, _) = second(rest)?;
+        let (rest, _) = second(rest).map_err(|err| err.relative_to(tokens, rest))?;
 
         Ok((rest, result))
     }
 }
 
+pub fn pair<'a, T: 'a, O1, O2>(
+    mut first: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O1>,
+    mut second: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, (O1, O2)> {
+    move |tokens: &'a [T]| {
+        let (rest, result1) = first(tokens)?;
+        let (rest, result2) = second(rest).map_err(|err| err.relative_to(tokens, rest))?;
+
+        Ok((rest, (result1, result2)))
+    }
+}
+
+pub fn separated_pair<'a, T: 'a, O1, OSep, O2>(
+    mut first: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O1>,
+    mut separator: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, OSep>,
+    mut second: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, (O1, O2)> {
+    move |tokens: &'a [T]| {
+        let (rest, result1) = first(tokens)?;
+        let (rest, _) = separator(rest).map_err(|err| err.relative_to(tokens, rest))?;
+        let (rest, result2) = second(rest).map_err(|err| err.relative_to(tokens, rest))?;
+
+        Ok((rest, (result1, result2)))
+    }
+}
+
 pub fn separated_list0<'a, T: 'a, O, OSep>(
+    separator_parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, OSep>,
+    item_parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>> {
+    separated_list0_capacity(separator_parser, item_parser, 0)
+}
+
+pub fn separated_list0_capacity<'a, T: 'a, O, OSep>(
     mut separator_parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, OSep>,
     mut item_parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
+    capacity: usize,
 ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>> {
     move |tokens: &'a [T]| {
-        let mut items = Vec::new();
+        let mut items = Vec::with_capacity(capacity);
         let mut rest = tokens;
-        let mut last_len = rest.len();
-        while !tokens.is_empty() {
+        while !rest.is_empty() {
+            let last_len = rest.len();
             match item_parser(rest) {
                 Ok((rest_tokens, item)) => {
-                    if rest_tokens.len() == last_len {
-                        return Err(TokenParseError::from_error_kind(
-                            TokenParseErrorKind::InfiniteLoop,
-                        ));
-                    }
-                    last_len = rest_tokens.len();
                     rest = rest_tokens;
                     items.push(item);
                 }
+                Err(err) if err.fatal => return Err(err.relative_to(tokens, rest)),
                 Err(_) => return Ok((rest, items)),
             }
             if rest.is_empty() {
@@ -264,97 +1029,498 @@ pub fn separated_list0<'a, T: 'a, O, OSep>(
                 Ok((rest_tokens, _)) => {
                     rest = rest_tokens;
                 }
+                Err(err) if err.fatal => return Err(err.relative_to(tokens, rest)),
                 Err(_) => return Ok((rest, items)),
             }
+            // Neither the item nor the separator consumed anything.
+            if rest.len() == last_len {
+                return Err(infinite_loop(tokens, rest));
+            }
         }
-        Ok((&[], Vec::new()))
+        Ok((rest, items))
     }
 }
 
 pub fn separated_list1<'a, T: 'a, O, OSep>(
+    separator_parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, OSep>,
+    item_parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>> {
+    separated_list1_capacity(separator_parser, item_parser, 0)
+}
+
+pub fn separated_list1_capacity<'a, T: 'a, O, OSep>(
+    separator_parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, OSep>,
+    item_parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
+    capacity: usize,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>> {
+    fold_separated(
+        separator_parser,
+        item_parser,
+        move || Vec::with_capacity(capacity),
+        |mut items, item| {
+            items.push(item);
+            items
+        },
+    )
+}
+
+// separated_list1 without the Vec: folds the items as they are parsed
+pub fn fold_separated<'a, T: 'a, O, OSep, Acc>(
     mut separator_parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, OSep>,
     mut item_parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
-) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Vec<O>> {
+    mut init: impl FnMut() -> Acc,
+    mut acc: impl FnMut(Acc, O) -> Acc,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, Acc> {
     move |tokens: &'a [T]| {
-        let num_tokens = tokens.len();
-        let mut items = Vec::new();
+        let mut result = None;
         let mut rest = tokens;
-        let mut last_len = rest.len();
-        while !tokens.is_empty() {
+        while !rest.is_empty() {
+            let last_len = rest.len();
             match item_parser(rest) {
                 Ok((rest_tokens, item)) => {
-                    if rest_tokens.len() == last_len {
-                        return Err(TokenParseError::from_error_kind(
-                            TokenParseErrorKind::InfiniteLoop,
-                        ));
-                    }
-                    last_len = rest_tokens.len();
                     rest = rest_tokens;
-                    items.push(item);
-                }
-                Err(err) => {
-                    if items.len() > 0 {
-                        return Ok((rest, items));
-                    } else {
-                        return Err(err.with_tokens_consumed(num_tokens - rest.len()));
-                    }
+                    result = Some(acc(result.take().unwrap_or_else(&mut init), item));
                 }
+                Err(err) => match result {
+                    Some(result) if !err.fatal => return Ok((rest, result)),
+                    _ => return Err(err.relative_to(tokens, rest)),
+                },
             }
             if rest.is_empty() {
-                return Ok((rest, items));
+                break;
             }
             match separator_parser(rest) {
                 Ok((rest_tokens, _)) => {
                     rest = rest_tokens;
                 }
-                Err(_) => return Ok((rest, items)),
+                Err(err) if err.fatal => return Err(err.relative_to(tokens, rest)),
+                Err(_) => break,
+            }
+            // Neither the item nor the separator consumed anything.
+            if rest.len() == last_len {
+                return Err(infinite_loop(tokens, rest));
+            }
+        }
+        match result {
+            Some(result) => Ok((rest, result)),
+            // If tokens is empty, returns error.
+            None => Err(TokenParseError {
+                errors: vec![TokenParseErrorKind::NotEnoughToken],
+                tokens_consumed: 0,
+                span: Some((0, 0)),
+                fatal: false,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeparatedList<O> {
+    pub items: Vec<O>,
+    // whether a separator after the last item was consumed, like the comma of `[1, 2, 3,]`
+    pub trailing_separator: bool,
+}
+
+// like separated_list0, but tells whether the list ended with a separator. Only a separator that
+// follows an item is consumed, so a leading or doubled one is left for the caller.
+pub fn separated_list_trailing<'a, T: 'a, O, OSep>(
+    mut separator_parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, OSep>,
+    mut item_parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, SeparatedList<O>> {
+    move |tokens: &'a [T]| {
+        let mut list = SeparatedList {
+            items: Vec::new(),
+            trailing_separator: false,
+        };
+        let mut rest = tokens;
+        loop {
+            let last_len = rest.len();
+            match item_parser(rest) {
+                Ok((rest_tokens, item)) => {
+                    rest = rest_tokens;
+                    list.items.push(item);
+                    list.trailing_separator = false;
+                }
+                Err(err) if err.fatal => return Err(err.relative_to(tokens, rest)),
+                Err(_) => return Ok((rest, list)),
+            }
+            match separator_parser(rest) {
+                Ok((rest_tokens, _)) => {
+                    rest = rest_tokens;
+                    list.trailing_separator = true;
+                }
+                Err(err) if err.fatal => return Err(err.relative_to(tokens, rest)),
+                Err(_) => return Ok((rest, list)),
+            }
+            // Neither the item nor the separator consumed anything.
+            if rest.len() == last_len {
+                return Err(infinite_loop(tokens, rest));
             }
         }
-        // If tokens is empty, returns error.
-        return Err(TokenParseError {
-            errors: vec![TokenParseErrorKind::NotEnoughToken],
-            tokens_consumed: 0,
-        });
     }
 }
 
-pub fn map<'a, T: 'a, OParser, O>(
-    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, OParser>,
+pub fn map<'a, T: 'a, OParser, O, E>(
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, OParser, E>,
     mut mapper: impl FnMut(OParser) -> O,
-) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O, E> {
     move |tokens: &'a [T]| {
         let (rest, result) = parser(tokens)?;
         Ok((rest, mapper(result)))
     }
 }
 
-pub fn map_res<'a, T: 'a, O1, O2>(
+pub fn flat_map<'a, T: 'a, O1, O2, P: TokenParser<'a, T, O2>>(
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O1>,
+    mut make_parser: impl FnMut(O1) -> P,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2> {
+    move |tokens: &'a [T]| {
+        let (rest, result) = parser(tokens)?;
+        make_parser(result)
+            .parse(rest)
+            .map_err(|err| err.relative_to(tokens, rest))
+    }
+}
+
+pub fn into<'a, T: 'a, O1, O2, E>(
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O1, E>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2, E>
+where
+    O1: Into<O2>,
+{
+    move |tokens: &'a [T]| {
+        let (rest, result) = parser(tokens)?;
+        Ok((rest, result.into()))
+    }
+}
+
+// like `map`, but the mapper may fail; its error is reported where `parser` started and is not
+// fatal, so `alt` goes on to the next alternative
+pub fn map_res<'a, T: 'a, O1, O2, E, EMapper>(
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O1, E>,
+    mut mapper: impl FnMut(O1) -> Result<O2, EMapper>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2, E>
+where
+    E: From<EMapper>,
+{
+    move |tokens: &'a [T]| {
+        let (rest, result) = parser(tokens)?;
+        match mapper(result) {
+            Ok(output) => Ok((rest, output)),
+            Err(err) => Err(E::from(err)),
+        }
+    }
+}
+
+pub fn from_err<'a, T: 'a, O, E1, E2>(
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O, E1>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O, E2>
+where
+    E2: From<E1>,
+{
+    move |tokens: &'a [T]| parser(tokens).map_err(E2::from)
+}
+
+// rewrites the error of a failed parser, e.g. to replace "expected symbol" with what the grammar
+// position needs; whatever `mapper` leaves untouched, tokens_consumed included, is kept
+pub fn map_err<'a, T: 'a, O>(
+    mut parser: impl TokenParser<'a, T, O>,
+    mut mapper: impl FnMut(TokenParseError<T>) -> TokenParseError<T>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+    move |tokens: &'a [T]| parser.parse(tokens).map_err(&mut mapper)
+}
+
+// maps the whole result of `parser`, failures included
+pub fn map_result<'a, T: 'a, O1, O2>(
     mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O1>,
     mut mapper: impl FnMut(TokenParseResult<'a, T, O1>) -> TokenParseResult<'a, T, O2>,
 ) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2> {
     move |tokens: &'a [T]| mapper(parser(tokens))
 }
 
+pub fn value<'a, T: 'a, O1, O2: Clone>(
+    constant: O2,
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O1>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O2> {
+    move |tokens: &'a [T]| {
+        let (rest, _) = parser(tokens)?;
+        Ok((rest, constant.clone()))
+    }
+}
+
+pub fn recognize<'a, T: 'a, O>(
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, &'a [T]> {
+    move |tokens: &'a [T]| {
+        let (rest, _) = parser(tokens)?;
+        Ok((rest, &tokens[..tokens.len() - rest.len()]))
+    }
+}
+
+pub fn consumed<'a, T: 'a, O>(
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, (&'a [T], O)> {
+    move |tokens: &'a [T]| {
+        let (rest, result) = parser(tokens)?;
+        Ok((rest, (&tokens[..tokens.len() - rest.len()], result)))
+    }
+}
+
+// like consumed, but counts the tokens instead of slicing them
+pub fn with_consumed_count<'a, T: 'a, O>(
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, (O, usize)> {
+    move |tokens: &'a [T]| {
+        let (rest, result) = parser(tokens)?;
+        Ok((rest, (result, tokens.len() - rest.len())))
+    }
+}
+
+pub fn peek<'a, T: 'a, O>(
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+    move |tokens: &'a [T]| {
+        let (_, result) = parser(tokens)?;
+        Ok((tokens, result))
+    }
+}
+
+pub fn not<'a, T: 'a, O>(
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, ()> {
+    move |tokens: &'a [T]| match parser(tokens) {
+        Ok(_) => Err(TokenParseError::from_error_kind(
+            TokenParseErrorKind::Context("unexpected match"),
+        )),
+        Err(_) => Ok((tokens, ())),
+    }
+}
+
+pub fn verify<'a, T: 'a, O, E>(
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O, E>,
+    predicate: impl Fn(&O) -> bool,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O, E>
+where
+    E: From<TokenParseErrorKind<T>>,
+{
+    move |tokens: &'a [T]| {
+        let (rest, result) = parser(tokens)?;
+        if predicate(&result) {
+            Ok((rest, result))
+        } else {
+            Err(E::from(TokenParseErrorKind::Context("verify")))
+        }
+    }
+}
+
 pub fn success<'a, T: 'a>(tokens: &'a [T]) -> TokenParseResult<'a, T, &T> {
     if tokens.is_empty() {
         return Err(TokenParseError {
             errors: vec![TokenParseErrorKind::NotEnoughToken],
             tokens_consumed: 0,
+            span: None,
+            fatal: false,
         });
     }
     Ok((&tokens[1..], &tokens[0]))
 }
 
+pub fn eof<'a, T: 'a + Clone>(tokens: &'a [T]) -> TokenParseResult<'a, T, ()> {
+    if tokens.is_empty() {
+        return Ok((tokens, ()));
+    }
+    Err(TokenParseError {
+        errors: vec![
+            TokenParseErrorKind::Expects {
+                expects: "end of input",
+                found: tokens[0].clone(),
+            },
+            TokenParseErrorKind::Context("expected end of input"),
+        ],
+        tokens_consumed: 0,
+        span: Some((0, tokens.len())),
+        fatal: false,
+    })
+}
+
+// Parsers are complete by default: running out of tokens is an error like any other. In streaming
+// mode a failure at the end of the input becomes Incomplete instead, so a REPL can wait for more
+// input rather than report a syntax error.
+pub fn streaming<'a, T: 'a, O>(
+    mut parser: impl TokenParser<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+    move |tokens: &'a [T]| {
+        parser.parse(tokens).map_err(|mut err| {
+            let failed_at = err.span.map_or(err.tokens_consumed, |(start, _)| start);
+            if failed_at >= tokens.len() {
+                let incomplete = TokenParseErrorKind::Incomplete(Needed::Unknown);
+                match err.errors.first_mut() {
+                    Some(kind) => *kind = incomplete,
+                    None => err.errors.push(incomplete),
+                }
+            }
+            err
+        })
+    }
+}
+
+pub fn all_consuming<'a, T: 'a + Clone, O>(
+    mut parser: impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O>,
+) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, O> {
+    move |tokens: &'a [T]| {
+        let (rest, result) = parser(tokens)?;
+        let (rest, _) = eof(rest).map_err(|err| err.relative_to(tokens, rest))?;
+        Ok((rest, result))
+    }
+}
+
+pub fn rest<'a, T: 'a>(tokens: &'a [T]) -> TokenParseResult<'a, T, &'a [T]> {
+    Ok((&tokens[tokens.len()..], tokens))
+}
+
+pub fn satisfy<'a, W, T>(
+    predicate: impl Fn(&T) -> bool,
+    expects: &'static str,
+) -> impl FnMut(&'a [W]) -> TokenParseResult<'a, W, &'a W>
+where
+    W: 'a + Clone + UnwrapToken<T>,
+{
+    move |tokens: &'a [W]| {
+        if tokens.is_empty() {
+            return Err(
+                TokenParseError::from_error_kind(TokenParseErrorKind::NotEnoughToken)
+                    .with_span(0, 0),
+            );
+        }
+        if predicate(tokens[0].unwrap_token()) {
+            Ok((&tokens[1..], &tokens[0]))
+        } else {
+            Err(
+                TokenParseError::from_error_kind(TokenParseErrorKind::Expects {
+                    expects,
+                    found: tokens[0].clone(),
+                })
+                .with_span(0, 1),
+            )
+        }
+    }
+}
+
+// satisfy that also extracts: consumes the token when `f` maps it to Some, e.g. to pull out the
+// payload of one variant
+pub fn satisfy_map<'a, W, T, O>(
+    f: impl Fn(&T) -> Option<O>,
+    expects: &'static str,
+) -> impl FnMut(&'a [W]) -> TokenParseResult<'a, W, O>
+where
+    W: 'a + Clone + UnwrapToken<T>,
+{
+    move |tokens: &'a [W]| {
+        let Some(token) = tokens.first() else {
+            return Err(
+                TokenParseError::from_error_kind(TokenParseErrorKind::NotEnoughToken)
+                    .with_span(0, 0),
+            );
+        };
+        match f(token.unwrap_token()) {
+            Some(output) => Ok((&tokens[1..], output)),
+            None => Err(
+                TokenParseError::from_error_kind(TokenParseErrorKind::Expects {
+                    expects,
+                    found: token.clone(),
+                })
+                .with_span(0, 1),
+            ),
+        }
+    }
+}
+
+pub fn take_while<'a, W, T>(
+    predicate: impl Fn(&T) -> bool,
+) -> impl FnMut(&'a [W]) -> TokenParseResult<'a, W, &'a [W]>
+where
+    W: 'a + UnwrapToken<T>,
+{
+    move |tokens: &'a [W]| {
+        let len = tokens
+            .iter()
+            .position(|token| !predicate(token.unwrap_token()))
+            .unwrap_or(tokens.len());
+        Ok((&tokens[len..], &tokens[..len]))
+    }
+}
+
+// skips ahead to the first token `predicate` accepts, which is left unconsumed, or to the end.
+// Never fails, so it can resynchronize after an error, e.g. at the next close delimiter as the
+// `skip` of recover.
+pub fn skip_until<'a, W, T>(
+    predicate: impl Fn(&T) -> bool,
+) -> impl FnMut(&'a [W]) -> TokenParseResult<'a, W, &'a [W]>
+where
+    W: 'a + UnwrapToken<T>,
+{
+    take_while(move |token| !predicate(token))
+}
+
+pub fn take_while1<'a, W, T>(
+    predicate: impl Fn(&T) -> bool,
+    expects: &'static str,
+) -> impl FnMut(&'a [W]) -> TokenParseResult<'a, W, &'a [W]>
+where
+    W: 'a + Clone + UnwrapToken<T>,
+{
+    move |tokens: &'a [W]| {
+        if tokens.is_empty() {
+            return Err(
+                TokenParseError::from_error_kind(TokenParseErrorKind::NotEnoughToken)
+                    .with_span(0, 0),
+            );
+        }
+        let len = tokens
+            .iter()
+            .position(|token| !predicate(token.unwrap_token()))
+            .unwrap_or(tokens.len());
+        if len == 0 {
+            return Err(
+                TokenParseError::from_error_kind(TokenParseErrorKind::Expects {
+                    expects,
+                    found: tokens[0].clone(),
+                })
+                .with_span(0, 1),
+            );
+        }
+        Ok((&tokens[len..], &tokens[..len]))
+    }
+}
+
+pub fn take<'a, T: 'a>(n: usize) -> impl FnMut(&'a [T]) -> TokenParseResult<'a, T, &'a [T]> {
+    move |tokens: &'a [T]| {
+        if tokens.len() < n {
+            return Err(TokenParseError {
+                errors: vec![TokenParseErrorKind::NotEnoughToken],
+                tokens_consumed: tokens.len(),
+                span: None,
+                fatal: false,
+            });
+        }
+        Ok((&tokens[n..], &tokens[..n]))
+    }
+}
+
 pub fn fail<'a, T: 'a>(tokens: &'a [T]) -> TokenParseResult<'a, &T, T> {
     if tokens.is_empty() {
         return Err(TokenParseError {
             errors: vec![TokenParseErrorKind::NotEnoughToken],
             tokens_consumed: 0,
+            span: None,
+            fatal: false,
         });
     }
     Err(TokenParseError {
         errors: vec![TokenParseErrorKind::Fail],
         tokens_consumed: 0,
+        span: None,
+        fatal: false,
     })
 }
 
@@ -369,15 +1535,13 @@ pub fn many0_count<'a, T: 'a, O>(
             match parser(rest) {
                 Ok((i, _)) => {
                     if i.len() == len {
-                        return Err(TokenParseError {
-                            errors: vec![TokenParseErrorKind::InfiniteLoop],
-                            tokens_consumed: 0,
-                        });
+                        return Err(infinite_loop(tokens, rest));
                     }
 
                     rest = i;
                     count += 1;
                 }
+                Err(err) if err.fatal => return Err(err.relative_to(tokens, rest)),
                 Err(_) => return Ok((rest, count)),
             }
         }
@@ -27,7 +27,8 @@ macro_rules! alt_trait_impl {
             #(let mut _succeeded_~N = false;)*
             #(let mut _error_of_parser~N: Option<TokenParseError<T>> = None;)*
             #(let mut _result_of_parser~N: Option<O~N> = None;)*
-            for _ in 0..$n {
+            loop {
+                let mut _progressed = false;
                 #(
                     if !_succeeded_~N {
                         match self.N.parse(_rest) {
@@ -35,6 +36,10 @@ macro_rules! alt_trait_impl {
                                 _rest = rest_tokens;
                                 _result_of_parser~N = Some(result);
                                 _succeeded_~N = true;
+                                _progressed = true;
+                            }
+                            Err(err) if err.fatal => {
+                                return Err(err.with_tokens_consumed(_num_tokens - _rest.len()));
                             }
                             Err(err) => {
                                 _error_of_parser~N = Some(err);
@@ -42,10 +47,16 @@ macro_rules! alt_trait_impl {
                         };
                     }
                 )*
+                // fails only when no remaining parser matches the current position
+                if !_progressed {
+                    break;
+                }
             }
             #(
                 if !_succeeded_~N {
-                    return Err(_error_of_parser~N.unwrap());
+                    return Err(_error_of_parser~N
+                        .unwrap()
+                        .with_tokens_consumed(_num_tokens - _rest.len()));
                 }
             )*
             #(
@@ -0,0 +1,93 @@
+use std::cell::RefCell;
+use std::fmt::{Debug, Display};
+
+use crate::TokenParseResult;
+
+// one parser run: where it started, how far it got, and the runs it made on the way
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attempt {
+    pub label: String,
+    // the first tokens it was given, for traced parsers; alt branches see their alt's tokens
+    pub head: Option<String>,
+    // tokens left when it started, so attempts at the same position line up
+    pub tokens_left: usize,
+    pub tokens_consumed: usize,
+    pub ok: bool,
+    pub attempts: Vec<Attempt>,
+}
+
+impl Attempt {
+    fn fmt_indented(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        write!(f, "{:width$}{}", "", self.label, width = depth * 2)?;
+        if let Some(head) = &self.head {
+            write!(f, " {}", head)?;
+        }
+        writeln!(
+            f,
+            " at {} left: {}, {} tokens consumed",
+            self.tokens_left,
+            if self.ok { "ok" } else { "failed" },
+            self.tokens_consumed
+        )?;
+        for attempt in &self.attempts {
+            attempt.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+// the attempt tree, one line per attempt, nested ones indented
+impl Display for Attempt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+thread_local! {
+    // attempts still running, innermost last
+    static RUNNING: RefCell<Vec<Attempt>> = const { RefCell::new(Vec::new()) };
+    static FINISHED: RefCell<Vec<Attempt>> = const { RefCell::new(Vec::new()) };
+}
+
+pub(crate) fn enter(label: String, head: Option<String>, tokens_left: usize) {
+    RUNNING.with(|running| {
+        running.borrow_mut().push(Attempt {
+            label,
+            head,
+            tokens_left,
+            tokens_consumed: 0,
+            ok: false,
+            attempts: Vec::new(),
+        })
+    });
+}
+
+pub(crate) fn exit<T, O>(result: &TokenParseResult<T, O>) {
+    let finished = RUNNING.with(|running| {
+        let mut running = running.borrow_mut();
+        let mut attempt = running.pop()?;
+        (attempt.ok, attempt.tokens_consumed) = match result {
+            Ok((rest, _)) => (true, attempt.tokens_left - rest.len()),
+            Err(err) => (false, err.tokens_consumed),
+        };
+        match running.last_mut() {
+            Some(parent) => {
+                parent.attempts.push(attempt);
+                None
+            }
+            None => Some(attempt),
+        }
+    });
+    if let Some(attempt) = finished {
+        FINISHED.with(|finished| finished.borrow_mut().push(attempt));
+    }
+}
+
+pub(crate) fn head<T: Debug>(tokens: &[T]) -> String {
+    format!("{:?}", &tokens[..tokens.len().min(3)])
+}
+
+// the outermost attempts recorded on this thread since the last call, in order
+pub fn take_attempts() -> Vec<Attempt> {
+    FINISHED.with(|finished| finished.take())
+}
@@ -6,6 +6,7 @@ pub trait Tuple<'a, W, O> {
     fn tuple(&mut self, tokens: &'a [W]) -> TokenParseResult<'a, W, O>;
 }
 
+/// Applies each parser of the tuple in sequence. Tuples of up to 16 parsers are supported.
 pub fn tuple<'a, W: Clone, O, List: Tuple<'a, W, O>>(
     mut l: List,
 ) -> impl FnMut(&'a [W]) -> TokenParseResult<'a, W, O> {
@@ -45,4 +46,25 @@ macro_rules! tuple_trait {
   }
 }
 
-tuple_trait!(16);
+tuple_trait!(17);
+
+/// Applies parsers in sequence, binding their outputs, then builds the output from the bindings.
+///
+/// `parser!(tokens => { let a = p1; let _ = sep; let b = p2; } => Node { a, b })`
+/// evaluates to a `TokenParseResult` of `Node`.
+#[macro_export]
+macro_rules! parser {
+    ($input:expr => { $(let $pattern:pat = $parser:expr;)* } => $output:expr) => {
+        'parser: {
+            let input = $input;
+            let rest = input;
+            $(
+                let ($pattern, rest) = match $crate::TokenParser::parse(&mut $parser, rest) {
+                    Ok((rest, output)) => (output, rest),
+                    Err(err) => break 'parser Err(err.with_tokens_consumed(input.len() - rest.len())),
+                };
+            )*
+            Ok((rest, $output))
+        }
+    };
+}
@@ -8,9 +8,23 @@ pub enum Token<'a> {
     Ident(&'a str),
     String(&'a str),
     Number(i32),
+    #[token(expects = "|")]
     Pipe,
 }
 
+impl<'a> std::fmt::Display for Token<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::LParen => f.write_str("("),
+            Token::RParen => f.write_str(")"),
+            Token::Ident(s) => f.write_str(s),
+            Token::String(s) => write!(f, "\"{}\"", s),
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Pipe => f.write_str("|"),
+        }
+    }
+}
+
 use parser::*;
 use token_combinator::*;
 
@@ -42,6 +56,23 @@ fn permutation_test() {
     assert_eq!(*b, "piyo");
 }
 
+#[test]
+fn permutation_error_test() {
+    let tokens = &[Token::Ident("hoge"), Token::Number(10), Token::Pipe];
+    assert_eq!(
+        permutation((number, string, ident))(tokens),
+        Err(TokenParseError {
+            tokens_consumed: 2,
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "string",
+                found: Token::Pipe
+            }],
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
 #[test]
 fn alt_test() {
     let tokens = &[Token::Ident("ident")];
@@ -60,7 +91,9 @@ fn alt_test() {
             errors: vec![TokenParseErrorKind::Expects {
                 expects: "string",
                 found: Token::Ident("add")
-            }]
+            }],
+            span: None,
+            fatal: false,
         })
     );
 
@@ -75,9 +108,117 @@ fn alt_test() {
             errors: vec![TokenParseErrorKind::Expects {
                 expects: "string",
                 found: Token::Ident("add")
-            }]
+            }],
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn alt_or_test() {
+    // unknown items are skipped one token at a time, committed ones included
+    let skip = map(take(1), |_| None);
+    let mut item = alt_or(
+        (
+            map(number_value, Some),
+            map(preceded(pipe, cut(number_value)), Some),
+        ),
+        skip,
+    );
+    let tokens = &[
+        Token::Number(1),
+        Token::Ident("a"),
+        Token::Pipe,
+        Token::String("b"),
+        Token::Number(2),
+    ];
+    let (rest, items) = many0(&mut item)(tokens).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(items, vec![Some(1), None, None, None, Some(2)]);
+
+    // the fallback may fail too
+    assert!(alt_or((ident, string), ident)(&[Token::Pipe]).is_err());
+}
+
+#[test]
+fn alt_furthest_error_test() {
+    let tokens = &[
+        Token::Ident("a"),
+        Token::Pipe,
+        Token::Ident("b"),
+        Token::Pipe,
+    ];
+    // A fails after 1 token, B fails after 3 tokens
+    assert_eq!(
+        alt((
+            recognize(tuple((ident, string))),
+            recognize(tuple((ident, pipe, ident, string))),
+        ))(tokens),
+        Err(TokenParseError {
+            tokens_consumed: 3,
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "string",
+                found: Token::Pipe
+            }],
+            span: None,
+            fatal: false,
+        })
+    );
+    // ties keep the earliest branch
+    assert_eq!(
+        alt((
+            recognize(tuple((ident, string))),
+            recognize(tuple((ident, number))),
+        ))(tokens),
+        Err(TokenParseError {
+            tokens_consumed: 1,
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "string",
+                found: Token::Pipe
+            }],
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn tuple_max_arity_test() {
+    let tokens = &[Token::Pipe; 16];
+    assert_eq!(
+        tuple((
+            pipe, pipe, pipe, pipe, pipe, pipe, pipe, pipe, pipe, pipe, pipe, pipe, pipe, pipe,
+            pipe, pipe,
+        ))(tokens)
+        .map(|(rest, _)| rest),
+        Ok(&[] as &[Token])
+    );
+}
+
+#[test]
+fn opt_consumed_test() {
+    let tokens = &[Token::LParen, Token::Ident("meta"), Token::Pipe];
+    let (rest, (output, err)) = opt_consumed(tuple((l_paren, ident, r_paren)))(tokens).unwrap();
+    assert_eq!(rest, tokens);
+    assert_eq!(output, None);
+    assert_eq!(
+        err,
+        Some(TokenParseError {
+            tokens_consumed: 2,
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "r paren",
+                found: Token::Pipe
+            }],
+            span: None,
+            fatal: false,
         })
     );
+
+    let (rest, (output, err)) = opt_consumed(ident)(&tokens[1..]).unwrap();
+    assert_eq!(rest, &[Token::Pipe]);
+    assert_eq!(output, Some(&"meta"));
+    assert_eq!(err, None);
 }
 
 #[test]
@@ -92,12 +233,87 @@ fn opt_test() {
     assert_eq!(ident_str, None);
 }
 
+#[test]
+fn opt_or_test() {
+    // (defn f "doc" [x]) with and without its docstring
+    let tokens = &[Token::Ident("f"), Token::String("doc"), Token::LParen];
+    let mut docstring = preceded(ident, opt_or(string_value, ""));
+    assert_eq!(docstring(tokens), Ok((&tokens[2..], "doc")));
+    let tokens = &[Token::Ident("f"), Token::LParen];
+    assert_eq!(docstring(tokens), Ok((&tokens[1..], "")));
+
+    // a fatal failure still fails, as with opt
+    assert!(opt_or(cut(string_value), "")(&tokens[1..]).is_err());
+}
+
 #[test]
 fn delimited_test() {
     let tokens = &[Token::LParen, Token::Ident("a"), Token::RParen];
     let (rest, ident_str) = delimited(l_paren, ident, r_paren)(tokens).unwrap();
     assert!(rest.is_empty());
     assert_eq!(*ident_str, "a");
+
+    // errors count the tokens from the opening delimiter on
+    let tokens = &[Token::LParen, Token::Ident("a"), Token::Pipe];
+    let err = delimited(l_paren, ident, r_paren)(tokens).unwrap_err();
+    assert_eq!(err.tokens_consumed, 2);
+    let err = delimited(l_paren, ident, r_paren)(&tokens[..1]).unwrap_err();
+    assert_eq!(err.tokens_consumed, 1);
+}
+
+#[test]
+fn delimited_open_ended_test() {
+    let mut list = delimited_open_ended(l_paren, many0(ident), r_paren);
+    let tokens = &[Token::LParen, Token::Ident("a"), Token::RParen];
+    let (rest, (idents, missing_close)) = list(tokens).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(idents, vec![&"a"]);
+    assert_eq!(missing_close, None);
+
+    let tokens = &[Token::LParen, Token::Ident("a"), Token::Ident("b")];
+    let (rest, (idents, missing_close)) = list(tokens).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(idents, vec![&"a", &"b"]);
+    assert_eq!(
+        missing_close,
+        Some(TokenParseError {
+            errors: vec![TokenParseErrorKind::NotEnoughToken],
+            tokens_consumed: 3,
+            span: None,
+            fatal: false,
+        })
+    );
+
+    // the tokens where the close was expected are left to the caller
+    let tokens = &[Token::LParen, Token::Ident("a"), Token::Pipe];
+    let (rest, (_, missing_close)) = list(tokens).unwrap();
+    assert_eq!(rest, &[Token::Pipe]);
+    assert_eq!(missing_close.unwrap().tokens_consumed, 2);
+
+    // a missing open still fails
+    assert!(list(&[Token::Ident("a")]).is_err());
+}
+
+#[test]
+fn ws_test() {
+    // pipes stand in for whitespace tokens
+    let tokens = &[
+        Token::Pipe,
+        Token::LParen,
+        Token::Pipe,
+        Token::Pipe,
+        Token::Ident("a"),
+        Token::RParen,
+        Token::Pipe,
+    ];
+    let mut list = ws(pipe, delimited(l_paren, ws(pipe, ident), r_paren));
+    let (rest, ident_str) = list(tokens).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(*ident_str, "a");
+
+    let (rest, ident_str) = ws(pipe, ident)(&tokens[4..5]).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(*ident_str, "a");
 }
 
 #[test]
@@ -106,6 +322,19 @@ fn preceded_test() {
     let (rest, ident_str) = preceded(l_paren, ident)(tokens).unwrap();
     assert!(rest.is_empty());
     assert_eq!(*ident_str, "a");
+
+    assert_eq!(
+        preceded(l_paren, ident)(&[Token::LParen, Token::RParen]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "ident",
+                found: Token::RParen
+            }],
+            tokens_consumed: 1,
+            span: None,
+            fatal: false,
+        })
+    );
 }
 
 #[test]
@@ -114,6 +343,63 @@ fn terminated_test() {
     let (rest, ident_str) = terminated(ident, r_paren)(tokens).unwrap();
     assert!(rest.is_empty());
     assert_eq!(*ident_str, "a");
+
+    assert_eq!(
+        terminated(ident, r_paren)(&[Token::Ident("a"), Token::LParen]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "r paren",
+                found: Token::LParen
+            }],
+            tokens_consumed: 1,
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn pair_test() {
+    let tokens = &[Token::Ident("a"), Token::Number(1)];
+    let (rest, (ident_str, num)) = pair(ident, number)(tokens).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(*ident_str, "a");
+    assert_eq!(*num, 1);
+
+    assert_eq!(
+        pair(ident, number)(&[Token::Ident("a"), Token::Pipe]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "number",
+                found: Token::Pipe
+            }],
+            tokens_consumed: 1,
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn separated_pair_test() {
+    let tokens = &[Token::Ident("a"), Token::Pipe, Token::Number(1)];
+    let (rest, (ident_str, num)) = separated_pair(ident, pipe, number)(tokens).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(*ident_str, "a");
+    assert_eq!(*num, 1);
+
+    assert_eq!(
+        separated_pair(ident, pipe, number)(&[Token::Ident("a"), Token::Pipe, Token::RParen]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "number",
+                found: Token::RParen
+            }],
+            tokens_consumed: 2,
+            span: None,
+            fatal: false,
+        })
+    );
 }
 
 #[test]
@@ -150,6 +436,66 @@ fn many0_until_end_test() {
     assert_eq!(idents, &[&"a", &"b", &"c"]);
 }
 
+#[test]
+fn capacity_test() {
+    let tokens = &[Token::Ident("a"), Token::Pipe, Token::Ident("b")];
+    let (rest, idents) = many0_capacity(ident, 8)(tokens).unwrap();
+    assert_eq!(rest, &tokens[1..]);
+    assert_eq!(idents, vec![&"a"]);
+    assert!(idents.capacity() >= 8);
+
+    let (rest, idents) = separated_list1_capacity(pipe, ident, 8)(tokens).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(idents, vec![&"a", &"b"]);
+    assert!(idents.capacity() >= 8);
+
+    assert_eq!(
+        many1_capacity(ident, 8)(&tokens[1..]),
+        many1(ident)(&tokens[1..])
+    );
+    assert_eq!(
+        separated_list0_capacity(pipe, number, 8)(tokens),
+        Ok((&tokens[..], vec![]))
+    );
+}
+
+#[test]
+fn iter_many_test() {
+    let tokens = &[
+        Token::Ident("a"),
+        Token::Ident("b"),
+        Token::Pipe,
+        Token::Ident("c"),
+    ];
+    let mut iter = iter_many(tokens, ident);
+    assert_eq!(iter.next(), Some(Ok(&"a")));
+    assert_eq!(iter.next(), Some(Ok(&"b")));
+    assert_eq!(iter.rest(), &tokens[2..]);
+    assert_eq!(
+        iter.next(),
+        Some(Err(TokenParseError::from_error_kind(
+            TokenParseErrorKind::Expects {
+                expects: "ident",
+                found: Token::Pipe,
+            }
+        )
+        .with_tokens_consumed(2)))
+    );
+    assert_eq!(iter.next(), None);
+
+    let idents = iter_many(&tokens[..2], ident).collect::<Result<Vec<_>, _>>();
+    assert_eq!(idents, Ok(vec![&"a", &"b"]));
+
+    let mut iter = iter_many(tokens, opt(pipe));
+    assert_eq!(
+        iter.next(),
+        Some(Err(TokenParseError::from_error_kind(
+            TokenParseErrorKind::InfiniteLoop
+        )))
+    );
+    assert_eq!(iter.next(), None);
+}
+
 #[test]
 fn many1_test() {
     let tokens = &[
@@ -161,6 +507,28 @@ fn many1_test() {
     let (tokens, idents) = many1(ident)(tokens).unwrap();
     assert_eq!(tokens, &[Token::RParen]);
     assert_eq!(idents, &[&"a", &"b", &"c"]);
+
+    assert_eq!(
+        many1(ident)(&[] as &[Token]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::NotEnoughToken],
+            tokens_consumed: 0,
+            span: Some((0, 0)),
+            fatal: false,
+        })
+    );
+    assert_eq!(
+        many1(ident)(&[Token::RParen, Token::Ident("a")]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "ident",
+                found: Token::RParen
+            }],
+            tokens_consumed: 0,
+            span: None,
+            fatal: false,
+        })
+    );
 }
 
 #[test]
@@ -180,6 +548,48 @@ fn separated_list0_test() {
     let (tokens, vec) = separated_list0(pipe, ident)(tokens).unwrap();
     assert!(tokens.is_empty());
     assert_eq!(vec, vec![&"a", &"b", &"c"]);
+
+    let tokens = &[Token::Ident("a"), Token::Pipe, Token::Ident("b"), Token::Pipe];
+    let (tokens, vec) = separated_list0(pipe, ident)(tokens).unwrap();
+    assert!(tokens.is_empty());
+    assert_eq!(vec, vec![&"a", &"b"]);
+
+    let (tokens, vec) = separated_list0(pipe, ident)(&[] as &[Token]).unwrap();
+    assert!(tokens.is_empty());
+    assert!(vec.is_empty());
+}
+
+#[test]
+fn separated_list_trailing_test() {
+    const A: Token = Token::Ident("a");
+    const B: Token = Token::Ident("b");
+    let list = |tokens| separated_list_trailing(pipe, ident)(tokens).unwrap();
+
+    let (rest, result) = list(&[A, Token::Pipe, B]);
+    assert!(rest.is_empty());
+    assert_eq!(result.items, vec![&"a", &"b"]);
+    assert!(!result.trailing_separator);
+
+    let (rest, result) = list(&[A, Token::Pipe, B, Token::Pipe, Token::RParen]);
+    assert_eq!(rest, &[Token::RParen]);
+    assert_eq!(result.items, vec![&"a", &"b"]);
+    assert!(result.trailing_separator);
+
+    // a leading separator is not consumed
+    let (rest, result) = list(&[Token::Pipe, A]);
+    assert_eq!(rest, &[Token::Pipe, A]);
+    assert!(result.items.is_empty());
+    assert!(!result.trailing_separator);
+
+    // nor is the second of a doubled one
+    let (rest, result) = list(&[A, Token::Pipe, Token::Pipe, B]);
+    assert_eq!(rest, &[Token::Pipe, B]);
+    assert_eq!(result.items, vec![&"a"]);
+    assert!(result.trailing_separator);
+
+    let (rest, result) = list(&[]);
+    assert!(rest.is_empty());
+    assert!(result.items.is_empty());
 }
 
 #[test]
@@ -192,7 +602,9 @@ fn separated_list1_test() {
                 expects: "ident",
                 found: Token::LParen
             }],
-            tokens_consumed: 0
+            tokens_consumed: 0,
+            span: None,
+            fatal: false,
         })
     );
 
@@ -206,22 +618,102 @@ fn separated_list1_test() {
     let (tokens, vec) = separated_list1(pipe, ident)(tokens).unwrap();
     assert!(tokens.is_empty());
     assert_eq!(vec, vec![&"a", &"b", &"c"]);
+
+    // zero-width items are fine as long as the separator makes progress
+    let tokens = &[Token::Pipe, Token::Ident("a"), Token::RParen];
+    let (tokens, vec) = separated_list1(pipe, opt(ident))(tokens).unwrap();
+    assert_eq!(tokens, &[Token::RParen]);
+    assert_eq!(vec, vec![None, Some(&"a")]);
+
+    assert_eq!(
+        separated_list1(opt(pipe), opt(ident))(&[Token::RParen]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::InfiniteLoop],
+            tokens_consumed: 0,
+            span: Some((0, 0)),
+            fatal: false,
+        })
+    );
 }
 
 #[test]
 fn map_test() {
     #[derive(Debug, PartialEq, Eq)]
-    enum AST {
+    enum Ast {
         Ident(String),
     }
     let tokens = &[Token::Ident("myon")];
-    let (_, result) = map(ident, |ident| AST::Ident(ident.to_string()))(tokens).unwrap();
-    assert_eq!(result, AST::Ident("myon".to_string()))
+    let (_, result) = map(ident, |ident| Ast::Ident(ident.to_string()))(tokens).unwrap();
+    assert_eq!(result, Ast::Ident("myon".to_string()))
 }
 
 #[test]
 fn map_res_test() {
-    let mut parser = map_res(ident, |res| match res {
+    let mut parser = map_res(ident, |ident_str| {
+        if *ident_str == "if" {
+            Ok(ident_str)
+        } else {
+            Err(TokenParseErrorKind::Expects {
+                expects: "if",
+                found: Token::Ident(ident_str),
+            })
+        }
+    });
+
+    assert_eq!(
+        parser(&[Token::Ident("myon")]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "if",
+                found: Token::Ident("myon"),
+            }],
+            tokens_consumed: 0,
+            span: None,
+            fatal: false,
+        })
+    );
+
+    let (rest, ident_str) = parser(&[Token::Ident("if")]).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(ident_str, &"if");
+
+    let (rest, ident_str) = alt((parser, map(string, |s| s)))(&[Token::String("if")]).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(ident_str, &"if");
+}
+
+#[test]
+fn map_err_test() {
+    let tokens = &[Token::LParen, Token::Number(1)];
+    let mut parser = preceded(
+        l_paren,
+        map_err(ident, |err| TokenParseError {
+            errors: vec![TokenParseErrorKind::Other(
+                "function name must be an ident".to_owned(),
+            )],
+            ..err
+        }),
+    );
+    assert_eq!(
+        parser(tokens),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Other(
+                "function name must be an ident".to_owned()
+            )],
+            tokens_consumed: 1,
+            span: None,
+            fatal: false,
+        })
+    );
+    // only failures are rewritten
+    let mut parser = map_err(number, TokenParseError::into_fatal);
+    assert_eq!(*parser(&tokens[1..]).unwrap().1, 1);
+    assert!(parser(tokens).unwrap_err().fatal);
+}
+
+#[test]
+fn map_result_test() {
+    let mut parser = map_result(ident, |res| match res {
         Ok((rest, ident_str)) => {
             if *ident_str == "if" {
                 Ok((rest, ident_str))
@@ -232,6 +724,8 @@ fn map_res_test() {
                         found: Token::Ident(ident_str),
                     }],
                     tokens_consumed: 0,
+                    span: None,
+                    fatal: false,
                 })
             }
         }
@@ -245,7 +739,9 @@ fn map_res_test() {
                 expects: "if",
                 found: Token::Ident("myon"),
             }],
-            tokens_consumed: 0
+            tokens_consumed: 0,
+            span: None,
+            fatal: false,
         })
     );
 
@@ -253,3 +749,1284 @@ fn map_res_test() {
     assert!(rest.is_empty());
     assert_eq!(ident_str, &"if");
 }
+
+#[test]
+fn recognize_test() {
+    let tokens = &[Token::LParen, Token::Ident("a"), Token::RParen, Token::Pipe];
+    let (rest, recognized) = recognize(delimited(l_paren, ident, r_paren))(tokens).unwrap();
+    assert_eq!(rest, &[Token::Pipe]);
+    assert_eq!(
+        recognized,
+        &[Token::LParen, Token::Ident("a"), Token::RParen]
+    );
+
+    assert!(recognize(delimited(l_paren, ident, r_paren))(&[Token::Pipe]).is_err());
+}
+
+#[test]
+fn consumed_test() {
+    let tokens = &[Token::LParen, Token::Ident("a"), Token::RParen, Token::Pipe];
+    let (rest, (matched, ident_str)) =
+        consumed(delimited(l_paren, ident, r_paren))(tokens).unwrap();
+    assert_eq!(rest, &[Token::Pipe]);
+    assert_eq!(matched, &tokens[..3]);
+    assert_eq!(*ident_str, "a");
+}
+
+#[test]
+fn with_consumed_count_test() {
+    let tokens = &[Token::LParen, Token::Ident("a"), Token::RParen, Token::Pipe];
+    let (rest, (ident_str, count)) =
+        with_consumed_count(delimited(l_paren, ident, r_paren))(tokens).unwrap();
+    assert_eq!(rest, &[Token::Pipe]);
+    assert_eq!(*ident_str, "a");
+    assert_eq!(count, 3);
+
+    let (rest, (idents, count)) = with_consumed_count(many0(ident))(&tokens[3..]).unwrap();
+    assert_eq!(rest, &[Token::Pipe]);
+    assert!(idents.is_empty());
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn peek_test() {
+    let tokens = &[Token::Ident("a"), Token::RParen];
+    let (rest, ident_str) = peek(ident)(tokens).unwrap();
+    assert_eq!(rest, tokens);
+    assert_eq!(*ident_str, "a");
+
+    assert_eq!(
+        peek(string)(tokens),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "string",
+                found: Token::Ident("a")
+            }],
+            tokens_consumed: 0,
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn not_test() {
+    let tokens = &[Token::Ident("a"), Token::RParen];
+    let (rest, _) = not(string)(tokens).unwrap();
+    assert_eq!(rest, tokens);
+
+    assert_eq!(
+        not(ident)(tokens),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Context("unexpected match")],
+            tokens_consumed: 0,
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn verify_test() {
+    let mut positive_number = verify(number, |n| **n > 0);
+    let (rest, n) = positive_number(&[Token::Number(3)]).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(*n, 3);
+
+    assert_eq!(
+        positive_number(&[Token::Number(-1)]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Context("verify")],
+            tokens_consumed: 0,
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn value_test() {
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Ast {
+        Nil,
+    }
+    let mut parser = value(Ast::Nil, pipe);
+    let (rest, result) = parser(&[Token::Pipe, Token::Pipe]).unwrap();
+    assert_eq!(result, Ast::Nil);
+    let (rest, result) = parser(rest).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(result, Ast::Nil);
+
+    assert!(parser(&[Token::LParen]).is_err());
+}
+
+#[test]
+fn count_test() {
+    let tokens = &[Token::Ident("a"), Token::Ident("b"), Token::Ident("c")];
+    let (rest, idents) = count(ident, 2)(tokens).unwrap();
+    assert_eq!(rest, &[Token::Ident("c")]);
+    assert_eq!(idents, &[&"a", &"b"]);
+
+    assert_eq!(
+        count(ident, 4)(tokens),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::NotEnoughToken],
+            tokens_consumed: 3,
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn many_m_n_test() {
+    let tokens = &[
+        Token::Ident("a"),
+        Token::Ident("b"),
+        Token::Ident("c"),
+        Token::RParen,
+    ];
+    let (rest, idents) = many_m_n(1, 2, ident)(tokens).unwrap();
+    assert_eq!(rest, &[Token::Ident("c"), Token::RParen]);
+    assert_eq!(idents, &[&"a", &"b"]);
+
+    let (rest, idents) = many_m_n(0, 5, ident)(tokens).unwrap();
+    assert_eq!(rest, &[Token::RParen]);
+    assert_eq!(idents, &[&"a", &"b", &"c"]);
+
+    assert_eq!(
+        many_m_n(4, 5, ident)(tokens),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "ident",
+                found: Token::RParen
+            }],
+            tokens_consumed: 3,
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn many_till_test() {
+    let tokens = &[
+        Token::Ident("a"),
+        Token::Ident("b"),
+        Token::RParen,
+        Token::Ident("c"),
+    ];
+    let (rest, (idents, _)) = many_till(ident, r_paren)(tokens).unwrap();
+    assert_eq!(rest, &[Token::Ident("c")]);
+    assert_eq!(idents, &[&"a", &"b"]);
+
+    let (rest, (items, _)) = many_till(success, r_paren)(tokens).unwrap();
+    assert_eq!(rest, &[Token::Ident("c")]);
+    assert_eq!(items, &[&Token::Ident("a"), &Token::Ident("b")]);
+
+    assert_eq!(
+        many_till(ident, r_paren)(&[Token::Ident("a"), Token::Pipe]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "ident",
+                found: Token::Pipe
+            }],
+            tokens_consumed: 1,
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn fold_many0_test() {
+    let tokens = &[
+        Token::Number(1),
+        Token::Number(2),
+        Token::Number(3),
+        Token::RParen,
+    ];
+    let (rest, sum) = fold_many0(number, || 0, |acc, n| acc + n)(tokens).unwrap();
+    assert_eq!(rest, &[Token::RParen]);
+    assert_eq!(sum, 6);
+
+    let (rest, sum) = fold_many0(number, || 0, |acc, n| acc + n)(&[Token::RParen]).unwrap();
+    assert_eq!(rest, &[Token::RParen]);
+    assert_eq!(sum, 0);
+}
+
+#[test]
+fn fold_many1_test() {
+    let tokens = &[Token::Number(1), Token::Number(2), Token::RParen];
+    let (rest, sum) = fold_many1(number, || 0, |acc, n| acc + n)(tokens).unwrap();
+    assert_eq!(rest, &[Token::RParen]);
+    assert_eq!(sum, 3);
+
+    assert_eq!(
+        fold_many1(number, || 0, |acc, n| acc + n)(&[Token::RParen]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "number",
+                found: Token::RParen
+            }],
+            tokens_consumed: 0,
+            span: None,
+            fatal: false,
+        })
+    );
+    assert_eq!(
+        fold_many1(number, || 0, |acc, n| acc + n)(&[] as &[Token]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::NotEnoughToken],
+            tokens_consumed: 0,
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn fold_separated_test() {
+    let tokens = &[
+        Token::Number(1),
+        Token::Pipe,
+        Token::Number(2),
+        Token::Pipe,
+        Token::Number(3),
+        Token::RParen,
+    ];
+    let mut sum = fold_separated(pipe, number, || 0, |acc, n| acc + n);
+    let (rest, result) = sum(tokens).unwrap();
+    assert_eq!(rest, &[Token::RParen]);
+    assert_eq!(result, 6);
+
+    // fails like separated_list1 when there are no items
+    for tokens in [&[Token::RParen] as &[Token], &[]] {
+        assert_eq!(
+            sum(tokens).map(|(rest, _)| rest),
+            separated_list1(pipe, number)(tokens).map(|(rest, _)| rest)
+        );
+    }
+}
+
+#[test]
+fn take_test() {
+    let tokens = &[Token::Pipe, Token::Pipe, Token::Ident("a")];
+    let (rest, taken) = take(2)(tokens).unwrap();
+    assert_eq!(rest, &[Token::Ident("a")]);
+    assert_eq!(taken, &[Token::Pipe, Token::Pipe]);
+
+    assert_eq!(
+        take(4)(tokens),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::NotEnoughToken],
+            tokens_consumed: 3,
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn satisfy_test() {
+    let mut paren = satisfy(
+        |token: &Token| matches!(token, Token::LParen | Token::RParen),
+        "paren",
+    );
+    let tokens = &[Token::RParen, Token::Pipe];
+    let (rest, token) = paren(tokens).unwrap();
+    assert_eq!(rest, &[Token::Pipe]);
+    assert_eq!(token, &Token::RParen);
+
+    assert_eq!(
+        paren(rest),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "paren",
+                found: Token::Pipe
+            }],
+            tokens_consumed: 0,
+            span: Some((0, 1)),
+            fatal: false,
+        })
+    );
+    assert_eq!(
+        paren(&[]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::NotEnoughToken],
+            tokens_consumed: 0,
+            span: Some((0, 0)),
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn satisfy_map_test() {
+    let mut small_number = satisfy_map(
+        |token: &Token| match token {
+            Token::Number(n) if *n < 10 => Some(*n),
+            _ => None,
+        },
+        "small number",
+    );
+    let tokens = &[Token::Number(3), Token::Number(30)];
+    let (rest, n) = small_number(tokens).unwrap();
+    assert_eq!(rest, &[Token::Number(30)]);
+    assert_eq!(n, 3);
+
+    assert_eq!(
+        small_number(rest),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "small number",
+                found: Token::Number(30)
+            }],
+            tokens_consumed: 0,
+            span: Some((0, 1)),
+            fatal: false,
+        })
+    );
+    assert_eq!(
+        small_number(&[]).unwrap_err().errors,
+        vec![TokenParseErrorKind::NotEnoughToken]
+    );
+}
+
+#[test]
+fn take_while_test() {
+    let tokens = &[Token::Pipe, Token::Pipe, Token::Ident("a")];
+    let (rest, pipes) = take_while(|token: &Token| *token == Token::Pipe)(tokens).unwrap();
+    assert_eq!(rest, &[Token::Ident("a")]);
+    assert_eq!(pipes, &[Token::Pipe, Token::Pipe]);
+
+    let (rest, pipes) = take_while(|token: &Token| *token == Token::Pipe)(rest).unwrap();
+    assert_eq!(rest, &[Token::Ident("a")]);
+    assert!(pipes.is_empty());
+}
+
+#[test]
+fn skip_until_test() {
+    // (1 | "x" 2) 3: the garbage after 1 is skipped up to the close paren
+    let tokens = &[
+        Token::LParen,
+        Token::Number(1),
+        Token::Pipe,
+        Token::String("x"),
+        Token::Number(2),
+        Token::RParen,
+        Token::Number(3),
+    ];
+    let mut to_close = skip_until(|token: &Token| *token == Token::RParen);
+    let (rest, skipped) = to_close(&tokens[2..]).unwrap();
+    assert_eq!(rest, &tokens[5..]);
+    assert_eq!(skipped, &tokens[2..5]);
+    // nothing left to skip, and no close at all
+    assert_eq!(to_close(rest), Ok((rest, &[] as &[Token])));
+    assert_eq!(to_close(&tokens[6..]), Ok((&[] as &[Token], &tokens[6..])));
+
+    let item = recover(number_value, |tokens, _| to_close(tokens).unwrap().0);
+    let (rest, (items, _)) = preceded(l_paren, many_till(item, r_paren))(tokens).unwrap();
+    assert_eq!(rest, &tokens[6..]);
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0], Ok(1));
+    assert!(items[1].is_err());
+}
+
+#[test]
+fn take_while1_test() {
+    let tokens = &[Token::Pipe, Token::Pipe, Token::Ident("a")];
+    let mut pipes = take_while1(|token: &Token| *token == Token::Pipe, "pipe");
+    let (rest, taken) = pipes(tokens).unwrap();
+    assert_eq!(rest, &[Token::Ident("a")]);
+    assert_eq!(taken, &[Token::Pipe, Token::Pipe]);
+
+    assert_eq!(
+        pipes(rest),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "pipe",
+                found: Token::Ident("a")
+            }],
+            tokens_consumed: 0,
+            span: Some((0, 1)),
+            fatal: false,
+        })
+    );
+    assert_eq!(
+        pipes(&[]),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::NotEnoughToken],
+            tokens_consumed: 0,
+            span: Some((0, 0)),
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn eof_test() {
+    let (rest, _) = eof(&[] as &[Token]).unwrap();
+    assert!(rest.is_empty());
+
+    let tokens = &[Token::Ident("a"), Token::Pipe];
+    assert_eq!(
+        terminated(ident, eof)(tokens),
+        Err(TokenParseError {
+            errors: vec![
+                TokenParseErrorKind::Expects {
+                    expects: "end of input",
+                    found: Token::Pipe
+                },
+                TokenParseErrorKind::Context("expected end of input")
+            ],
+            tokens_consumed: 1,
+            span: Some((1, 2)),
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn streaming_test() {
+    let tokens = &[Token::LParen, Token::Ident("a")];
+    let list = || delimited(l_paren, many0(ident), r_paren);
+    assert_eq!(
+        streaming(list())(tokens),
+        Err(TokenParseError {
+            errors: vec![TokenParseErrorKind::Incomplete(Needed::Unknown)],
+            tokens_consumed: 2,
+            span: None,
+            fatal: false,
+        })
+    );
+    // complete parsers still fail outright
+    assert_eq!(
+        list()(tokens).unwrap_err().errors,
+        vec![TokenParseErrorKind::NotEnoughToken]
+    );
+    // errors before the end of the input are not incomplete
+    let tokens = &[Token::LParen, Token::Pipe, Token::RParen];
+    assert!(matches!(
+        streaming(list())(tokens).unwrap_err().errors[0],
+        TokenParseErrorKind::Expects { .. }
+    ));
+}
+
+#[test]
+fn parse_complete_test() {
+    let tokens = &[Token::Ident("a"), Token::Ident("b")];
+    assert_eq!(many0(ident).parse_complete(tokens).unwrap(), &[&"a", &"b"]);
+    assert_eq!(
+        ident.parse_complete(tokens),
+        Err(TokenParseError {
+            errors: vec![
+                TokenParseErrorKind::Expects {
+                    expects: "end of input",
+                    found: Token::Ident("b")
+                },
+                TokenParseErrorKind::Context("expected end of input")
+            ],
+            tokens_consumed: 1,
+            span: Some((1, 2)),
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn all_consuming_test() {
+    let (rest, idents) =
+        all_consuming(many0(ident))(&[Token::Ident("a"), Token::Ident("b")]).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(idents, &[&"a", &"b"]);
+
+    assert_eq!(
+        all_consuming(many0(ident))(&[Token::Ident("a"), Token::Pipe]),
+        Err(TokenParseError {
+            errors: vec![
+                TokenParseErrorKind::Expects {
+                    expects: "end of input",
+                    found: Token::Pipe
+                },
+                TokenParseErrorKind::Context("expected end of input")
+            ],
+            tokens_consumed: 1,
+            span: Some((1, 2)),
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn rest_test() {
+    let tokens = &[Token::Ident("a"), Token::Pipe, Token::RParen];
+    let (remaining, (ident_str, others)) = pair(ident, rest)(tokens).unwrap();
+    assert!(remaining.is_empty());
+    assert_eq!(*ident_str, "a");
+    assert_eq!(others, &[Token::Pipe, Token::RParen]);
+
+    let (remaining, others) = rest(&[] as &[Token]).unwrap();
+    assert!(remaining.is_empty());
+    assert!(others.is_empty());
+}
+
+#[test]
+fn dbg_parser_test() {
+    // traced or not, the result is the parser's own
+    let tokens = &[Token::LParen, Token::Ident("a"), Token::Pipe];
+    let mut list = dbg_parser("list", delimited(l_paren, ident, r_paren));
+    assert_eq!(list(tokens), delimited(l_paren, ident, r_paren)(tokens));
+    let (rest, ident_str) = dbg_parser("ident", ident)(&tokens[1..]).unwrap();
+    assert_eq!(rest, &[Token::Pipe]);
+    assert_eq!(*ident_str, "a");
+}
+
+#[test]
+#[cfg(feature = "trace")]
+fn traced_test() {
+    let tokens = &[Token::LParen, Token::Ident("a"), Token::Pipe];
+    let mut form = traced(
+        "form",
+        alt((
+            traced("call", delimited(l_paren, ident, r_paren)),
+            map(traced("pair", preceded(l_paren, ident)), |_| &"pair"),
+        )),
+    );
+    assert_eq!(*form(tokens).unwrap().1, "pair");
+    let attempts = token_combinator::trace::take_attempts();
+    assert_eq!(attempts.len(), 1);
+    assert_eq!(
+        attempts[0].to_string(),
+        "form [LParen, Ident(\"a\"), Pipe] at 3 left: ok, 2 tokens consumed
+  alt branch 0 at 3 left: failed, 2 tokens consumed
+    call [LParen, Ident(\"a\"), Pipe] at 3 left: failed, 2 tokens consumed
+  alt branch 1 at 3 left: ok, 2 tokens consumed
+    pair [LParen, Ident(\"a\"), Pipe] at 3 left: ok, 2 tokens consumed
+"
+    );
+    assert!(token_combinator::trace::take_attempts().is_empty());
+}
+
+#[test]
+fn parser_struct_test() {
+    struct Grammar<F> {
+        name: Parser<F>,
+    }
+    let grammar = Grammar {
+        name: Parser::new(ident),
+    };
+    let tokens = &[Token::Ident("a"), Token::Pipe, Token::Number(1)];
+    let mut name = grammar.name;
+    let (rest, a) = name.parse(tokens).unwrap();
+    assert_eq!(rest, &tokens[1..]);
+    assert_eq!(*a, "a");
+
+    // handlers of different types share one boxed type
+    let mut handlers: std::collections::HashMap<char, Parser<BoxedParser<Token, String>>> =
+        std::collections::HashMap::new();
+    handlers.insert('i', Parser::boxed(ident.map(|s| s.to_string())));
+    handlers.insert('n', Parser::boxed(number.map(|n| n.to_string())));
+    let (rest, one) = handlers.get_mut(&'n').unwrap().parse(&tokens[2..]).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(one, "1");
+
+    // copies work on their own, and chain like any other parser
+    let mut numbers = Parser::new(number);
+    let mut twice = preceded(pipe, numbers.map(|n| n * 2));
+    assert_eq!(twice(&tokens[1..]).unwrap().1, 2);
+    assert!(numbers.parse(tokens).is_err());
+}
+
+#[test]
+fn tokens_consumed_test() {
+    // errors count the tokens consumed before the failing parser was called as well as those it
+    // consumed itself, so nesting never moves them. Here the number at 5 of `(a | b | 1)` is not
+    // an ident.
+    const TOKENS: &[Token<'static>] = &[
+        Token::LParen,
+        Token::Ident("a"),
+        Token::Pipe,
+        Token::Ident("b"),
+        Token::Pipe,
+        Token::Number(1),
+        Token::RParen,
+    ];
+    fn failed_at<O>(
+        mut parser: impl FnMut(&'static [Token<'static>]) -> TokenParseResult<'static, Token, O>,
+    ) -> usize {
+        let err = parser(TOKENS).err().expect("parser should fail");
+        err.tokens_consumed
+    }
+    let item = || preceded(pipe, cut(ident));
+    // the repetitions start at the first pipe, after the head
+    let head = || pair(l_paren, ident);
+    let list = || delimited(l_paren, preceded(ident, many0(item())), r_paren);
+
+    assert_eq!(failed_at(list()), 5);
+    assert_eq!(failed_at(map(list(), |idents| idents.len())), 5);
+    assert_eq!(failed_at(opt(list())), 5);
+    assert_eq!(failed_at(preceded(head(), many1(item()))), 5);
+    assert_eq!(failed_at(preceded(head(), many_m_n(0, 3, item()))), 5);
+    assert_eq!(failed_at(preceded(head(), count(item(), 2))), 5);
+    assert_eq!(failed_at(preceded(head(), many_till(item(), r_paren))), 5);
+    assert_eq!(failed_at(preceded(head(), many0_until_end(item()))), 5);
+    assert_eq!(failed_at(preceded(head(), many0_count(item()))), 5);
+    let fold = fold_many1(item(), || 0, |n, _| n + 1);
+    assert_eq!(failed_at(preceded(head(), fold)), 5);
+
+    let name = || cut(ident);
+    let list0 = separated_list0(pipe, name());
+    assert_eq!(failed_at(preceded(l_paren, list0)), 5);
+    let list1 = separated_list1(pipe, name());
+    assert_eq!(failed_at(preceded(l_paren, list1)), 5);
+    let trailing = separated_list_trailing(pipe, name());
+    assert_eq!(failed_at(preceded(l_paren, trailing)), 5);
+
+    // so do the errors the repetitions raise themselves
+    let err = preceded(l_paren, many0(opt(pipe)))(TOKENS).unwrap_err();
+    assert_eq!((err.tokens_consumed, err.span), (1, Some((1, 1))));
+}
+
+#[test]
+fn context_test() {
+    let mut parser = context(
+        "in list",
+        delimited(
+            l_paren,
+            context("in vector", preceded(pipe, ident)),
+            r_paren,
+        ),
+    );
+    assert_eq!(
+        parser(&[Token::LParen, Token::Pipe, Token::Number(1), Token::RParen]),
+        Err(TokenParseError {
+            errors: vec![
+                TokenParseErrorKind::Expects {
+                    expects: "ident",
+                    found: Token::Number(1)
+                },
+                TokenParseErrorKind::Context("in vector"),
+                TokenParseErrorKind::Context("in list"),
+            ],
+            tokens_consumed: 2,
+            span: None,
+            fatal: false,
+        })
+    );
+}
+
+#[test]
+fn error_display_test() {
+    let err = TokenParseError {
+        errors: vec![
+            TokenParseErrorKind::Expects {
+                expects: "ident",
+                found: Token::Pipe,
+            },
+            TokenParseErrorKind::Context("vector"),
+            TokenParseErrorKind::Context("list"),
+        ],
+        tokens_consumed: 1,
+        span: None,
+        fatal: false,
+    };
+    assert_eq!(
+        err.to_string(),
+        "expected ident, found | (in vector) (in list) at token 1"
+    );
+    let err: Box<dyn std::error::Error> =
+        Box::new(TokenParseError::<Token>::from_error_kind(TokenParseErrorKind::NotEnoughToken));
+    assert_eq!(err.to_string(), "not enough tokens at token 0");
+    let err = TokenParseError::from_error_kind(TokenParseErrorKind::MismatchedClose {
+        open: Token::LParen,
+        expects: ")",
+        found: Token::Pipe,
+    })
+    .with_tokens_consumed(2);
+    assert_eq!(err.to_string(), "expected `)`, found `|` at token 2");
+}
+
+#[derive(Debug, PartialEq)]
+enum DefnError<'a> {
+    Parse(TokenParseError<Token<'a>>),
+    Arity(String),
+}
+
+impl<'a> From<TokenParseError<Token<'a>>> for DefnError<'a> {
+    fn from(err: TokenParseError<Token<'a>>) -> Self {
+        DefnError::Parse(err)
+    }
+}
+
+impl<'a> From<TokenParseErrorKind<Token<'a>>> for DefnError<'a> {
+    fn from(kind: TokenParseErrorKind<Token<'a>>) -> Self {
+        DefnError::Parse(TokenParseError::from_error_kind(kind))
+    }
+}
+
+fn parse_arity<'a>(tokens: &'a [Token<'a>]) -> TokenParseResult<'a, Token<'a>, i32, DefnError<'a>> {
+    map_res(from_err(many1(number)), |numbers: Vec<&i32>| {
+        if numbers.len() != 2 {
+            return Err(DefnError::Arity(format!(
+                "expected 2 args, found {}",
+                numbers.len()
+            )));
+        }
+        Ok(numbers[0] + numbers[1])
+    })(tokens)
+}
+
+#[test]
+fn user_error_test() {
+    assert_eq!(
+        parse_arity(&[Token::Number(1), Token::Number(2)]),
+        Ok((&[] as &[Token], 3))
+    );
+    assert_eq!(
+        parse_arity(&[Token::Number(1)]),
+        Err(DefnError::Arity("expected 2 args, found 1".to_owned()))
+    );
+    assert_eq!(
+        parse_arity(&[Token::Pipe]),
+        Err(DefnError::Parse(TokenParseError {
+            errors: vec![TokenParseErrorKind::Expects {
+                expects: "number",
+                found: Token::Pipe,
+            }],
+            tokens_consumed: 0,
+            span: None,
+            fatal: false,
+        }))
+    );
+    assert_eq!(
+        verify(from_err(number), |n: &&i32| **n > 0)(&[Token::Number(0)]),
+        Err(DefnError::Parse(TokenParseError::from_error_kind(
+            TokenParseErrorKind::Context("verify")
+        )))
+    );
+}
+
+#[test]
+fn cut_test() {
+    let tokens = &[Token::LParen, Token::Ident("def"), Token::Pipe];
+    let expected = TokenParseError {
+        errors: vec![TokenParseErrorKind::Expects {
+            expects: "number",
+            found: Token::Pipe,
+        }],
+        tokens_consumed: 2,
+        span: None,
+        fatal: true,
+    };
+    let def_form = || preceded(tuple((l_paren, ident)), cut(number));
+    assert_eq!(
+        alt((map(def_form(), |_| ()), map(success, |_| ())))(tokens).unwrap_err(),
+        expected
+    );
+    assert_eq!(opt(def_form())(tokens).unwrap_err(), expected);
+    assert!(many0(def_form())(tokens).unwrap_err().fatal);
+
+    // without cut, opt backtracks
+    assert_eq!(
+        opt(preceded(tuple((l_paren, ident)), number))(tokens),
+        Ok((tokens as &[Token], None))
+    );
+}
+
+#[test]
+fn recover_test() {
+    let tokens = &[
+        Token::LParen,
+        Token::Ident("def"),
+        Token::Pipe,
+        Token::RParen,
+        Token::Number(1),
+    ];
+    let def_form = || preceded(tuple((l_paren, ident)), cut(number));
+    // resumes after the closing paren of the form that failed
+    let skip_form = |tokens: &'static [Token<'static>], _: &TokenParseError<Token<'static>>| {
+        let end = tokens.iter().position(|token| *token == Token::RParen);
+        &tokens[end.map_or(tokens.len(), |end| end + 1)..]
+    };
+    let (rest, result) = recover(def_form(), skip_form)(tokens).unwrap();
+    assert_eq!(rest, &[Token::Number(1)]);
+    assert!(result.unwrap_err().fatal);
+
+    let (rest, result) = recover(number, skip_form)(&tokens[4..]).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(*result.unwrap(), 1);
+}
+
+#[test]
+fn alt_all_test() {
+    let tokens = &[Token::Pipe];
+    let err = alt_all((
+        recognize(l_paren),
+        recognize(string),
+        recognize(tuple((number, ident))),
+        recognize(string),
+    ))(tokens)
+    .unwrap_err();
+    assert_eq!(
+        err,
+        TokenParseError {
+            errors: vec![
+                TokenParseErrorKind::Expects {
+                    expects: "l paren",
+                    found: Token::Pipe,
+                },
+                TokenParseErrorKind::Expects {
+                    expects: "string",
+                    found: Token::Pipe,
+                },
+                TokenParseErrorKind::Expects {
+                    expects: "number",
+                    found: Token::Pipe,
+                },
+            ],
+            tokens_consumed: 0,
+            span: None,
+            fatal: false,
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "expected one of l paren, string or number, found | at token 0"
+    );
+
+    // only the branches that advanced the furthest are listed
+    let tokens = &[Token::Number(1), Token::Pipe];
+    assert_eq!(
+        alt_all((recognize(l_paren), recognize(tuple((number, ident)))))(tokens)
+            .unwrap_err()
+            .to_string(),
+        "expected ident, found | at token 1"
+    );
+
+    let (rest, matched) = alt_all((recognize(l_paren), recognize(number)))(tokens).unwrap();
+    assert_eq!(rest, &[Token::Pipe]);
+    assert_eq!(matched, &[Token::Number(1)]);
+}
+
+#[test]
+fn token_expects_attribute_test() {
+    assert_eq!(
+        parser::pipe(&[Token::RParen]).unwrap_err().to_string(),
+        "expected |, found ) at token 0"
+    );
+    // falls back to the variant name without the attribute
+    assert_eq!(
+        parser::r_paren(&[Token::Pipe]).unwrap_err().to_string(),
+        "expected r paren, found | at token 0"
+    );
+}
+
+#[test]
+fn token_kind_test() {
+    assert_eq!(Token::Ident("a").kind(), TokenKind::Ident);
+    assert_eq!(Token::Pipe.kind(), TokenKind::Pipe);
+
+    let tokens = &[Token::Ident("a"), Token::Pipe];
+    let (rest, token) = kind::ident(tokens).unwrap();
+    assert_eq!(rest, &[Token::Pipe]);
+    assert_eq!(token, &Token::Ident("a"));
+    assert_eq!(
+        kind::number(tokens),
+        Err(TokenParseError::from_error_kind(
+            TokenParseErrorKind::Expects {
+                expects: "number",
+                found: Token::Ident("a"),
+            }
+        ))
+    );
+    assert_eq!(
+        kind::pipe(&[] as &[Token]),
+        Err(TokenParseError::from_error_kind(
+            TokenParseErrorKind::NotEnoughToken
+        ))
+    );
+}
+
+#[test]
+fn value_parser_test() {
+    let tokens = &[Token::Number(10), Token::Ident("a")];
+    let (rest, n): (_, i32) = number_value(tokens).unwrap();
+    assert_eq!(rest, &[Token::Ident("a")]);
+    assert_eq!(n, 10);
+    let (_, sum) =
+        fold_many1(number_value, || 0, |acc, n| acc + n)(&[Token::Number(1), Token::Number(2)])
+            .unwrap();
+    assert_eq!(sum, 3);
+    assert_eq!(
+        ident_value(tokens),
+        Err(TokenParseError::from_error_kind(
+            TokenParseErrorKind::Expects {
+                expects: "ident",
+                found: Token::Number(10),
+            }
+        ))
+    );
+}
+
+// dispatches the value parser on the tag, like #inst "..." or #uuid "..."
+fn tagged<'a>(tokens: &'a [Token<'a>]) -> TokenParseResult<'a, Token<'a>, (&'a str, String)> {
+    flat_map(ident_value, |tag| {
+        move |tokens: &'a [Token<'a>]| match tag {
+            "count" => map(many1(pipe), |pipes| (tag, pipes.len().to_string()))(tokens),
+            _ => map(string_value, |s| (tag, s.to_owned()))(tokens),
+        }
+    })(tokens)
+}
+
+#[test]
+fn flat_map_test() {
+    let tokens = &[Token::Ident("count"), Token::Pipe, Token::Pipe];
+    assert_eq!(
+        tagged(tokens),
+        Ok((&[] as &[Token], ("count", "2".to_owned())))
+    );
+    let tokens = &[Token::Ident("inst"), Token::String("2023")];
+    assert_eq!(
+        tagged(tokens),
+        Ok((&[] as &[Token], ("inst", "2023".to_owned())))
+    );
+    assert_eq!(
+        tagged(&[Token::Ident("inst"), Token::Pipe]),
+        Err(
+            TokenParseError::from_error_kind(TokenParseErrorKind::Expects {
+                expects: "string",
+                found: Token::Pipe,
+            })
+            .with_tokens_consumed(1)
+        )
+    );
+
+    let (_, n) = number_value
+        .flat_map(|n| count(pipe, n as usize))
+        .parse(&[Token::Number(2), Token::Pipe, Token::Pipe])
+        .unwrap();
+    assert_eq!(n.len(), 2);
+}
+
+#[derive(Debug, PartialEq)]
+enum Expr<'a> {
+    Sym(&'a str),
+    Num(i32),
+}
+
+impl<'a> From<&'a &'a str> for Expr<'a> {
+    fn from(name: &'a &'a str) -> Self {
+        Expr::Sym(name)
+    }
+}
+
+#[test]
+fn into_test() {
+    let tokens = &[Token::Ident("a"), Token::Number(1)];
+    let (rest, expr): (_, Expr) = into(ident)(tokens).unwrap();
+    assert_eq!(rest, &[Token::Number(1)]);
+    assert_eq!(expr, Expr::Sym("a"));
+
+    let (_, n): (_, i64) = into(number_value)(rest).unwrap();
+    assert_eq!(n, 1);
+}
+
+#[test]
+fn chaining_test() {
+    let tokens = &[Token::Ident("a"), Token::Number(1), Token::Pipe];
+    let mut expr = ident_value.map(Expr::Sym).or(number_value.map(Expr::Num));
+    let (rest, exprs) = many0(&mut expr)(tokens).unwrap();
+    assert_eq!(rest, &[Token::Pipe]);
+    assert_eq!(exprs, vec![Expr::Sym("a"), Expr::Num(1)]);
+
+    let (rest, pair) = ident.and_then(number.opt()).parse(tokens).unwrap();
+    assert_eq!(rest, &[Token::Pipe]);
+    assert_eq!(pair, (&"a", Some(&1)));
+
+    let (_, numbers) = number_value.many0().parse(&tokens[1..]).unwrap();
+    assert_eq!(numbers, vec![1]);
+
+    assert_eq!(
+        number
+            .map_res(|n| if *n > 0 {
+                Ok(*n)
+            } else {
+                Err(TokenParseErrorKind::Fail)
+            })
+            .parse(&[Token::Number(0)]),
+        Err(TokenParseError::from_error_kind(TokenParseErrorKind::Fail))
+    );
+}
+
+// returns the nesting depth of a form
+fn depth<'a>() -> BoxedParser<'a, Token<'a>, usize> {
+    boxed(alt((
+        map(ident, |_| 0),
+        map(
+            delimited(l_paren, many0(|tokens| depth()(tokens)), r_paren),
+            |depths| depths.into_iter().max().unwrap_or(0) + 1,
+        ),
+    )))
+}
+
+#[test]
+fn boxed_test() {
+    let tokens = &[
+        Token::LParen,
+        Token::Ident("a"),
+        Token::LParen,
+        Token::LParen,
+        Token::Ident("b"),
+        Token::RParen,
+        Token::RParen,
+        Token::RParen,
+    ];
+    let (rest, d) = depth()(tokens).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(d, 3);
+}
+
+#[test]
+fn lazy_test() {
+    let mut built = 0;
+    let mut parser = lazy(|| {
+        built += 1;
+        ident
+    });
+    assert_eq!(parser(&[Token::Ident("a")]), Ok((&[] as &[Token], &"a")));
+    assert!(parser(&[Token::Pipe]).is_err());
+    drop(parser);
+    assert_eq!(built, 1);
+}
+
+#[derive(Debug, PartialEq)]
+struct Binding<'a> {
+    name: &'a str,
+    value: i32,
+}
+
+fn binding<'a>(tokens: &'a [Token<'a>]) -> TokenParseResult<'a, Token<'a>, Binding<'a>> {
+    parser!(tokens => {
+        let name = ident;
+        let _ = pipe;
+        let value = number_value;
+    } => Binding { name, value })
+}
+
+#[test]
+fn parser_macro_test() {
+    let tokens = &[
+        Token::Ident("x"),
+        Token::Pipe,
+        Token::Number(1),
+        Token::Pipe,
+    ];
+    assert_eq!(
+        binding(tokens),
+        Ok((
+            &[Token::Pipe] as &[Token],
+            Binding {
+                name: "x",
+                value: 1
+            }
+        ))
+    );
+    assert_eq!(
+        many1(binding)(&[Token::Ident("x"), Token::Pipe, Token::String("1")]),
+        Err(
+            TokenParseError::from_error_kind(TokenParseErrorKind::Expects {
+                expects: "number",
+                found: Token::String("1"),
+            })
+            .with_tokens_consumed(2)
+        )
+    );
+}
+
+mod owned {
+    use token_combinator::*;
+
+    // tokens owning their text, neither Copy nor borrowing the source
+    #[derive(Debug, Clone, PartialEq, TokenParser)]
+    pub enum OwnedToken {
+        LParen,
+        RParen,
+        Symbol(String),
+        Str(String),
+        // `16rFF`: the radix and the digits
+        Radix(u32, String),
+    }
+
+    use parser::*;
+
+    #[test]
+    fn owned_token_test() {
+        let tokens = vec![
+            OwnedToken::LParen,
+            OwnedToken::Symbol("println".to_owned()),
+            OwnedToken::Str("hello".to_owned()),
+            OwnedToken::RParen,
+        ];
+        let (rest, (name, args)) =
+            delimited(l_paren, pair(symbol_value, many0(str_value)), r_paren)(&tokens).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(name, "println");
+        assert_eq!(args, vec!["hello".to_owned()]);
+        assert_eq!(
+            symbol(&tokens),
+            Err(TokenParseError::from_error_kind(
+                TokenParseErrorKind::Expects {
+                    expects: "symbol",
+                    found: OwnedToken::LParen,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn tuple_variant_test() {
+        let tokens = vec![OwnedToken::Radix(16, "FF".to_owned())];
+        let (rest, (base, digits)) = radix(&tokens).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!((*base, digits.as_str()), (16, "FF"));
+    }
+
+    // a wrapper neither Copy nor convertible into its token
+    #[derive(Debug, Clone, PartialEq)]
+    struct Lexed {
+        token: OwnedToken,
+        source: String,
+    }
+
+    impl UnwrapToken<OwnedToken> for Lexed {
+        fn unwrap_token(&self) -> &OwnedToken {
+            &self.token
+        }
+    }
+
+    #[test]
+    fn wrapped_owned_token_test() {
+        let lexed = |token: OwnedToken, source: &str| Lexed {
+            token,
+            source: source.to_owned(),
+        };
+        let tokens = vec![
+            lexed(OwnedToken::LParen, "("),
+            lexed(OwnedToken::Symbol("f".to_owned()), "f"),
+            lexed(OwnedToken::RParen, ")"),
+        ];
+        let (rest, name) = delimited(kind::l_paren, symbol, kind::r_paren)(&tokens).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(name, "f");
+        let (_, paren) = satisfy(
+            |token: &OwnedToken| token.kind() == OwnedTokenKind::LParen,
+            "paren",
+        )(&tokens)
+        .unwrap();
+        assert_eq!(paren.source, "(");
+        assert_eq!(
+            kind::symbol(&tokens).unwrap_err().errors,
+            vec![TokenParseErrorKind::Expects {
+                expects: "symbol",
+                found: tokens[0].clone(),
+            }]
+        );
+    }
+}
+
+#[test]
+fn token_span_test() {
+    // (a b) 10
+    let tokens = &[
+        Token::LParen,
+        Token::Ident("a"),
+        Token::Ident("b"),
+        Token::RParen,
+        Token::Number(10),
+    ];
+    let mut list = token_span(
+        tokens,
+        delimited(l_paren, many0(token_span(tokens, ident)), r_paren),
+    );
+    let (rest, parsed) = list(tokens).unwrap();
+    assert_eq!((parsed.start, parsed.end), (0, 4));
+    assert_eq!(parsed.tokens(tokens), Some(&tokens[..4]));
+    assert_eq!(parsed.tokens(&tokens[2..]), None);
+    assert_eq!(
+        parsed.value,
+        vec![
+            TokenSpan {
+                value: &"a",
+                start: 1,
+                end: 2,
+            },
+            TokenSpan {
+                value: &"b",
+                start: 2,
+                end: 3,
+            },
+        ]
+    );
+
+    // a later call still points into the original slice, and nothing consumed is empty
+    let (_, number) = token_span(tokens, number)(rest).unwrap();
+    assert_eq!((*number.value, number.start, number.end), (10, 4, 5));
+    let (_, nothing) = token_span(tokens, opt(ident))(rest).unwrap();
+    assert_eq!((nothing.start, nothing.end), (4, 4));
+}
+
+#[test]
+fn spanned_test() {
+    // (a b) 10
+    let tokens = &[
+        Spanned {
+            token: Token::LParen,
+            start: 0,
+            end: 1,
+        },
+        Spanned {
+            token: Token::Ident("a"),
+            start: 1,
+            end: 2,
+        },
+        Spanned {
+            token: Token::Ident("b"),
+            start: 3,
+            end: 4,
+        },
+        Spanned {
+            token: Token::RParen,
+            start: 4,
+            end: 5,
+        },
+        Spanned {
+            token: Token::Number(10),
+            start: 6,
+            end: 8,
+        },
+    ];
+    let (rest, list) =
+        spanned(delimited(l_paren, many0(spanned(ident_value)), r_paren))(tokens).unwrap();
+    assert_eq!(rest, &tokens[4..]);
+    assert_eq!((list.start, list.end), (0, 5));
+    assert_eq!(
+        list.token
+            .iter()
+            .map(|s| (s.token, s.start, s.end))
+            .collect::<Vec<_>>(),
+        vec![("a", 1, 2), ("b", 3, 4)]
+    );
+
+    let (_, number) = spanned(number_value)(rest).unwrap();
+    assert_eq!((number.token, number.start, number.end), (10, 6, 8));
+    assert_eq!(
+        l_paren(rest),
+        Err(TokenParseError::from_error_kind(
+            TokenParseErrorKind::Expects {
+                expects: "l paren",
+                found: tokens[4],
+            }
+        ))
+    );
+}
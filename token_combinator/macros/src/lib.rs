@@ -4,16 +4,19 @@ use convert_case::Casing;
 use proc_macro::TokenStream;
 use proc_macro2::{Punct, Spacing, TokenTree};
 use quote::{format_ident, quote};
-use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, Ident, Lit, Meta, NestedMeta};
 
-#[proc_macro_derive(TokenParser)]
+#[proc_macro_derive(TokenParser, attributes(token))]
 pub fn derive_parse_token(input: TokenStream) -> TokenStream {
     let item = parse_macro_input!(input as DeriveInput);
     let enum_name = item.ident;
     let enum_variants = extract_enum_fields(&item.data);
+    let kind_name = format_ident!("{}Kind", enum_name);
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    let generic_params = item.generics.params.iter().collect::<Vec<_>>();
     let prev_quote = Rc::new(RefCell::new(false));
     let token_lifetime_parameter = Rc::new(RefCell::new(Option::<proc_macro2::TokenStream>::None));
-    let parser_functions = enum_variants.iter().map(|(variant_name, fields)| {
+    let parser_functions = enum_variants.iter().map(|(variant_name, fields, expects)| {
         let parser_name = format_ident!(
             "{}",
             variant_name.to_string().to_case(convert_case::Case::Snake)
@@ -32,7 +35,7 @@ pub fn derive_parse_token(input: TokenStream) -> TokenStream {
                         quote! { #ty }
                     });
                     quote! {
-                        &(#(#type_tokens_stream),*)
+                        (#(&#type_tokens_stream),*)
                     }
                 }
             }
@@ -72,10 +75,11 @@ pub fn derive_parse_token(input: TokenStream) -> TokenStream {
                 quote! {}
             }
         };
-        let token_life_parameter_with_comma = if token_life_parameter.is_empty() {
-            quote! {}
+        // the slice gets its own lifetime so payloads can outlive the token vector
+        let parser_generics = if token_life_parameter.is_empty() {
+            quote! { 't }
         } else {
-            quote! { #token_life_parameter , }
+            quote! { 't, #token_life_parameter: 't }
         };
         let token_life_parameter_with_angles = if token_life_parameter.is_empty() {
             quote! {}
@@ -110,14 +114,59 @@ pub fn derive_parse_token(input: TokenStream) -> TokenStream {
             Fields::Unit => quote! { () },
         };
 
-        let lower_variant_name = variant_name
-            .to_string()
-            .to_case(convert_case::Case::Snake)
-            .replace("_", " ");
+        let lower_variant_name = expects.clone().unwrap_or_else(|| {
+            variant_name
+                .to_string()
+                .to_case(convert_case::Case::Snake)
+                .replace("_", " ")
+        });
+        // single-field variants also get <variant>_value returning the payload by value
+        let value_parser = match fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let value_parser_name = format_ident!("{}_value", parser_name);
+                let ty = &fields.unnamed.first().unwrap().ty;
+                quote! {
+                    pub fn #value_parser_name<#parser_generics, W>(
+                        tokens: &'t [W],
+                    ) -> token_combinator::TokenParseResult<'t, W, #ty>
+                    where
+                        W: Clone + token_combinator::UnwrapToken<#enum_name #token_life_parameter_with_angles>
+                    {
+                        let (rest, value) = #parser_name(tokens)?;
+                        Ok((rest, value.clone()))
+                    }
+                }
+            }
+            _ => quote! {},
+        };
+        let kind_parser = quote! {
+            pub fn #parser_name<'t, #(#generic_params,)* W>(
+                tokens: &'t [W],
+            ) -> token_combinator::TokenParseResult<'t, W, &'t W>
+            where
+                W: Clone + token_combinator::UnwrapToken<#enum_name #ty_generics>
+            {
+                if tokens.is_empty() {
+                    return Err(token_combinator::TokenParseError::from_error_kind(
+                        token_combinator::TokenParseErrorKind::NotEnoughToken
+                    ))
+                }
+                if tokens[0].unwrap_token().kind() == #kind_name::#variant_name {
+                    Ok((&tokens[1..], &tokens[0]))
+                } else {
+                    Err(token_combinator::TokenParseError::from_error_kind(
+                        token_combinator::TokenParseErrorKind::Expects {
+                            expects: #lower_variant_name,
+                            found: tokens[0].clone()
+                        }
+                    ))
+                }
+            }
+        };
         let ret = quote! {
-            pub fn #parser_name<#token_life_parameter_with_comma W>(
-                tokens: & #token_life_parameter [W],
-            ) -> token_combinator::TokenParseResult<#token_life_parameter, W, #return_type_stream>
+            pub fn #parser_name<#parser_generics, W>(
+                tokens: &'t [W],
+            ) -> token_combinator::TokenParseResult<'t, W, #return_type_stream>
             where
                 W: Clone + token_combinator::UnwrapToken<#enum_name #token_life_parameter_with_angles>
             {
@@ -126,7 +175,9 @@ pub fn derive_parse_token(input: TokenStream) -> TokenStream {
                         errors: vec![
                             token_combinator::TokenParseErrorKind::NotEnoughToken
                         ],
-                        tokens_consumed: 0
+                        tokens_consumed: 0,
+                        span: None,
+                        fatal: false,
                     })
                 }
                 let wrapped_token = &tokens[0];
@@ -140,29 +191,91 @@ pub fn derive_parse_token(input: TokenStream) -> TokenStream {
                             found: wrapped_token.clone()
                         }],
                         tokens_consumed: 0,
+                        span: None,
+                        fatal: false,
                     })
                 }
             }
             };
-        ret
+        (quote! { #ret #value_parser }, kind_parser)
+    });
+    let (parser_functions, kind_parsers): (Vec<_>, Vec<_>) = parser_functions.unzip();
+
+    let kind_variants = enum_variants.iter().map(|(variant_name, _, _)| variant_name);
+    let kind_arms = enum_variants.iter().map(|(variant_name, fields, _)| {
+        let wildcard = match fields {
+            Fields::Unit => quote! {},
+            _ => quote! { (..) },
+        };
+        quote! { #enum_name::#variant_name #wildcard => #kind_name::#variant_name }
     });
 
     let expanded = quote! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum #kind_name {
+            #(#kind_variants),*
+        }
+
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            pub fn kind(&self) -> #kind_name {
+                match self {
+                    #(#kind_arms),*
+                }
+            }
+        }
+
         pub mod parser {
             use super::*;
             #(#parser_functions)*
         }
+
+        // matchers returning the whole (wrapped) token instead of its payload
+        pub mod kind {
+            use super::*;
+            #(#kind_parsers)*
+        }
     };
     TokenStream::from(expanded)
 }
 
-fn extract_enum_fields(data: &Data) -> Vec<(&Ident, &Fields)> {
+fn extract_enum_fields(data: &Data) -> Vec<(&Ident, &Fields, Option<String>)> {
     match *data {
         Data::Enum(ref data) => data
             .variants
             .iter()
-            .map(|variant| (&variant.ident, &variant.fields))
+            .map(|variant| {
+                (
+                    &variant.ident,
+                    &variant.fields,
+                    extract_expects(&variant.attrs),
+                )
+            })
             .collect::<Vec<_>>(),
         _ => panic!("invalid data"),
     }
 }
+
+// reads #[token(expects = "...")]
+fn extract_expects(attrs: &[Attribute]) -> Option<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("token"))
+        .find_map(|attr| match attr.parse_meta() {
+            Ok(Meta::List(list)) => list
+                .nested
+                .iter()
+                .map(|nested| match nested {
+                    NestedMeta::Meta(Meta::NameValue(name_value))
+                        if name_value.path.is_ident("expects") =>
+                    {
+                        match &name_value.lit {
+                            Lit::Str(s) => s.value(),
+                            _ => panic!("expects must be a string literal."),
+                        }
+                    }
+                    _ => panic!("unknown token attribute."),
+                })
+                .next(),
+            _ => panic!("token attribute must be like #[token(expects = \"...\")]."),
+        })
+}